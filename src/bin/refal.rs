@@ -1,13 +1,322 @@
-use refal::{compiler, data, vm};
-use std::{env, fs};
-
-fn main() -> data::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let refal_file = &args[1];
-    let goal = &args[2];
-    let input = fs::read_to_string(refal_file).unwrap();
-    let defs = compiler::compile(&input)?;
-    let result = vm::eval_main(&defs, goal);
-    println!("{:?}", result);
+use refal::coverage::{Coverage, SourceMap};
+use refal::host::StdHost;
+use refal::loader::Loader;
+use refal::profile::Profile;
+use refal::program::Program;
+use refal::repl::Repl;
+use refal::symbol::Symbol;
+use refal::{bytecode, c_backend, compiler, data, disassembler, formatter, rust_backend, vm};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+const USAGE: &str =
+    "usage: refal [-I DIR]... [-l MODULE]... [--profile] [--coverage LCOV] FILE GOAL [EXPR]
+       refal [-I DIR]... [-l MODULE]... --dump-commands FILE
+       refal [-I DIR]... [-l MODULE]... -o OUTPUT FILE
+       refal [-I DIR]... [-l MODULE]... --emit-rust DIR [--refal-path PATH] FILE
+       refal [-I DIR]... [-l MODULE]... --emit-c DIR FILE
+       refal [-I DIR]... [-l MODULE]... --repl [FILE]...
+       refal fmt [--check] FILE...
+
+FILE is Refal source or a program compiled with -o.
+--warnings prints the sentences never selected and the variables never used to stderr.
+--decision-trees matches the pattern adjacent sentences begin with in common only once, and
+  goes straight to the sentence beginning with the symbol of the argument.
+--profile prints the calls and time by function and sentence to stderr.
+--coverage writes the sentences selected to LCOV and summarizes them to stderr.
+--emit-rust writes a Rust crate to DIR, in which each function calls the runtime once per
+  command, evaluated by the loop of the VM; it depends on the refal crate at PATH, or on the
+  version of refal running.
+--emit-c writes a C program evaluating its argument as a goal, and its runtime, to DIR.
+--repl evaluates the expressions entered against FILE, keeping them in ~/.refal_history.
+fmt formats the files in place; with --check, it lists those not formatted and fails if any.";
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> data::Result<()> {
+    let mut search_paths = Vec::<PathBuf>::new();
+    let mut modules = Vec::<String>::new();
+    let mut positional = Vec::<String>::new();
+    let mut dump_commands = false;
+    let mut decision_trees = false;
+    let mut warnings = false;
+    let mut interactive = false;
+    let mut profile = None::<Profile>;
+    let mut coverage = None::<PathBuf>;
+    let mut output = None::<PathBuf>;
+    let mut emit_rust = None::<PathBuf>;
+    let mut refal_path = None::<String>;
+    let mut emit_c = None::<PathBuf>;
+    let mut args = env::args().skip(1).peekable();
+    if args.next_if_eq("fmt").is_some() {
+        return run_fmt(args);
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-I" => search_paths.push(PathBuf::from(option_value(&mut args))),
+            "-l" => modules.push(option_value(&mut args)),
+            "--dump-commands" => dump_commands = true,
+            "--decision-trees" => decision_trees = true,
+            "--warnings" => warnings = true,
+            "--repl" => interactive = true,
+            "--profile" => profile = Some(Profile::new()),
+            "--coverage" => coverage = Some(PathBuf::from(option_value(&mut args))),
+            "-o" => output = Some(PathBuf::from(option_value(&mut args))),
+            "--emit-rust" => emit_rust = Some(PathBuf::from(option_value(&mut args))),
+            "--refal-path" => refal_path = Some(option_value(&mut args)),
+            "--emit-c" => emit_c = Some(PathBuf::from(option_value(&mut args))),
+            _ => positional.push(arg),
+        }
+    }
+    let loader = Loader::new(search_paths);
+    if interactive {
+        let mut repl = Repl::new(loader, modules);
+        let files: Vec<_> = positional.iter().map(PathBuf::from).collect();
+        repl.load(&files)?;
+        run_repl(repl);
+        return Ok(());
+    }
+    if dump_commands || output.is_some() || emit_rust.is_some() || emit_c.is_some() {
+        let refal_file = match &positional[..] {
+            [file] => file,
+            _ => usage(),
+        };
+        let (mut defs, _) = load_program(&loader, Path::new(refal_file), &modules, warnings)?;
+        if decision_trees {
+            compiler::decision_trees(&mut defs);
+        }
+        if dump_commands {
+            print!("{}", disassembler::disassemble(&defs));
+        }
+        if let Some(output) = output {
+            bytecode::save(&output, &defs)?;
+        }
+        if let Some(dir) = emit_rust {
+            let refal = match refal_path {
+                // Cargo would take a relative path from the crate emitted.
+                Some(path) => {
+                    let path = env::current_dir().unwrap_or_default().join(path);
+                    rust_backend::Dependency::Path(path.display().to_string())
+                }
+                None => rust_backend::Dependency::Version(env!("CARGO_PKG_VERSION").to_string()),
+            };
+            emit_crate(&dir, Path::new(refal_file), &defs, &refal)?;
+        }
+        if let Some(dir) = emit_c {
+            emit_c_program(&dir, Path::new(refal_file), &defs)?;
+        }
+        return Ok(());
+    }
+    let (refal_file, goal) = match &positional[..] {
+        [file, goal] | [file, goal, _] => (file, goal),
+        _ => usage(),
+    };
+    let (mut defs, source_map) = load_program(&loader, Path::new(refal_file), &modules, warnings)?;
+    if decision_trees {
+        compiler::decision_trees(&mut defs);
+    }
+    let program = Program::link(defs)?;
+    let arg = match positional.get(2) {
+        Some(expression) => {
+            let module = goal.rsplit_once('.').map_or("", |(m, _)| m);
+            compiler::compile_expression(module, expression)?
+        }
+        None => vec![],
+    };
+    let result = if profile.is_none() && coverage.is_none() {
+        vm::eval_call(&program, goal, &arg)?
+    } else {
+        let name = vm::Object::Symbol(Symbol::intern(goal));
+        let call = [
+            &[vm::Object::FunBracketL, name],
+            &arg[..],
+            &[vm::Object::FunBracketR],
+        ]
+        .concat();
+        let mut host = StdHost::new();
+        let mut sentences = coverage.as_ref().map(|_| Coverage::new());
+        let result = vm::eval_expression(
+            &program,
+            &call,
+            &mut host,
+            vm::Limits::default(),
+            None,
+            profile.as_mut(),
+            sentences.as_mut(),
+        );
+        if let Some(profile) = &profile {
+            eprintln!("{}", profile.report());
+        }
+        if let (Some(path), Some(sentences)) = (&coverage, &sentences) {
+            fs::write(path, sentences.lcov(&source_map))
+                .map_err(|error| data::Error::Io(format!("{}: {}", path.display(), error)))?;
+            eprintln!("{}", sentences.summary(&source_map));
+        }
+        result?
+    };
+    if !result.is_empty() {
+        println!("{}", vm::render(&result));
+    }
+    Ok(())
+}
+
+/// Reads a compiled program as is, or compiles the source in `file` with `modules`.
+/// A compiled program comes without sources, so its source map is empty and it has
+/// no `warnings` to print.
+fn load_program(
+    loader: &Loader,
+    file: &Path,
+    modules: &[String],
+    warnings: bool,
+) -> data::Result<(HashMap<String, Vec<data::Command>>, SourceMap)> {
+    match fs::read(file) {
+        Ok(bytes) if bytecode::is_bytecode(&bytes) => {
+            Ok((bytecode::read(&bytes)?, SourceMap::new()))
+        }
+        _ => {
+            let loaded = loader.load_with_source_map(file, modules)?;
+            if warnings {
+                for warning in &loaded.warnings {
+                    eprintln!("{}", warning);
+                }
+            }
+            Ok((loaded.defs, loaded.source_map))
+        }
+    }
+}
+
+/// Writes the crate `rust_backend` generates for `defs` to `dir`, named after `file`,
+/// depending on `refal`.
+fn emit_crate(
+    dir: &Path,
+    file: &Path,
+    defs: &HashMap<String, Vec<data::Command>>,
+    refal: &rust_backend::Dependency,
+) -> data::Result<()> {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = stem
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    write_file(
+        &dir.join("Cargo.toml"),
+        &rust_backend::manifest(&name, refal),
+    )?;
+    write_file(
+        &dir.join("src").join("lib.rs"),
+        &rust_backend::generate(defs),
+    )
+}
+
+/// Writes the program `c_backend` generates for `defs` to `dir`, named after `file`,
+/// with the runtime.
+fn emit_c_program(
+    dir: &Path,
+    file: &Path,
+    defs: &HashMap<String, Vec<data::Command>>,
+) -> data::Result<()> {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    write_file(&dir.join("refal.h"), c_backend::RUNTIME_HEADER)?;
+    write_file(&dir.join("refal.c"), c_backend::RUNTIME)?;
+    write_file(&dir.join(format!("{}.c", stem)), &c_backend::generate(defs))
+}
+
+fn write_file(path: &Path, text: &str) -> data::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(path, text))
+        .map_err(|error| data::Error::Io(format!("{}: {}", path.display(), error)))
+}
+
+/// Formats `files` in place, or with `--check` only reports those not formatted.
+fn run_fmt(args: impl Iterator<Item = String>) -> data::Result<()> {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+    let mut unformatted = false;
+    for file in &files {
+        let text = fs::read_to_string(file)
+            .map_err(|error| data::Error::Io(format!("{}: {}", file.display(), error)))?;
+        let formatted = formatter::format_file(&file.display().to_string(), &text)?;
+        if formatted == text {
+            continue;
+        }
+        if check {
+            eprintln!("{}: not formatted", file.display());
+            unformatted = true;
+        } else {
+            write_file(file, &formatted)?;
+        }
+    }
+    if unformatted {
+        process::exit(1);
+    }
     Ok(())
 }
+
+/// Reads lines from the standard input until `:quit` or the end of input, with line editing.
+/// The lines are kept in `~/.refal_history` and recalled in the next sessions.
+fn run_repl(mut repl: Repl) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    if let Some(home) = env::var_os("HOME") {
+        if let Err(error) = repl.open_history(Path::new(&home).join(".refal_history")) {
+            eprintln!("{}", error);
+        }
+    }
+    for line in repl.history() {
+        editor.add_history_entry(line.as_str()).ok();
+    }
+    let mut host = StdHost::new();
+    println!("type :help for help");
+    loop {
+        let line = match editor.readline("refal> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("{}", error);
+                break;
+            }
+        };
+        editor.add_history_entry(line.as_str()).ok();
+        if matches!(line.trim(), ":quit" | ":q") {
+            break;
+        }
+        match repl.execute(&line, &mut host) {
+            Ok(output) if output.is_empty() => (),
+            Ok(output) => println!("{}", output),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+fn option_value(args: &mut impl Iterator<Item = String>) -> String {
+    args.next().unwrap_or_else(|| usage())
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
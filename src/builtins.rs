@@ -0,0 +1,435 @@
+#[cfg(test)]
+mod tests;
+
+use crate::host::{Host, Mode};
+use crate::runtime::{display, Object};
+use std::cmp::Ordering;
+
+/// A native function: maps the argument of a call to its result,
+/// or `None` if the argument is not in the domain of the function.
+pub type Builtin = fn(&mut dyn Host, &[Object]) -> Option<Vec<Object>>;
+
+/// The built-in functions by name.
+static BUILTINS: [(&str, Builtin); 13] = [
+    ("Add", add),
+    ("Sub", sub),
+    ("Mul", mul),
+    ("Div", div),
+    ("Mod", modulo),
+    ("Compare", compare),
+    ("Prout", prout),
+    ("Print", print),
+    ("Card", card),
+    ("Open", open),
+    ("Get", get),
+    ("Put", put),
+    ("Close", close),
+];
+
+/// Finds the built-in function called `name`.
+pub fn lookup(name: &str) -> Option<Builtin> {
+    all().iter().find(|(n, _)| *n == name).map(|&(_, f)| f)
+}
+
+/// All built-in functions with their names.
+pub fn all() -> &'static [(&'static str, Builtin)] {
+    &BUILTINS
+}
+
+fn add(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    Some(x.add(&y).to_objects())
+}
+
+fn sub(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    Some(x.add(&y.negate()).to_objects())
+}
+
+fn mul(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    Some(x.mul(&y).to_objects())
+}
+
+fn div(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    let (quotient, _) = x.div_rem(&y)?;
+    Some(quotient.to_objects())
+}
+
+fn modulo(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    let (_, remainder) = x.div_rem(&y)?;
+    Some(remainder.to_objects())
+}
+
+fn compare(_: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (x, y) = operands(arg)?;
+    let sign = match x.cmp(&y) {
+        Ordering::Less => '-',
+        Ordering::Equal => '0',
+        Ordering::Greater => '+',
+    };
+    Some(vec![Object::Char(sign)])
+}
+
+fn prout(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    host.write_line(&display(arg)).ok()?;
+    Some(Vec::new())
+}
+
+fn print(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    host.write_line(&display(arg)).ok()?;
+    Some(arg.to_vec())
+}
+
+fn card(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    if !arg.is_empty() {
+        return None;
+    }
+    Some(line_objects(host.read_line()))
+}
+
+/// `<Open s.Mode s.Channel e.FileName>`, where the mode is `'r'`, `'w'` or `'a'`.
+fn open(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let mode = match arg.first()? {
+        Object::Char('r' | 'R') => Mode::Read,
+        Object::Char('w' | 'W') => Mode::Write,
+        Object::Char('a' | 'A') => Mode::Append,
+        _ => return None,
+    };
+    let channel = file_channel(arg.get(1)?)?;
+    let path = text(&arg[2..])?;
+    host.open(channel, &path, mode).ok()?;
+    Some(Vec::new())
+}
+
+/// `<Get s.Channel>` reads a line from a file, or from the console for channel 0.
+fn get(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let line = match arg {
+        [Object::Number(0)] => host.read_line(),
+        [channel] => host.get(file_channel(channel)?).ok()?,
+        _ => return None,
+    };
+    Some(line_objects(line))
+}
+
+/// `<Put s.Channel e.Expr>` writes a line to a file, or to the console for channel 0.
+fn put(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    let (channel, expr) = arg.split_first()?;
+    let line = display(expr);
+    match channel {
+        Object::Number(0) => host.write_line(&line).ok()?,
+        channel => host.put(file_channel(channel)?, &line).ok()?,
+    }
+    Some(expr.to_vec())
+}
+
+fn close(host: &mut dyn Host, arg: &[Object]) -> Option<Vec<Object>> {
+    match arg {
+        [channel] => host.close(file_channel(channel)?),
+        _ => return None,
+    }
+    Some(Vec::new())
+}
+
+/// Channel 0 is the console, files are bound to channels 1 to 39.
+fn file_channel(object: &Object) -> Option<u32> {
+    match object {
+        Object::Number(n) if (1..40).contains(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// The characters of a line read, or 0 at the end of input.
+fn line_objects(line: Option<String>) -> Vec<Object> {
+    match line {
+        Some(line) => line.chars().map(Object::Char).collect(),
+        None => vec![Object::Number(0)],
+    }
+}
+
+/// Concatenates the characters and other symbols of `objects`, e.g. to get a file name.
+fn text(objects: &[Object]) -> Option<String> {
+    let mut text = String::new();
+    for object in objects {
+        match object {
+            Object::Symbol(s) => text.push_str(s.as_str()),
+            Object::Char(c) => text.push(*c),
+            Object::Number(n) => text.push_str(&n.to_string()),
+            _ => return None,
+        }
+    }
+    Some(text)
+}
+
+/// Splits the argument of an arithmetic function into its operands,
+/// written either as `(e.N1) e.N2` or as `s.N1 e.N2`.
+fn operands(arg: &[Object]) -> Option<(Int, Int)> {
+    let (first, second) = match arg.first()? {
+        Object::StrBracketL => {
+            let end = arg.iter().position(|o| *o == Object::StrBracketR)?;
+            (&arg[1..end], &arg[end + 1..])
+        }
+        _ => arg.split_at(1),
+    };
+    Some((Int::parse(first)?, Int::parse(second)?))
+}
+
+/// An arbitrary-precision integer: a sign and a magnitude in base 2^32,
+/// least significant macrodigit first and without trailing zeros.
+#[derive(Debug, PartialEq, Eq)]
+struct Int {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl Int {
+    /// Reads a sequence of macrodigits, most significant first, with an optional sign.
+    fn parse(objects: &[Object]) -> Option<Int> {
+        let (negative, digits) = match objects.first() {
+            Some(Object::Char('-')) => (true, &objects[1..]),
+            Some(Object::Char('+')) => (false, &objects[1..]),
+            _ => (false, objects),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut magnitude = digits
+            .iter()
+            .rev()
+            .map(|o| match o {
+                Object::Number(n) => Some(*n),
+                _ => None,
+            })
+            .collect::<Option<Vec<u32>>>()?;
+        trim(&mut magnitude);
+        Some(Int::new(negative, magnitude))
+    }
+
+    fn new(negative: bool, magnitude: Vec<u32>) -> Int {
+        let negative = negative && !magnitude.is_empty();
+        Int {
+            negative,
+            magnitude,
+        }
+    }
+
+    fn to_objects(&self) -> Vec<Object> {
+        let mut objects = Vec::new();
+        if self.negative {
+            objects.push(Object::Char('-'));
+        }
+        if self.magnitude.is_empty() {
+            objects.push(Object::Number(0));
+        }
+        objects.extend(self.magnitude.iter().rev().map(|n| Object::Number(*n)));
+        objects
+    }
+
+    fn negate(self) -> Int {
+        Int::new(!self.negative, self.magnitude)
+    }
+
+    fn add(&self, other: &Int) -> Int {
+        if self.negative == other.negative {
+            return Int::new(
+                self.negative,
+                add_magnitudes(&self.magnitude, &other.magnitude),
+            );
+        }
+        match compare_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Int::new(
+                other.negative,
+                sub_magnitudes(&other.magnitude, &self.magnitude),
+            ),
+            _ => Int::new(
+                self.negative,
+                sub_magnitudes(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+
+    fn mul(&self, other: &Int) -> Int {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &x) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &y) in other.magnitude.iter().enumerate() {
+                let t = product[i + j] as u64 + x as u64 * y as u64 + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        trim(&mut product);
+        Int::new(self.negative != other.negative, product)
+    }
+
+    /// Divides truncating toward zero, so the remainder has the sign of the dividend.
+    /// Returns `None` on division by zero.
+    fn div_rem(&self, other: &Int) -> Option<(Int, Int)> {
+        if other.magnitude.is_empty() {
+            return None;
+        }
+        let (mut quotient, mut remainder) = match other.magnitude[..] {
+            [divisor] => {
+                let (quotient, remainder) = div_rem_digit(&self.magnitude, divisor);
+                (quotient, vec![remainder])
+            }
+            _ if compare_magnitudes(&self.magnitude, &other.magnitude) == Ordering::Less => {
+                (Vec::new(), self.magnitude.clone())
+            }
+            _ => div_rem_magnitudes(&self.magnitude, &other.magnitude),
+        };
+        trim(&mut quotient);
+        trim(&mut remainder);
+        Some((
+            Int::new(self.negative != other.negative, quotient),
+            Int::new(self.negative, remainder),
+        ))
+    }
+}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Int) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Int {
+    fn cmp(&self, other: &Int) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+fn compare_magnitudes(x: &[u32], y: &[u32]) -> Ordering {
+    x.len()
+        .cmp(&y.len())
+        .then_with(|| x.iter().rev().cmp(y.iter().rev()))
+}
+
+fn add_magnitudes(x: &[u32], y: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(x.len().max(y.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..x.len().max(y.len()) {
+        let t = *x.get(i).unwrap_or(&0) as u64 + *y.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+/// Subtracts `y` from `x`, which must not be smaller.
+fn sub_magnitudes(x: &[u32], y: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(x.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in x.iter().enumerate() {
+        let mut t = digit as i64 - *y.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if t < 0 {
+            t += 1 << 32;
+            borrow = 1;
+        }
+        difference.push(t as u32);
+    }
+    trim(&mut difference);
+    difference
+}
+
+/// Divides `x` by a single macrodigit.
+fn div_rem_digit(x: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; x.len()];
+    let mut remainder = 0u64;
+    for (i, &digit) in x.iter().enumerate().rev() {
+        let t = remainder << 32 | digit as u64;
+        quotient[i] = (t / divisor as u64) as u32;
+        remainder = t % divisor as u64;
+    }
+    (quotient, remainder as u32)
+}
+
+/// Divides `x` by `y`, of two macrodigits or more and not greater than `x`, a macrodigit of
+/// the quotient at a time with Knuth's algorithm D (The Art of Computer Programming, 4.3.1).
+fn div_rem_magnitudes(x: &[u32], y: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    // Normalizing makes the top macrodigit of the divisor at least 2^31, so that the
+    // estimate of each macrodigit of the quotient is at most 2 too large.
+    let shift = y[y.len() - 1].leading_zeros();
+    let mut v = shift_left(y, shift);
+    v.pop();
+    let mut u = shift_left(x, shift);
+    let n = v.len();
+    let (top, next) = (v[n - 1] as u64, v[n - 2] as u64);
+    let mut quotient = vec![0u32; u.len() - n];
+    for j in (0..quotient.len()).rev() {
+        let t = (u[j + n] as u64) << 32 | u[j + n - 1] as u64;
+        let (mut estimate, mut rest) = (t / top, t % top);
+        while estimate >> 32 != 0 || estimate * next > (rest << 32 | u[j + n - 2] as u64) {
+            estimate -= 1;
+            rest += top;
+            if rest >> 32 != 0 {
+                break;
+            }
+        }
+        // Subtracts `estimate * v` from the digits of `u` at `j`.
+        let (mut borrow, mut carry) = (0i64, 0u64);
+        for i in 0..n {
+            let product = estimate * v[i] as u64 + carry;
+            carry = product >> 32;
+            let t = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = i64::from(t < 0);
+        }
+        let t = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = t as u32;
+        if t < 0 {
+            // The estimate was one too large, which is rare: adds `v` back.
+            estimate -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = estimate as u32;
+    }
+    (quotient, shift_right(&u[..n], shift))
+}
+
+/// Computes `x * 2^shift`, one macrodigit longer than `x`, for `shift` below 32.
+fn shift_left(x: &[u32], shift: u32) -> Vec<u32> {
+    let mut shifted = Vec::with_capacity(x.len() + 1);
+    let mut carry = 0u32;
+    for &digit in x {
+        let t = (digit as u64) << shift;
+        shifted.push(t as u32 | carry);
+        carry = (t >> 32) as u32;
+    }
+    shifted.push(carry);
+    shifted
+}
+
+/// Computes `x / 2^shift`, for `shift` below 32.
+fn shift_right(x: &[u32], shift: u32) -> Vec<u32> {
+    (0..x.len())
+        .map(|i| {
+            let high = *x.get(i + 1).unwrap_or(&0) as u64;
+            ((high << 32 | x[i] as u64) >> shift) as u32
+        })
+        .collect()
+}
@@ -0,0 +1,134 @@
+use crate::builtins::lookup;
+use crate::host::MemoryHost;
+use crate::runtime::Object;
+use crate::runtime::Object::*;
+use crate::symbol;
+
+#[cfg(test)]
+fn call(name: &str, arg: &[Object]) -> Option<Vec<Object>> {
+    lookup(name).unwrap()(&mut MemoryHost::default(), arg)
+}
+
+#[cfg(test)]
+fn minus() -> Object {
+    Char('-')
+}
+
+#[test]
+fn test_add() {
+    assert_eq!(call("Add", &[Number(2), Number(3)]), Some(vec![Number(5)]));
+    assert_eq!(
+        call("Add", &[Number(u32::MAX), Number(1)]),
+        Some(vec![Number(1), Number(0)])
+    );
+    assert_eq!(
+        call(
+            "Add",
+            &[StrBracketL, minus(), Number(7), StrBracketR, Number(3)]
+        ),
+        Some(vec![minus(), Number(4)])
+    );
+}
+
+#[test]
+fn test_sub() {
+    assert_eq!(
+        call("Sub", &[Number(3), Number(5)]),
+        Some(vec![minus(), Number(2)])
+    );
+    assert_eq!(
+        call(
+            "Sub",
+            &[StrBracketL, Number(1), Number(0), StrBracketR, Number(1)]
+        ),
+        Some(vec![Number(u32::MAX)])
+    );
+    assert_eq!(call("Sub", &[Number(4), Number(4)]), Some(vec![Number(0)]));
+}
+
+#[test]
+fn test_mul() {
+    assert_eq!(
+        call("Mul", &[Number(u32::MAX), Number(u32::MAX)]),
+        Some(vec![Number(u32::MAX - 1), Number(1)])
+    );
+    assert_eq!(
+        call("Mul", &[Number(6), minus(), Number(7)]),
+        Some(vec![minus(), Number(42)])
+    );
+    assert_eq!(
+        call("Mul", &[Number(0), minus(), Number(7)]),
+        Some(vec![Number(0)])
+    );
+}
+
+#[test]
+fn test_div_mod() {
+    let big = [StrBracketL, Number(1), Number(5), StrBracketR, Number(2)];
+    assert_eq!(call("Div", &big), Some(vec![Number((1 << 31) + 2)]));
+    assert_eq!(call("Mod", &big), Some(vec![Number(1)]));
+    let negative = [StrBracketL, minus(), Number(7), StrBracketR, Number(2)];
+    assert_eq!(call("Div", &negative), Some(vec![minus(), Number(3)]));
+    assert_eq!(call("Mod", &negative), Some(vec![minus(), Number(1)]));
+    assert_eq!(call("Div", &[Number(7), Number(0)]), None);
+    assert_eq!(call("Mod", &[Number(7), Number(0)]), None);
+}
+
+#[test]
+fn test_div_mod_many_digits() {
+    // Macrodigits at the edges of the estimates of the quotient, most significant first.
+    let edges = [0, 1, 0x7fff_ffff, 0x8000_0000, u32::MAX - 1, u32::MAX];
+    let mut seed = 1u32;
+    let mut number = |length: usize| -> Vec<Object> {
+        (0..length)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                Number(edges[(seed >> 16) as usize % edges.len()].max(u32::from(i == 0)))
+            })
+            .collect()
+    };
+    let bracketed = |objects: &[Object]| [&[StrBracketL], objects, &[StrBracketR]].concat();
+    for &(length, divisor_length) in [(40, 20), (12, 2), (7, 6), (30, 3), (3, 1)]
+        .iter()
+        .cycle()
+        .take(50)
+    {
+        let (quotient, divisor) = (number(length - divisor_length), number(divisor_length));
+        let remainder = match divisor_length {
+            1 => vec![Number(0)],
+            _ => number(divisor_length - 1),
+        };
+        let product = call("Mul", &[bracketed(&quotient), divisor.clone()].concat()).unwrap();
+        let dividend = call("Add", &[bracketed(&product), remainder.clone()].concat()).unwrap();
+        let arg = [bracketed(&dividend), divisor].concat();
+        assert_eq!(call("Div", &arg), Some(quotient));
+        assert_eq!(call("Mod", &arg), Some(remainder));
+    }
+}
+
+#[test]
+fn test_compare() {
+    let sign = |c: char| Some(vec![Char(c)]);
+    assert_eq!(call("Compare", &[Number(1), Number(2)]), sign('-'));
+    assert_eq!(call("Compare", &[Number(2), Number(2)]), sign('0'));
+    assert_eq!(call("Compare", &[Number(0), minus(), Number(2)]), sign('+'));
+    assert_eq!(
+        call(
+            "Compare",
+            &[StrBracketL, Number(1), Number(0), StrBracketR, Number(7)]
+        ),
+        sign('+')
+    );
+}
+
+#[test]
+fn test_invalid_arguments() {
+    assert_eq!(call("Add", &[]), None);
+    assert_eq!(call("Add", &[Number(1)]), None);
+    assert_eq!(
+        call("Add", &[Symbol(symbol::Symbol::intern("A")), Number(1)]),
+        None
+    );
+    assert_eq!(call("Add", &[StrBracketL, Number(1), Number(2)]), None);
+    assert!(lookup("Foo").is_none());
+}
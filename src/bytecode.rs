@@ -0,0 +1,306 @@
+#[cfg(test)]
+mod tests;
+
+use crate::data::{Command, Error, Result};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+/// The first bytes of a compiled program.
+pub const MAGIC: &[u8; 4] = b"RFLC";
+/// The version of the format, bumped whenever the encoding of commands changes.
+pub const VERSION: u16 = 2;
+
+/// Encodes a definition table. Little-endian layout:
+///
+/// - header: `MAGIC`, `VERSION` as `u16`;
+/// - symbol table: a `u32` count, then each string as a `u32` byte length and UTF-8 bytes;
+/// - functions sorted by name: a `u32` count, then for each function the symbol table index
+///   of its name, a `u32` command count and the commands, each an opcode byte followed by
+///   a `u32` operand if it has one (a symbol table index for symbols), the number of entries
+///   of a `SwitchSymbolL` being followed by a symbol and an address for each;
+/// - the Adler-32 checksum of all the bytes before it as `u32`.
+pub fn write(defs: &HashMap<String, Vec<Command>>) -> Vec<u8> {
+    let mut names: Vec<_> = defs.keys().collect();
+    names.sort();
+    let mut symbols = SymbolTable::default();
+    let mut code = Vec::new();
+    put_u32(&mut code, names.len());
+    for name in names {
+        put_u32(&mut code, symbols.index(name));
+        put_u32(&mut code, defs[name].len());
+        for command in &defs[name] {
+            let (opcode, operand) = encode(command, &mut symbols);
+            code.push(opcode);
+            if let Some(operand) = operand {
+                put_u32(&mut code, operand);
+            }
+            if let Command::SwitchSymbolL(table) = command {
+                for (symbol, target) in table {
+                    put_u32(&mut code, symbols.index(symbol.as_str()));
+                    put_u32(&mut code, *target);
+                }
+            }
+        }
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    put_u32(&mut bytes, symbols.strings.len());
+    for string in &symbols.strings {
+        put_u32(&mut bytes, string.len());
+        bytes.extend(string.as_bytes());
+    }
+    bytes.extend(code);
+    let checksum = adler32(&bytes);
+    bytes.extend(checksum.to_le_bytes());
+    bytes
+}
+
+/// Decodes a definition table written by `write`.
+pub fn read(bytes: &[u8]) -> Result<HashMap<String, Vec<Command>>> {
+    if bytes.len() < MAGIC.len() + 2 + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a compiled Refal program"));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    if adler32(body).to_le_bytes() != checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+    let mut reader = Reader {
+        bytes: body,
+        position: MAGIC.len(),
+    };
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "unsupported version {}, expected {}",
+            version, VERSION
+        )));
+    }
+
+    let mut symbols = Vec::new();
+    for _ in 0..reader.u32()? {
+        let length = reader.u32()? as usize;
+        let string = String::from_utf8(reader.take(length)?.to_vec())
+            .map_err(|_| invalid("symbol is not valid UTF-8"))?;
+        symbols.push(string);
+    }
+    let mut defs = HashMap::new();
+    for _ in 0..reader.u32()? {
+        let name = reader.symbol(&symbols)?;
+        let count = reader.u32()?;
+        let mut commands = Vec::new();
+        for _ in 0..count {
+            commands.push(reader.command(&symbols)?);
+        }
+        defs.insert(name, commands);
+    }
+    if reader.position != body.len() {
+        return Err(invalid("trailing bytes"));
+    }
+    Ok(defs)
+}
+
+/// Writes the compiled program `defs` to `path`.
+pub fn save(path: &Path, defs: &HashMap<String, Vec<Command>>) -> Result<()> {
+    fs::write(path, write(defs))
+        .map_err(|error| Error::Io(format!("{}: {}", path.display(), error)))
+}
+
+/// Reads a compiled program from `path`, ready to be linked by `program::Program::link`.
+pub fn load(path: &Path) -> Result<HashMap<String, Vec<Command>>> {
+    let bytes =
+        fs::read(path).map_err(|error| Error::Io(format!("{}: {}", path.display(), error)))?;
+    read(&bytes)
+}
+
+/// Whether `bytes` start like a compiled program rather than source text.
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Bytecode(message.to_string())
+}
+
+/// The strings of a program, each stored once and referred to by index.
+#[derive(Default)]
+struct SymbolTable {
+    strings: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl SymbolTable {
+    fn index(&mut self, string: &str) -> usize {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+        self.strings.push(string.to_string());
+        self.indices
+            .insert(string.to_string(), self.strings.len() - 1);
+        self.strings.len() - 1
+    }
+}
+
+/// Operands are stored as `u32`; projection numbers and counts never come close to the limit.
+fn put_u32(bytes: &mut Vec<u8>, value: usize) {
+    let value = u32::try_from(value).expect("operand does not fit in 32 bits");
+    bytes.extend(value.to_le_bytes());
+}
+
+fn encode(command: &Command, symbols: &mut SymbolTable) -> (u8, Option<usize>) {
+    match command {
+        Command::MatchStart => (0, None),
+        Command::MatchEmpty => (1, None),
+        Command::MatchSymbolL(s) => (2, Some(symbols.index(s.as_str()))),
+        Command::MatchSymbolR(s) => (3, Some(symbols.index(s.as_str()))),
+        Command::MatchNumberL(n) => (4, Some(*n as usize)),
+        Command::MatchNumberR(n) => (5, Some(*n as usize)),
+        Command::MatchCharL(c) => (6, Some(*c as usize)),
+        Command::MatchCharR(c) => (7, Some(*c as usize)),
+        Command::MatchStrBracketL => (8, None),
+        Command::MatchStrBracketR => (9, None),
+        Command::MatchSVarL => (10, None),
+        Command::MatchSVarR => (11, None),
+        Command::MatchSVarLProj(n) => (12, Some(*n)),
+        Command::MatchSVarRProj(n) => (13, Some(*n)),
+        Command::MatchTVarL => (14, None),
+        Command::MatchTVarR => (15, None),
+        Command::MatchEVar => (16, None),
+        Command::MatchEVarPrepare => (17, None),
+        Command::MatchEVarLengthen => (18, None),
+        Command::MatchEVarLProj(n) => (19, Some(*n)),
+        Command::MatchEVarRProj(n) => (20, Some(*n)),
+        Command::MatchMoveBorderL(n) => (21, Some(*n)),
+        Command::MatchMoveBorderR(n) => (22, Some(*n)),
+        Command::SetupTransition(n) => (23, Some(*n)),
+        Command::ConstrainLengthen(n) => (24, Some(*n)),
+        Command::ConditionStart => (25, None),
+        Command::ConditionEvaluate => (26, None),
+        Command::RewriteStart => (27, None),
+        Command::InsertStrBracketL => (28, None),
+        Command::InsertStrBracketR => (29, None),
+        Command::InsertFunBracketL => (30, None),
+        Command::InsertFunBracketR => (31, None),
+        Command::InsertSymbol(s) => (32, Some(symbols.index(s.as_str()))),
+        Command::InsertNumber(n) => (33, Some(*n as usize)),
+        Command::InsertChar(c) => (34, Some(*c as usize)),
+        Command::CopySymbol(n) => (35, Some(*n)),
+        Command::CopyExpr(n) => (36, Some(*n)),
+        Command::TransplantObject(n) => (37, Some(*n)),
+        Command::TransplantExpr(n) => (38, Some(*n)),
+        Command::RewriteFinalize => (39, None),
+        Command::SwitchSymbolL(table) => (40, Some(table.len())),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8]> {
+        let end = self.position.saturating_add(length);
+        let taken = self
+            .bytes
+            .get(self.position..end)
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn symbol(&mut self, symbols: &[String]) -> Result<String> {
+        let index = self.u32()? as usize;
+        symbols
+            .get(index)
+            .cloned()
+            .ok_or_else(|| invalid(&format!("symbol index {} out of range", index)))
+    }
+
+    fn char(&mut self) -> Result<char> {
+        let code = self.u32()?;
+        char::from_u32(code).ok_or_else(|| invalid(&format!("invalid character {:#x}", code)))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn command(&mut self, symbols: &[String]) -> Result<Command> {
+        let command = match self.byte()? {
+            0 => Command::MatchStart,
+            1 => Command::MatchEmpty,
+            2 => Command::MatchSymbolL(Symbol::intern(&self.symbol(symbols)?)),
+            3 => Command::MatchSymbolR(Symbol::intern(&self.symbol(symbols)?)),
+            4 => Command::MatchNumberL(self.u32()?),
+            5 => Command::MatchNumberR(self.u32()?),
+            6 => Command::MatchCharL(self.char()?),
+            7 => Command::MatchCharR(self.char()?),
+            8 => Command::MatchStrBracketL,
+            9 => Command::MatchStrBracketR,
+            10 => Command::MatchSVarL,
+            11 => Command::MatchSVarR,
+            12 => Command::MatchSVarLProj(self.usize()?),
+            13 => Command::MatchSVarRProj(self.usize()?),
+            14 => Command::MatchTVarL,
+            15 => Command::MatchTVarR,
+            16 => Command::MatchEVar,
+            17 => Command::MatchEVarPrepare,
+            18 => Command::MatchEVarLengthen,
+            19 => Command::MatchEVarLProj(self.usize()?),
+            20 => Command::MatchEVarRProj(self.usize()?),
+            21 => Command::MatchMoveBorderL(self.usize()?),
+            22 => Command::MatchMoveBorderR(self.usize()?),
+            23 => Command::SetupTransition(self.usize()?),
+            24 => Command::ConstrainLengthen(self.usize()?),
+            25 => Command::ConditionStart,
+            26 => Command::ConditionEvaluate,
+            27 => Command::RewriteStart,
+            28 => Command::InsertStrBracketL,
+            29 => Command::InsertStrBracketR,
+            30 => Command::InsertFunBracketL,
+            31 => Command::InsertFunBracketR,
+            32 => Command::InsertSymbol(Symbol::intern(&self.symbol(symbols)?)),
+            33 => Command::InsertNumber(self.u32()?),
+            34 => Command::InsertChar(self.char()?),
+            35 => Command::CopySymbol(self.usize()?),
+            36 => Command::CopyExpr(self.usize()?),
+            37 => Command::TransplantObject(self.usize()?),
+            38 => Command::TransplantExpr(self.usize()?),
+            39 => Command::RewriteFinalize,
+            40 => {
+                let mut table = Vec::new();
+                for _ in 0..self.u32()? {
+                    let symbol = Symbol::intern(&self.symbol(symbols)?);
+                    table.push((symbol, self.usize()?));
+                }
+                Command::SwitchSymbolL(table)
+            }
+            opcode => return Err(invalid(&format!("unknown opcode {}", opcode))),
+        };
+        Ok(command)
+    }
+}
+
+/// The Adler-32 checksum of `bytes`.
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,120 @@
+use crate::bytecode::{load, read, save, write, MAGIC};
+use crate::compiler::compile;
+use crate::data::{Command, Error};
+use crate::program::Program;
+use crate::symbol::Symbol;
+use crate::vm::{eval_main, Object};
+use std::collections::HashMap;
+use std::fs;
+
+#[test]
+fn test_round_trip() {
+    let defs = compile(
+        "$MODULE T;
+        Go { = <Rev 'ab' 1 (X)>; }
+        Rev { $t.1 $e.2, <Rev $e.2> : $e.3 = $e.3 $t.1; = ; }",
+    )
+    .unwrap();
+    let bytes = write(&defs);
+    assert!(bytes.starts_with(MAGIC));
+    assert_eq!(read(&bytes), Ok(defs.clone()));
+    assert_eq!(write(&defs), bytes);
+
+    let path = std::env::temp_dir().join(format!("refal-bytecode-{}.rfc", std::process::id()));
+    save(&path, &defs).unwrap();
+    let loaded = load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        eval_main(&Program::new(loaded), "T.Go"),
+        Ok(vec![
+            Object::StrBracketL,
+            Object::Symbol(Symbol::intern("X")),
+            Object::StrBracketR,
+            Object::Number(1),
+            Object::Char('b'),
+            Object::Char('a'),
+        ])
+    );
+}
+
+#[test]
+fn test_all_commands() {
+    let commands = vec![
+        Command::SetupTransition(3),
+        Command::SwitchSymbolL(vec![
+            (Symbol::intern("B"), 2),
+            (Symbol::intern("Überall"), 4),
+        ]),
+        Command::MatchSymbolL(Symbol::intern("A")),
+        Command::MatchSymbolR(Symbol::intern("Überall")),
+        Command::MatchNumberL(u32::MAX),
+        Command::MatchNumberR(0),
+        Command::MatchCharL('\n'),
+        Command::MatchCharR('λ'),
+        Command::MatchStrBracketL,
+        Command::MatchStrBracketR,
+        Command::MatchSVarL,
+        Command::MatchSVarR,
+        Command::MatchSVarLProj(1),
+        Command::MatchSVarRProj(2),
+        Command::MatchTVarL,
+        Command::MatchTVarR,
+        Command::MatchEVar,
+        Command::MatchEVarPrepare,
+        Command::MatchEVarLengthen,
+        Command::MatchEVarLProj(3),
+        Command::MatchEVarRProj(4),
+        Command::MatchMoveBorderL(5),
+        Command::MatchMoveBorderR(6),
+        Command::ConstrainLengthen(7),
+        Command::ConditionStart,
+        Command::ConditionEvaluate,
+        Command::RewriteStart,
+        Command::InsertStrBracketL,
+        Command::InsertStrBracketR,
+        Command::InsertFunBracketL,
+        Command::InsertFunBracketR,
+        Command::InsertSymbol(Symbol::intern("A")),
+        Command::InsertNumber(42),
+        Command::InsertChar('\''),
+        Command::CopySymbol(8),
+        Command::CopyExpr(9),
+        Command::TransplantObject(10),
+        Command::TransplantExpr(11),
+        Command::RewriteFinalize,
+        Command::MatchStart,
+        Command::MatchEmpty,
+    ];
+    let mut defs = HashMap::new();
+    defs.insert(String::from("T.F"), commands);
+    defs.insert(String::from("A"), vec![]);
+    assert_eq!(read(&write(&defs)), Ok(defs));
+}
+
+#[test]
+fn test_invalid() {
+    let mut defs = HashMap::new();
+    defs.insert(String::from("T.F"), vec![Command::MatchEmpty]);
+    let bytes = write(&defs);
+    let invalid = |message: &str| Err(Error::Bytecode(String::from(message)));
+
+    assert_eq!(read(b"$MODULE T;"), invalid("not a compiled Refal program"));
+    let mut corrupt = bytes.clone();
+    corrupt[8] ^= 1;
+    assert_eq!(read(&corrupt), invalid("checksum mismatch"));
+    assert_eq!(
+        read(&bytes[..bytes.len() - 1]),
+        invalid("checksum mismatch")
+    );
+
+    let mut future = bytes.clone();
+    future[4] = 3;
+    let checksum = super::adler32(&future[..future.len() - 4]);
+    let end = future.len() - 4;
+    future[end..].copy_from_slice(&checksum.to_le_bytes());
+    assert_eq!(read(&future), invalid("unsupported version 3, expected 2"));
+    assert_eq!(
+        read(&future).unwrap_err().to_string(),
+        "invalid compiled program: unsupported version 3, expected 2"
+    );
+}
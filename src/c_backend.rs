@@ -0,0 +1,223 @@
+#[cfg(test)]
+mod tests;
+
+use crate::builtins;
+use crate::compiler::{arm_starts, Symbols};
+use crate::data::{Command, Object};
+use crate::printer::render;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The header of the C runtime programs `generate` makes are linked with.
+pub const RUNTIME_HEADER: &str = include_str!("c_backend/refal.h");
+
+/// The C runtime: the view, the commands, the evaluation loop and the built-in functions.
+pub const RUNTIME: &str = include_str!("c_backend/refal.c");
+
+/// Generates a C99 program implementing the functions of a definition table, to link with
+/// `RUNTIME`. The program evaluates the call of the function named by its argument, as
+/// `vm::eval_main` does, and prints the result.
+pub fn generate(defs: &HashMap<String, Vec<Command>>) -> String {
+    let mut names: Vec<_> = defs.keys().collect();
+    names.sort();
+    // The functions come first, function `f{i}` being named by symbol `i`.
+    let mut symbols = Symbols::default();
+    for name in &names {
+        symbols.index(name);
+    }
+    let functions: Vec<_> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| generate_function(index, name, &defs[*name], &mut symbols))
+        .collect();
+    for (name, _) in builtins::all() {
+        symbols.index(name);
+    }
+
+    let mut text = String::from("/* Generated by `refal --emit-c`. */\n#include \"refal.h\"\n\n");
+    for index in 0..names.len() {
+        writeln!(text, "static void f{}(r_machine *m);", index).unwrap();
+    }
+    text.push_str("\n/* The symbols of the program, naming its functions and the built-ins. */\n");
+    text.push_str("static const r_symbol symbols[] = {\n");
+    for (index, name) in symbols.names.iter().enumerate() {
+        let image = render(&[Object::Symbol(name.clone())]);
+        let call_image = render(&[Object::FunBracketL, Object::Symbol(name.clone())]);
+        let function = match index < names.len() {
+            true => format!("f{}", index),
+            false => String::from("NULL"),
+        };
+        writeln!(
+            text,
+            "    {{ {}, {}, {}, {} }},",
+            c_string(name),
+            c_string(&image),
+            c_string(&call_image[1..]),
+            function
+        )
+        .unwrap();
+    }
+    text.push_str(
+        "};\n\nint main(int argc, char **argv)\n{\n    \
+         return r_main(symbols, sizeof symbols / sizeof *symbols, argc, argv);\n}\n",
+    );
+    for function in functions {
+        text.push('\n');
+        text.push_str(&function);
+    }
+    text
+}
+
+/// Generates function `f{index}` executing `commands`, a `case` of a `switch` on the address
+/// to go on at starting at each address matching may come back to, as in `rust_backend`.
+/// A failed match goes on at the address the runtime gives.
+fn generate_function(
+    index: usize,
+    name: &str,
+    commands: &[Command],
+    symbols: &mut Symbols,
+) -> String {
+    let starts = arm_starts(commands);
+    let image = render(&[Object::FunBracketL, Object::Symbol(name.to_string())]);
+    let mut text = format!(
+        "/* {} */\nstatic void f{}(r_machine *m)\n{{\n    for (;;) {{\n        \
+         switch (r_address(m)) {{\n",
+        image[1..].replace("*/", "* /"),
+        index
+    );
+    let mut reachable = false;
+    for (address, command) in commands.iter().enumerate() {
+        if starts.contains(&address) {
+            if reachable {
+                text.push_str("            /* fall through */\n");
+            }
+            writeln!(text, "        case {}:", address).unwrap();
+            reachable = true;
+        }
+        if !reachable {
+            continue;
+        }
+        let (call, fallible) = match command {
+            Command::MatchStart => {
+                reachable = false;
+                continue;
+            }
+            Command::MatchEmpty => (String::from("r_match_empty(m)"), true),
+            Command::MatchStrBracketL => (String::from("r_match_str_bracket_l(m)"), true),
+            Command::MatchStrBracketR => (String::from("r_match_str_bracket_r(m)"), true),
+            Command::MatchSymbolL(s) => {
+                let symbol = symbols.index(s.as_str());
+                (format!("r_match_symbol_l(m, {})", symbol), true)
+            }
+            Command::MatchSymbolR(s) => {
+                let symbol = symbols.index(s.as_str());
+                (format!("r_match_symbol_r(m, {})", symbol), true)
+            }
+            Command::MatchNumberL(n) => (format!("r_match_number_l(m, {}u)", n), true),
+            Command::MatchNumberR(n) => (format!("r_match_number_r(m, {}u)", n), true),
+            Command::MatchCharL(c) => (format!("r_match_char_l(m, {})", c_char(*c)), true),
+            Command::MatchCharR(c) => (format!("r_match_char_r(m, {})", c_char(*c)), true),
+            Command::MatchSVarL => (String::from("r_match_s_var_l(m)"), true),
+            Command::MatchSVarR => (String::from("r_match_s_var_r(m)"), true),
+            Command::MatchSVarLProj(n) => (format!("r_match_s_var_l_proj(m, {})", n), true),
+            Command::MatchSVarRProj(n) => (format!("r_match_s_var_r_proj(m, {})", n), true),
+            Command::MatchTVarL => (String::from("r_match_t_var_l(m)"), true),
+            Command::MatchTVarR => (String::from("r_match_t_var_r(m)"), true),
+            Command::MatchEVarPrepare => {
+                writeln!(text, "            r_prepare_lengthen(m, {});", address).unwrap();
+                text.push_str("            continue;\n");
+                reachable = false;
+                continue;
+            }
+            Command::MatchEVar => (String::from("r_match_e_var(m)"), false),
+            Command::MatchEVarLengthen => (format!("r_lengthen(m, {})", address), true),
+            Command::MatchEVarLProj(n) => (format!("r_match_e_var_l_proj(m, {})", n), true),
+            Command::MatchEVarRProj(n) => (format!("r_match_e_var_r_proj(m, {})", n), true),
+            Command::MatchMoveBorderL(n) => (format!("r_match_move_border_l(m, {})", n), false),
+            Command::MatchMoveBorderR(n) => (format!("r_match_move_border_r(m, {})", n), false),
+            Command::SetupTransition(n) => (format!("r_setup_transition(m, {})", n), false),
+            Command::SwitchSymbolL(table) => {
+                text.push_str("            switch (r_symbol_l(m)) {\n");
+                for (s, target) in table {
+                    let symbol = symbols.index(s.as_str());
+                    writeln!(
+                        text,
+                        "            case {}:\n                r_goto(m, {});\n                continue;",
+                        symbol, target
+                    )
+                    .unwrap();
+                }
+                text.push_str("            }\n");
+                continue;
+            }
+            Command::ConstrainLengthen(n) => (format!("r_constrain_lengthen(m, {})", n), false),
+            Command::ConditionStart => (String::from("r_condition_start(m)"), false),
+            Command::ConditionEvaluate => {
+                (format!("r_condition_evaluate(m, {})", address + 1), false)
+            }
+            Command::RewriteStart => (String::from("r_rewrite_start(m)"), false),
+            Command::RewriteFinalize => (String::from("r_rewrite_finalize(m)"), false),
+            Command::InsertSymbol(s) => {
+                let symbol = symbols.index(s.as_str());
+                (format!("r_insert_symbol(m, {})", symbol), false)
+            }
+            Command::InsertNumber(n) => (format!("r_insert_number(m, {}u)", n), false),
+            Command::InsertChar(c) => (format!("r_insert_char(m, {})", c_char(*c)), false),
+            Command::InsertStrBracketL => (String::from("r_insert_str_bracket_l(m)"), false),
+            Command::InsertStrBracketR => (String::from("r_insert_str_bracket_r(m)"), false),
+            Command::InsertFunBracketL => (String::from("r_insert_fun_bracket_l(m)"), false),
+            Command::InsertFunBracketR => (String::from("r_insert_fun_bracket_r(m)"), false),
+            Command::CopySymbol(n) => (format!("r_copy_symbol(m, {})", n), false),
+            Command::CopyExpr(n) => (format!("r_copy_expr(m, {})", n), false),
+            Command::TransplantObject(n) => (format!("r_transplant_object(m, {})", n), false),
+            Command::TransplantExpr(n) => (format!("r_transplant_expr(m, {})", n), false),
+        };
+        if fallible {
+            writeln!(
+                text,
+                "            if (!{})\n                continue;",
+                call
+            )
+            .unwrap();
+        } else {
+            writeln!(text, "            {};", call).unwrap();
+        }
+        // The call is rewritten or suspended, the runtime goes on with another.
+        if matches!(
+            command,
+            Command::RewriteFinalize | Command::ConditionEvaluate
+        ) {
+            text.push_str("            return;\n");
+            reachable = false;
+        }
+    }
+    text.push_str("        default:\n            return;\n        }\n    }\n}\n");
+    text
+}
+
+/// A C string literal holding the UTF-8 encoding of `s`. Question marks are escaped
+/// so as not to form trigraphs.
+pub(crate) fn c_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for &byte in s.as_bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7e => literal.push(byte as char),
+            _ => write!(literal, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// A character as a C character constant if it is printable ASCII, as its code otherwise.
+pub(crate) fn c_char(c: char) -> String {
+    match c {
+        '\'' | '\\' => format!("'\\{}'", c),
+        ' '..='~' => format!("'{}'", c),
+        _ => (c as u32).to_string(),
+    }
+}
@@ -0,0 +1,1396 @@
+/*
+ * The runtime of Refal programs compiled to C99: the view, the matching and rewriting
+ * commands, the evaluation loop and the built-in functions.
+ */
+#include "refal.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+enum { R_SYMBOL, R_CHAR, R_NUMBER, R_STR_L, R_STR_R, R_FUN_L, R_FUN_R, R_BORDER };
+
+typedef struct r_node {
+    struct r_node *prev, *next, *twin;
+    int kind;
+    uint32_t value;
+} r_node;
+
+/* An object out of the view, as the built-in functions take and give them. */
+typedef struct {
+    int kind;
+    uint32_t value;
+} r_object;
+
+#define R_STACK(type) \
+    struct { \
+        type *items; \
+        size_t len, cap; \
+    }
+
+typedef R_STACK(r_node *) r_nodes;
+typedef R_STACK(r_object) r_objects;
+typedef R_STACK(char) r_text;
+
+typedef struct {
+    r_node *border_l, *border_r;
+    size_t projections;
+    size_t address;
+} r_jump;
+
+typedef R_STACK(r_jump) r_jumps;
+
+/* The result of a condition, matched against its pattern. */
+typedef struct {
+    r_node *first, *last;
+    /* The number of jumps when the matching started: backtracking past them discards
+       the result. */
+    size_t jumps;
+} r_condition;
+
+typedef R_STACK(r_condition) r_conditions;
+
+/* A sentence suspended while the result of one of its conditions is evaluated. */
+typedef struct {
+    r_function function;
+    size_t address;
+    r_nodes projections;
+    r_jumps jumps;
+    r_conditions conditions;
+    /* The number of pending calls outside of the result. */
+    size_t dots_base;
+    r_node *first, *last;
+} r_frame;
+
+typedef R_STACK(r_frame) r_frames;
+
+/* A variable to move into the rewrite after `border` once the call is replaced. */
+typedef struct {
+    r_node *border, *start, *end;
+} r_transplant;
+
+typedef R_STACK(r_transplant) r_transplants;
+
+/* Maps the argument of a call to its result; returns 0 if the argument is not in the
+   domain of the function. */
+typedef int (*r_builtin)(r_machine *m, const r_object *arg, size_t len, r_objects *result);
+
+struct r_machine {
+    const r_symbol *symbols;
+    size_t symbol_count;
+    /* The built-in function named by each symbol not naming a compiled one. */
+    r_builtin *builtins;
+    r_function function;
+    size_t address;
+    int done;
+    int status;
+    r_node *border_l, *border_r;
+    r_nodes projections;
+    r_jumps jumps;
+    r_nodes dots;
+    r_frames frames;
+    r_conditions conditions;
+    /* The objects of a rewrite or of the result of a condition are inserted after `border`. */
+    r_node *border;
+    r_nodes l_brackets;
+    /* The right brackets of the calls inserted. */
+    r_nodes new_dots;
+    r_transplants transplants;
+    r_node *result_first, *result_last;
+    r_node *free;
+    FILE *channels[40];
+};
+
+static void *grow(void *items, size_t *cap, size_t size)
+{
+    *cap = *cap ? *cap * 2 : 16;
+    items = realloc(items, *cap * size);
+    if (!items) {
+        fputs("out of memory\n", stderr);
+        exit(2);
+    }
+    return items;
+}
+
+#define R_PUSH(stack, item) \
+    do { \
+        if ((stack).len == (stack).cap) \
+            (stack).items = grow((stack).items, &(stack).cap, sizeof *(stack).items); \
+        (stack).items[(stack).len++] = (item); \
+    } while (0)
+
+#define R_TOP(stack) ((stack).items[(stack).len - 1])
+
+/* The view */
+
+/* As in Refal-2, freed nodes are linked through `next` into a free list and reused. */
+static r_node *alloc(r_machine *m, int kind, uint32_t value)
+{
+    r_node *node;
+    if (!m->free) {
+        size_t i, count = 1024;
+        r_node *block = malloc(count * sizeof *block);
+        if (!block) {
+            fputs("out of memory\n", stderr);
+            exit(2);
+        }
+        for (i = 0; i < count; i++)
+            block[i].next = i + 1 < count ? &block[i + 1] : NULL;
+        m->free = block;
+    }
+    node = m->free;
+    m->free = node->next;
+    node->prev = node->next = node->twin = NULL;
+    node->kind = kind;
+    node->value = value;
+    return node;
+}
+
+/* Frees `start` and the nodes following it. */
+static void free_nodes(r_machine *m, r_node *start)
+{
+    r_node *last = start;
+    while (last->next)
+        last = last->next;
+    last->next = m->free;
+    m->free = start;
+}
+
+static void link(r_node *n1, r_node *n2)
+{
+    n1->next = n2;
+    n2->prev = n1;
+}
+
+static void pair(r_node *n1, r_node *n2)
+{
+    n1->twin = n2;
+    n2->twin = n1;
+}
+
+static int same(const r_node *n1, const r_node *n2)
+{
+    if (n1->kind != n2->kind)
+        return 0;
+    return n1->kind > R_NUMBER || n1->value == n2->value;
+}
+
+/* Collects the objects strictly between `left` and `right`. */
+static void objects_between(const r_node *left, const r_node *right, r_objects *objects)
+{
+    const r_node *cursor;
+    objects->len = 0;
+    for (cursor = left->next; cursor != right; cursor = cursor->next) {
+        r_object object;
+        object.kind = cursor->kind;
+        object.value = cursor->value;
+        R_PUSH(*objects, object);
+    }
+}
+
+/* Inserts a node holding an object after `m->border`, pairing brackets. */
+static void insert(r_machine *m, int kind, uint32_t value)
+{
+    r_node *node = alloc(m, kind, value);
+    r_node *next = m->border->next;
+    link(m->border, node);
+    link(node, next);
+    if (kind == R_STR_L || kind == R_FUN_L) {
+        R_PUSH(m->l_brackets, node);
+    } else if (kind == R_STR_R || kind == R_FUN_R) {
+        pair(m->l_brackets.items[--m->l_brackets.len], node);
+        if (kind == R_FUN_R)
+            R_PUSH(m->new_dots, node);
+    }
+    m->border = node;
+}
+
+/* Moves the right brackets of the calls inserted to the pending calls, the one to evaluate
+   first on top. */
+static void push_new_dots(r_machine *m)
+{
+    while (m->new_dots.len)
+        R_PUSH(m->dots, m->new_dots.items[--m->new_dots.len]);
+}
+
+/* Text */
+
+static void push_text(r_text *text, const char *s)
+{
+    while (*s)
+        R_PUSH(*text, *s++);
+}
+
+static void push_utf8(r_text *text, uint32_t c)
+{
+    if (c < 0x80) {
+        R_PUSH(*text, (char)c);
+    } else if (c < 0x800) {
+        R_PUSH(*text, (char)(0xC0 | c >> 6));
+        R_PUSH(*text, (char)(0x80 | (c & 0x3F)));
+    } else if (c < 0x10000) {
+        R_PUSH(*text, (char)(0xE0 | c >> 12));
+        R_PUSH(*text, (char)(0x80 | (c >> 6 & 0x3F)));
+        R_PUSH(*text, (char)(0x80 | (c & 0x3F)));
+    } else {
+        R_PUSH(*text, (char)(0xF0 | c >> 18));
+        R_PUSH(*text, (char)(0x80 | (c >> 12 & 0x3F)));
+        R_PUSH(*text, (char)(0x80 | (c >> 6 & 0x3F)));
+        R_PUSH(*text, (char)(0x80 | (c & 0x3F)));
+    }
+}
+
+static void push_number(r_text *text, uint32_t n)
+{
+    char digits[16];
+    sprintf(digits, "%lu", (unsigned long)n);
+    push_text(text, digits);
+}
+
+/* Ends the text with a null character, not counted in its length. */
+static const char *c_str(r_text *text)
+{
+    R_PUSH(*text, '\0');
+    text->len--;
+    return text->items;
+}
+
+/* Decodes the characters of a line of UTF-8 text, an invalid byte standing for itself. */
+static void push_chars(r_objects *objects, const char *line, size_t len)
+{
+    const unsigned char *s = (const unsigned char *)line;
+    size_t i = 0;
+    while (i < len) {
+        r_object object;
+        uint32_t c = s[i];
+        size_t extra = c >= 0xF0 ? 3 : c >= 0xE0 ? 2 : c >= 0xC0 ? 1 : 0;
+        size_t j;
+        if (extra && i + extra < len) {
+            uint32_t decoded = c & (0x3F >> extra);
+            for (j = 1; j <= extra && (s[i + j] & 0xC0) == 0x80; j++)
+                decoded = decoded << 6 | (s[i + j] & 0x3F);
+            if (j > extra) {
+                c = decoded;
+                i += extra;
+            }
+        }
+        i++;
+        object.kind = R_CHAR;
+        object.value = c;
+        R_PUSH(*objects, object);
+    }
+}
+
+static int is_word(int kind)
+{
+    return kind == R_SYMBOL || kind == R_NUMBER;
+}
+
+/* Renders objects the way the output built-ins print them: characters as they are,
+   brackets, and words and numbers separated by spaces. */
+static void display(r_machine *m, const r_object *objects, size_t len, r_text *text)
+{
+    size_t i;
+    for (i = 0; i < len; i++) {
+        int kind = objects[i].kind;
+        if (i > 0 && !(text->len && text->items[text->len - 1] == ' ')) {
+            int prev = objects[i - 1].kind;
+            if ((is_word(prev) && kind != R_STR_R && kind != R_FUN_R)
+                || (is_word(kind) && prev != R_STR_L && prev != R_FUN_L))
+                R_PUSH(*text, ' ');
+        }
+        switch (kind) {
+        case R_SYMBOL:
+            push_text(text, m->symbols[objects[i].value].name);
+            break;
+        case R_CHAR:
+            push_utf8(text, objects[i].value);
+            break;
+        case R_NUMBER:
+            push_number(text, objects[i].value);
+            break;
+        default:
+            R_PUSH(*text, "()<>"[kind - R_STR_L]);
+        }
+    }
+}
+
+static void push_escaped(r_text *text, uint32_t c)
+{
+    char escape[8];
+    switch (c) {
+    case '\n':
+        push_text(text, "\\n");
+        break;
+    case '\t':
+        push_text(text, "\\t");
+        break;
+    case '\r':
+        push_text(text, "\\r");
+        break;
+    case '\\':
+        push_text(text, "\\\\");
+        break;
+    case '\'':
+        push_text(text, "\\'");
+        break;
+    default:
+        if (c < 0x20 || c == 0x7F) {
+            sprintf(escape, "\\x%02X", (unsigned)c);
+            push_text(text, escape);
+        } else {
+            push_utf8(text, c);
+        }
+    }
+}
+
+/* Renders objects in Refal syntax, runs of characters as single quoted literals. */
+static void render(r_machine *m, const r_object *objects, size_t len, r_text *text)
+{
+    size_t i = 0;
+    while (i < len) {
+        int kind = objects[i].kind;
+        int opening = i == 0 || objects[i - 1].kind == R_STR_L || objects[i - 1].kind == R_FUN_L;
+        if (!opening && kind != R_STR_R && kind != R_FUN_R)
+            R_PUSH(*text, ' ');
+        switch (kind) {
+        case R_SYMBOL:
+            if (i > 0 && objects[i - 1].kind == R_FUN_L)
+                push_text(text, m->symbols[objects[i].value].call_image);
+            else
+                push_text(text, m->symbols[objects[i].value].image);
+            break;
+        case R_CHAR:
+            R_PUSH(*text, '\'');
+            for (; i < len && objects[i].kind == R_CHAR; i++)
+                push_escaped(text, objects[i].value);
+            R_PUSH(*text, '\'');
+            continue;
+        case R_NUMBER:
+            push_number(text, objects[i].value);
+            break;
+        default:
+            R_PUSH(*text, "()<>"[kind - R_STR_L]);
+        }
+        i++;
+    }
+}
+
+/* Errors */
+
+/* Stops the evaluation with an error about the call of the function named `fun`. */
+static void call_error(r_machine *m, const char *message, const r_node *fun, const r_node *end)
+{
+    r_objects arg = { 0 };
+    r_text text = { 0 };
+    objects_between(fun, end, &arg);
+    render(m, arg.items, arg.len, &text);
+    fprintf(stderr, "%s: <%s %s>\n", message, m->symbols[fun->value].name, c_str(&text));
+    free(arg.items);
+    free(text.items);
+    m->status = 1;
+    m->done = 1;
+}
+
+/* Matching */
+
+static void free_condition(r_machine *m, r_condition condition)
+{
+    condition.last->next = NULL;
+    free_nodes(m, condition.first);
+}
+
+/* Goes back to the last alternative, or stops with an error if there is none. */
+static int fail(r_machine *m)
+{
+    r_jump jump;
+    if (!m->jumps.len) {
+        call_error(m, "recognition impossible", m->projections.items[1], m->projections.items[2]);
+        return 0;
+    }
+    jump = m->jumps.items[--m->jumps.len];
+    while (m->conditions.len && R_TOP(m->conditions).jumps > m->jumps.len)
+        free_condition(m, m->conditions.items[--m->conditions.len]);
+    m->border_l = jump.border_l;
+    m->border_r = jump.border_r;
+    m->projections.len = jump.projections;
+    m->address = jump.address;
+    return 0;
+}
+
+static int shift_border_l(r_machine *m)
+{
+    m->border_l = m->border_l->next;
+    return m->border_l != m->border_r || fail(m);
+}
+
+static int shift_border_r(r_machine *m)
+{
+    m->border_r = m->border_r->prev;
+    return m->border_l != m->border_r || fail(m);
+}
+
+static int project(r_machine *m, r_node *node)
+{
+    R_PUSH(m->projections, node);
+    return 1;
+}
+
+size_t r_address(const r_machine *m)
+{
+    return m->done ? R_DONE : m->address;
+}
+
+void r_goto(r_machine *m, size_t address)
+{
+    m->address = address;
+}
+
+int r_match_empty(r_machine *m)
+{
+    return m->border_l->next == m->border_r || fail(m);
+}
+
+int r_match_str_bracket_l(r_machine *m)
+{
+    if (!shift_border_l(m))
+        return 0;
+    if (m->border_l->kind != R_STR_L)
+        return fail(m);
+    m->border_r = m->border_l->twin;
+    project(m, m->border_l);
+    return project(m, m->border_r);
+}
+
+int r_match_str_bracket_r(r_machine *m)
+{
+    r_node *twin;
+    if (!shift_border_r(m))
+        return 0;
+    if (m->border_r->kind != R_STR_R)
+        return fail(m);
+    twin = m->border_r->twin;
+    project(m, twin);
+    project(m, m->border_r);
+    m->border_r = twin;
+    return 1;
+}
+
+static int match_l(r_machine *m, int kind, uint32_t value)
+{
+    if (!shift_border_l(m))
+        return 0;
+    if (m->border_l->kind != kind || m->border_l->value != value)
+        return fail(m);
+    return project(m, m->border_l);
+}
+
+static int match_r(r_machine *m, int kind, uint32_t value)
+{
+    if (!shift_border_r(m))
+        return 0;
+    if (m->border_r->kind != kind || m->border_r->value != value)
+        return fail(m);
+    return project(m, m->border_r);
+}
+
+int r_match_symbol_l(r_machine *m, uint32_t symbol)
+{
+    return match_l(m, R_SYMBOL, symbol);
+}
+
+int r_match_symbol_r(r_machine *m, uint32_t symbol)
+{
+    return match_r(m, R_SYMBOL, symbol);
+}
+
+int r_match_number_l(r_machine *m, uint32_t number)
+{
+    return match_l(m, R_NUMBER, number);
+}
+
+int r_match_number_r(r_machine *m, uint32_t number)
+{
+    return match_r(m, R_NUMBER, number);
+}
+
+int r_match_char_l(r_machine *m, uint32_t c)
+{
+    return match_l(m, R_CHAR, c);
+}
+
+int r_match_char_r(r_machine *m, uint32_t c)
+{
+    return match_r(m, R_CHAR, c);
+}
+
+int r_match_s_var_l(r_machine *m)
+{
+    if (!shift_border_l(m))
+        return 0;
+    return m->border_l->kind <= R_NUMBER ? project(m, m->border_l) : fail(m);
+}
+
+int r_match_s_var_r(r_machine *m)
+{
+    if (!shift_border_r(m))
+        return 0;
+    return m->border_r->kind <= R_NUMBER ? project(m, m->border_r) : fail(m);
+}
+
+int r_match_s_var_l_proj(r_machine *m, size_t n)
+{
+    if (!shift_border_l(m))
+        return 0;
+    return same(m->border_l, m->projections.items[n]) ? project(m, m->border_l) : fail(m);
+}
+
+int r_match_s_var_r_proj(r_machine *m, size_t n)
+{
+    if (!shift_border_r(m))
+        return 0;
+    return same(m->border_r, m->projections.items[n]) ? project(m, m->border_r) : fail(m);
+}
+
+int r_match_t_var_l(r_machine *m)
+{
+    if (!shift_border_l(m))
+        return 0;
+    project(m, m->border_l);
+    if (m->border_l->kind == R_STR_L)
+        m->border_l = m->border_l->twin;
+    return project(m, m->border_l);
+}
+
+int r_match_t_var_r(r_machine *m)
+{
+    r_node *to_insert;
+    if (!shift_border_r(m))
+        return 0;
+    to_insert = m->border_r;
+    if (m->border_r->kind == R_STR_R)
+        m->border_r = m->border_r->twin;
+    project(m, m->border_r);
+    return project(m, to_insert);
+}
+
+static void push_jump(r_machine *m, size_t address)
+{
+    r_jump jump;
+    jump.border_l = m->border_l;
+    jump.border_r = m->border_r;
+    jump.projections = m->projections.len;
+    jump.address = address;
+    R_PUSH(m->jumps, jump);
+}
+
+void r_prepare_lengthen(r_machine *m, size_t address)
+{
+    project(m, m->border_l->next);
+    project(m, m->border_l);
+    push_jump(m, address + 1);
+    m->address = address + 2;
+}
+
+void r_match_e_var(r_machine *m)
+{
+    project(m, m->border_l->next);
+    project(m, m->border_r->prev);
+}
+
+int r_lengthen(r_machine *m, size_t address)
+{
+    m->address = address + 1;
+    m->border_l = m->projections.items[--m->projections.len];
+    if (!shift_border_l(m))
+        return 0;
+    if (m->border_l->kind == R_STR_L)
+        m->border_l = m->border_l->twin;
+    project(m, m->border_l);
+    push_jump(m, address);
+    return 1;
+}
+
+int r_match_e_var_l_proj(r_machine *m, size_t n)
+{
+    r_node *border2 = m->projections.items[n];
+    r_node *start = m->border_l->next;
+    r_node *cursor = m->projections.items[n - 1]->prev;
+    while (cursor != border2) {
+        cursor = cursor->next;
+        if (!shift_border_l(m))
+            return 0;
+        if (!same(cursor, m->border_l))
+            return fail(m);
+    }
+    project(m, start);
+    return project(m, m->border_l);
+}
+
+int r_match_e_var_r_proj(r_machine *m, size_t n)
+{
+    r_node *border1 = m->projections.items[n - 1];
+    r_node *end = m->border_r->prev;
+    r_node *cursor = m->projections.items[n]->next;
+    while (cursor != border1) {
+        cursor = cursor->prev;
+        if (!shift_border_r(m))
+            return 0;
+        if (!same(cursor, m->border_r))
+            return fail(m);
+    }
+    project(m, m->border_r);
+    return project(m, end);
+}
+
+void r_match_move_border_l(r_machine *m, size_t n)
+{
+    m->border_l = m->projections.items[n];
+}
+
+void r_match_move_border_r(r_machine *m, size_t n)
+{
+    m->border_r = m->projections.items[n];
+}
+
+void r_setup_transition(r_machine *m, size_t address)
+{
+    push_jump(m, address);
+}
+
+uint32_t r_symbol_l(const r_machine *m)
+{
+    const r_node *next = m->border_l->next;
+    return next != m->border_r && next->kind == R_SYMBOL ? next->value : R_NO_SYMBOL;
+}
+
+void r_constrain_lengthen(r_machine *m, size_t n)
+{
+    m->jumps.len -= n;
+}
+
+/* Evaluation */
+
+static void match_start(r_machine *m);
+
+void r_condition_start(r_machine *m)
+{
+    m->result_first = alloc(m, R_BORDER, 0);
+    m->result_last = alloc(m, R_BORDER, 0);
+    link(m->result_first, m->result_last);
+    m->border = m->result_first;
+}
+
+void r_condition_evaluate(r_machine *m, size_t address)
+{
+    r_frame frame;
+    frame.function = m->function;
+    frame.address = address;
+    frame.projections = m->projections;
+    frame.jumps = m->jumps;
+    frame.conditions = m->conditions;
+    frame.dots_base = m->dots.len;
+    frame.first = m->result_first;
+    frame.last = m->result_last;
+    R_PUSH(m->frames, frame);
+    memset(&m->projections, 0, sizeof m->projections);
+    memset(&m->jumps, 0, sizeof m->jumps);
+    memset(&m->conditions, 0, sizeof m->conditions);
+    push_new_dots(m);
+    match_start(m);
+}
+
+/* Continues a suspended sentence by matching the evaluated result of its condition. */
+static void resume(r_machine *m)
+{
+    r_frame frame = m->frames.items[--m->frames.len];
+    r_condition condition;
+    free(m->projections.items);
+    free(m->jumps.items);
+    free(m->conditions.items);
+    m->function = frame.function;
+    m->address = frame.address;
+    m->projections = frame.projections;
+    m->jumps = frame.jumps;
+    m->conditions = frame.conditions;
+    m->border_l = frame.first;
+    m->border_r = frame.last;
+    project(m, m->border_l);
+    project(m, m->border_r);
+    condition.first = frame.first;
+    condition.last = frame.last;
+    condition.jumps = m->jumps.len;
+    R_PUSH(m->conditions, condition);
+}
+
+void r_rewrite_start(r_machine *m)
+{
+    m->jumps.len = 0;
+    m->transplants.len = 0;
+    m->border = m->projections.items[0];
+}
+
+void r_rewrite_finalize(r_machine *m)
+{
+    r_node *fun_br_r = m->projections.items[2];
+    r_node *next = fun_br_r->next;
+    r_node *garbage = m->border->next;
+    link(m->border, next);
+    fun_br_r->next = NULL;
+    garbage->prev = NULL;
+    while (m->transplants.len) {
+        r_transplant transplant = m->transplants.items[--m->transplants.len];
+        link(transplant.start->prev, transplant.end->next);
+        link(transplant.end, transplant.border->next);
+        link(transplant.border, transplant.start);
+    }
+    free_nodes(m, garbage);
+    push_new_dots(m);
+    while (m->conditions.len)
+        free_condition(m, m->conditions.items[--m->conditions.len]);
+    m->projections.len = 0;
+    match_start(m);
+}
+
+void r_insert_symbol(r_machine *m, uint32_t symbol)
+{
+    insert(m, R_SYMBOL, symbol);
+}
+
+void r_insert_number(r_machine *m, uint32_t number)
+{
+    insert(m, R_NUMBER, number);
+}
+
+void r_insert_char(r_machine *m, uint32_t c)
+{
+    insert(m, R_CHAR, c);
+}
+
+void r_insert_str_bracket_l(r_machine *m)
+{
+    insert(m, R_STR_L, 0);
+}
+
+void r_insert_str_bracket_r(r_machine *m)
+{
+    insert(m, R_STR_R, 0);
+}
+
+void r_insert_fun_bracket_l(r_machine *m)
+{
+    insert(m, R_FUN_L, 0);
+}
+
+void r_insert_fun_bracket_r(r_machine *m)
+{
+    insert(m, R_FUN_R, 0);
+}
+
+void r_copy_symbol(r_machine *m, size_t n)
+{
+    const r_node *node = m->projections.items[n];
+    insert(m, node->kind, node->value);
+}
+
+void r_copy_expr(r_machine *m, size_t n)
+{
+    r_node *end = m->projections.items[n];
+    r_node *cursor = m->projections.items[n - 1]->prev;
+    while (cursor != end) {
+        cursor = cursor->next;
+        insert(m, cursor->kind, cursor->value);
+    }
+}
+
+void r_transplant_object(r_machine *m, size_t n)
+{
+    r_transplant transplant;
+    transplant.border = m->border;
+    transplant.start = transplant.end = m->projections.items[n];
+    R_PUSH(m->transplants, transplant);
+}
+
+void r_transplant_expr(r_machine *m, size_t n)
+{
+    r_transplant transplant;
+    transplant.border = m->border;
+    transplant.start = m->projections.items[n - 1];
+    transplant.end = m->projections.items[n];
+    if (transplant.end->next != transplant.start)
+        R_PUSH(m->transplants, transplant);
+}
+
+/* Replaces the call between `fun_br_l` and `fun_br_r` with `objects`. */
+static void replace_call(r_machine *m, r_node *fun_br_l, r_node *fun_br_r, const r_objects *objects)
+{
+    size_t i;
+    r_node *next = fun_br_r->next;
+    m->border = fun_br_l->prev;
+    fun_br_r->next = NULL;
+    free_nodes(m, fun_br_l);
+    link(m->border, next);
+    for (i = 0; i < objects->len; i++)
+        insert(m, objects->items[i].kind, objects->items[i].value);
+    push_new_dots(m);
+}
+
+/* Finds the next call to evaluate, evaluating calls of built-in functions on the way, or
+   resumes the sentence whose condition is evaluated. */
+static void match_start(r_machine *m)
+{
+    r_objects arg = { 0 }, result = { 0 };
+    for (;;) {
+        r_node *fun_br_r, *fun_br_l, *fun;
+        if (m->frames.len && R_TOP(m->frames).dots_base == m->dots.len) {
+            resume(m);
+            break;
+        }
+        if (!m->dots.len) {
+            m->done = 1;
+            break;
+        }
+        fun_br_r = m->dots.items[--m->dots.len];
+        fun_br_l = fun_br_r->twin;
+        fun = fun_br_l->next;
+        if (fun == fun_br_r || fun->kind != R_SYMBOL) {
+            r_text text = { 0 };
+            objects_between(fun_br_l->prev, fun_br_r->next, &arg);
+            render(m, arg.items, arg.len, &text);
+            fprintf(stderr, "malformed view: %s\n", c_str(&text));
+            free(text.items);
+            m->status = 1;
+            m->done = 1;
+            break;
+        }
+        if (m->symbols[fun->value].function) {
+            m->function = m->symbols[fun->value].function;
+            m->address = 0;
+            project(m, fun_br_l->prev);
+            project(m, fun);
+            project(m, fun_br_r);
+            m->border_l = fun;
+            m->border_r = fun_br_r;
+            break;
+        }
+        if (!m->builtins[fun->value]) {
+            call_error(m, "undefined function", fun, fun_br_r);
+            break;
+        }
+        objects_between(fun, fun_br_r, &arg);
+        result.len = 0;
+        if (!m->builtins[fun->value](m, arg.items, arg.len, &result)) {
+            call_error(m, "recognition impossible", fun, fun_br_r);
+            break;
+        }
+        replace_call(m, fun_br_l, fun_br_r, &result);
+    }
+    free(arg.items);
+    free(result.items);
+}
+
+/* Built-in functions */
+
+static void push_object(r_objects *objects, int kind, uint32_t value)
+{
+    r_object object;
+    object.kind = kind;
+    object.value = value;
+    R_PUSH(*objects, object);
+}
+
+/* An arbitrary-precision integer: a sign and a magnitude in base 2^32, least significant
+   macrodigit first and without leading zeros. */
+typedef struct {
+    int negative;
+    size_t len;
+    uint32_t *digits;
+} r_int;
+
+static r_int int_new(size_t len)
+{
+    r_int x;
+    x.negative = 0;
+    x.len = len;
+    x.digits = calloc(len + 1, sizeof *x.digits);
+    if (!x.digits) {
+        fputs("out of memory\n", stderr);
+        exit(2);
+    }
+    return x;
+}
+
+static void trim(r_int *x)
+{
+    while (x->len && !x->digits[x->len - 1])
+        x->len--;
+    if (!x->len)
+        x->negative = 0;
+}
+
+/* Reads a sequence of macrodigits, most significant first, with an optional sign. */
+static int int_parse(const r_object *objects, size_t len, r_int *x)
+{
+    size_t i;
+    int negative = 0;
+    if (len && objects[0].kind == R_CHAR && (objects[0].value == '-' || objects[0].value == '+')) {
+        negative = objects[0].value == '-';
+        objects++;
+        len--;
+    }
+    if (!len)
+        return 0;
+    for (i = 0; i < len; i++)
+        if (objects[i].kind != R_NUMBER)
+            return 0;
+    *x = int_new(len);
+    for (i = 0; i < len; i++)
+        x->digits[i] = objects[len - 1 - i].value;
+    x->negative = negative;
+    trim(x);
+    return 1;
+}
+
+static void int_objects(const r_int *x, r_objects *objects)
+{
+    size_t i;
+    if (x->negative)
+        push_object(objects, R_CHAR, '-');
+    if (!x->len)
+        push_object(objects, R_NUMBER, 0);
+    for (i = x->len; i > 0; i--)
+        push_object(objects, R_NUMBER, x->digits[i - 1]);
+}
+
+static int compare_magnitudes(const r_int *x, const r_int *y)
+{
+    size_t i;
+    if (x->len != y->len)
+        return x->len < y->len ? -1 : 1;
+    for (i = x->len; i > 0; i--)
+        if (x->digits[i - 1] != y->digits[i - 1])
+            return x->digits[i - 1] < y->digits[i - 1] ? -1 : 1;
+    return 0;
+}
+
+static int int_compare(const r_int *x, const r_int *y)
+{
+    if (x->negative != y->negative)
+        return x->negative ? -1 : 1;
+    return x->negative ? compare_magnitudes(y, x) : compare_magnitudes(x, y);
+}
+
+/* Adds `y`, negated if `negate`, to `x`. */
+static r_int int_add(const r_int *x, const r_int *y, int negate)
+{
+    int y_negative = y->negative != negate && y->len;
+    size_t i, len = x->len > y->len ? x->len : y->len;
+    r_int sum = int_new(len + 1);
+    if (x->negative == y_negative) {
+        uint64_t carry = 0;
+        for (i = 0; i <= len; i++) {
+            uint64_t t = carry;
+            t += i < x->len ? x->digits[i] : 0;
+            t += i < y->len ? y->digits[i] : 0;
+            sum.digits[i] = (uint32_t)t;
+            carry = t >> 32;
+        }
+        sum.negative = x->negative;
+    } else {
+        const r_int *big = x, *small = y;
+        int64_t borrow = 0;
+        sum.negative = x->negative;
+        if (compare_magnitudes(x, y) < 0) {
+            big = y;
+            small = x;
+            sum.negative = y_negative;
+        }
+        for (i = 0; i < big->len; i++) {
+            int64_t t = (int64_t)big->digits[i] - (i < small->len ? small->digits[i] : 0) - borrow;
+            borrow = t < 0;
+            sum.digits[i] = (uint32_t)(t + (borrow ? (int64_t)1 << 32 : 0));
+        }
+    }
+    trim(&sum);
+    return sum;
+}
+
+static r_int int_mul(const r_int *x, const r_int *y)
+{
+    size_t i, j;
+    r_int product = int_new(x->len + y->len);
+    for (i = 0; i < x->len; i++) {
+        uint64_t carry = 0;
+        for (j = 0; j < y->len; j++) {
+            uint64_t t = product.digits[i + j] + (uint64_t)x->digits[i] * y->digits[j] + carry;
+            product.digits[i + j] = (uint32_t)t;
+            carry = t >> 32;
+        }
+        product.digits[i + y->len] = (uint32_t)carry;
+    }
+    product.negative = x->negative != y->negative;
+    trim(&product);
+    return product;
+}
+
+/* Divides truncating toward zero, so the remainder has the sign of the dividend.
+   Returns 0 on division by zero. */
+static int int_div_rem(const r_int *x, const r_int *y, r_int *quotient, r_int *remainder)
+{
+    size_t i;
+    if (!y->len)
+        return 0;
+    *quotient = int_new(x->len);
+    *remainder = int_new(y->len + 1);
+    remainder->len = 0;
+    for (i = x->len * 32; i > 0; i--) {
+        uint32_t bit = x->digits[(i - 1) / 32] >> ((i - 1) % 32) & 1;
+        uint32_t carry = bit;
+        size_t j;
+        /* remainder = 2 * remainder + bit */
+        for (j = 0; j < remainder->len; j++) {
+            uint32_t digit = remainder->digits[j];
+            remainder->digits[j] = digit << 1 | carry;
+            carry = digit >> 31;
+        }
+        if (carry)
+            remainder->digits[remainder->len++] = carry;
+        if (compare_magnitudes(remainder, y) >= 0) {
+            int64_t borrow = 0;
+            for (j = 0; j < remainder->len; j++) {
+                int64_t t = (int64_t)remainder->digits[j] - (j < y->len ? y->digits[j] : 0) - borrow;
+                borrow = t < 0;
+                remainder->digits[j] = (uint32_t)(t + (borrow ? (int64_t)1 << 32 : 0));
+            }
+            trim(remainder);
+            quotient->digits[(i - 1) / 32] |= (uint32_t)1 << ((i - 1) % 32);
+        }
+    }
+    quotient->negative = x->negative != y->negative;
+    remainder->negative = x->negative;
+    trim(quotient);
+    trim(remainder);
+    return 1;
+}
+
+/* Splits the argument of an arithmetic function into its operands, written either as
+   `(e.N1) e.N2` or as `s.N1 e.N2`. */
+static int operands(const r_object *arg, size_t len, r_int *x, r_int *y)
+{
+    size_t first = 0, end = 1, second = 1;
+    if (!len)
+        return 0;
+    if (arg[0].kind == R_STR_L) {
+        first = 1;
+        for (end = 1; end < len && arg[end].kind != R_STR_R; end++)
+            ;
+        if (end == len)
+            return 0;
+        second = end + 1;
+    }
+    if (!int_parse(arg + first, end - first, x))
+        return 0;
+    if (!int_parse(arg + second, len - second, y)) {
+        free(x->digits);
+        return 0;
+    }
+    return 1;
+}
+
+enum { R_ADD, R_SUB, R_MUL, R_DIV, R_MOD, R_COMPARE };
+
+static int arithmetic(int operation, const r_object *arg, size_t len, r_objects *result)
+{
+    r_int x, y, z, remainder;
+    if (!operands(arg, len, &x, &y))
+        return 0;
+    switch (operation) {
+    case R_ADD:
+    case R_SUB:
+        z = int_add(&x, &y, operation == R_SUB);
+        break;
+    case R_MUL:
+        z = int_mul(&x, &y);
+        break;
+    case R_COMPARE: {
+        int sign = int_compare(&x, &y);
+        push_object(result, R_CHAR, sign < 0 ? '-' : sign > 0 ? '+' : '0');
+        free(x.digits);
+        free(y.digits);
+        return 1;
+    }
+    default:
+        if (!int_div_rem(&x, &y, &z, &remainder)) {
+            free(x.digits);
+            free(y.digits);
+            return 0;
+        }
+        if (operation == R_MOD) {
+            free(z.digits);
+            z = remainder;
+        } else {
+            free(remainder.digits);
+        }
+    }
+    int_objects(&z, result);
+    free(x.digits);
+    free(y.digits);
+    free(z.digits);
+    return 1;
+}
+
+static int add(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_ADD, arg, len, result);
+}
+
+static int sub(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_SUB, arg, len, result);
+}
+
+static int mul(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_MUL, arg, len, result);
+}
+
+static int divide(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_DIV, arg, len, result);
+}
+
+static int modulo(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_MOD, arg, len, result);
+}
+
+static int compare(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    return arithmetic(R_COMPARE, arg, len, result);
+}
+
+/* Writes the objects as the output built-ins print them, and a new line. */
+static int write_line(r_machine *m, FILE *file, const r_object *objects, size_t len)
+{
+    r_text text = { 0 };
+    int ok;
+    display(m, objects, len, &text);
+    R_PUSH(text, '\n');
+    ok = fwrite(text.items, 1, text.len, file) == text.len;
+    free(text.items);
+    return ok;
+}
+
+/* Reads a line without its terminator as characters, or 0 at the end of the input. */
+static int read_line(FILE *file, r_objects *result)
+{
+    r_text line = { 0 };
+    int c;
+    while ((c = getc(file)) != EOF && c != '\n')
+        R_PUSH(line, (char)c);
+    if (c == EOF && !line.len) {
+        push_object(result, R_NUMBER, 0);
+    } else {
+        if (c == '\n' && line.len && line.items[line.len - 1] == '\r')
+            line.len--;
+        push_chars(result, line.items, line.len);
+    }
+    free(line.items);
+    return !ferror(file);
+}
+
+static int prout(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)result;
+    return write_line(m, stdout, arg, len);
+}
+
+static int print(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    size_t i;
+    for (i = 0; i < len; i++)
+        R_PUSH(*result, arg[i]);
+    return write_line(m, stdout, arg, len);
+}
+
+static int card(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    (void)m;
+    (void)arg;
+    return !len && read_line(stdin, result);
+}
+
+/* Channel 0 is the console, files are bound to channels 1 to 39. */
+static int file_channel(const r_object *object)
+{
+    if (object->kind == R_NUMBER && object->value >= 1 && object->value < 40)
+        return (int)object->value;
+    return 0;
+}
+
+/* `<Open s.Mode s.Channel e.FileName>`, where the mode is `'r'`, `'w'` or `'a'`. */
+static int open_channel(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    const char *mode;
+    r_text path = { 0 };
+    size_t i;
+    int channel;
+    (void)result;
+    if (len < 2 || arg[0].kind != R_CHAR || !(channel = file_channel(&arg[1])))
+        return 0;
+    switch (arg[0].value) {
+    case 'r':
+    case 'R':
+        mode = "r";
+        break;
+    case 'w':
+    case 'W':
+        mode = "w";
+        break;
+    case 'a':
+    case 'A':
+        mode = "a";
+        break;
+    default:
+        return 0;
+    }
+    for (i = 2; i < len; i++) {
+        switch (arg[i].kind) {
+        case R_SYMBOL:
+            push_text(&path, m->symbols[arg[i].value].name);
+            break;
+        case R_CHAR:
+            push_utf8(&path, arg[i].value);
+            break;
+        case R_NUMBER:
+            push_number(&path, arg[i].value);
+            break;
+        default:
+            free(path.items);
+            return 0;
+        }
+    }
+    if (m->channels[channel])
+        fclose(m->channels[channel]);
+    m->channels[channel] = fopen(c_str(&path), mode);
+    free(path.items);
+    return m->channels[channel] != NULL;
+}
+
+/* `<Get s.Channel>` reads a line from a file, or from the console for channel 0. */
+static int get(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    int channel;
+    if (len != 1)
+        return 0;
+    if (arg[0].kind == R_NUMBER && arg[0].value == 0)
+        return read_line(stdin, result);
+    channel = file_channel(&arg[0]);
+    return channel && m->channels[channel] && read_line(m->channels[channel], result);
+}
+
+/* `<Put s.Channel e.Expr>` writes a line to a file, or to the console for channel 0. */
+static int put(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    FILE *file = stdout;
+    size_t i;
+    if (!len)
+        return 0;
+    if (!(arg[0].kind == R_NUMBER && arg[0].value == 0)) {
+        int channel = file_channel(&arg[0]);
+        if (!channel || !(file = m->channels[channel]))
+            return 0;
+    }
+    for (i = 1; i < len; i++)
+        R_PUSH(*result, arg[i]);
+    return write_line(m, file, arg + 1, len - 1);
+}
+
+static int close_channel(r_machine *m, const r_object *arg, size_t len, r_objects *result)
+{
+    int channel;
+    (void)result;
+    if (len != 1 || !(channel = file_channel(&arg[0])))
+        return 0;
+    if (m->channels[channel])
+        fclose(m->channels[channel]);
+    m->channels[channel] = NULL;
+    return 1;
+}
+
+static const struct {
+    const char *name;
+    r_builtin builtin;
+} builtins[] = {
+    { "Add", add },
+    { "Sub", sub },
+    { "Mul", mul },
+    { "Div", divide },
+    { "Mod", modulo },
+    { "Compare", compare },
+    { "Prout", prout },
+    { "Print", print },
+    { "Card", card },
+    { "Open", open_channel },
+    { "Get", get },
+    { "Put", put },
+    { "Close", close_channel },
+};
+
+int r_main(const r_symbol *symbols, size_t count, int argc, char **argv)
+{
+    r_machine machine = { 0 };
+    r_machine *m = &machine;
+    r_node *first, *last, *fun_br_l, *fun_br_r;
+    r_objects result = { 0 };
+    size_t i, j, goal;
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s GOAL\n", argv[0]);
+        return 2;
+    }
+    m->symbols = symbols;
+    m->symbol_count = count;
+    m->builtins = calloc(count + 1, sizeof *m->builtins);
+    for (i = 0; i < count; i++)
+        for (j = 0; j < sizeof builtins / sizeof *builtins; j++)
+            if (!strcmp(symbols[i].name, builtins[j].name))
+                m->builtins[i] = builtins[j].builtin;
+    for (goal = 0; goal < count && strcmp(symbols[goal].name, argv[1]); goal++)
+        ;
+    if (goal == count) {
+        fprintf(stderr, "undefined function: <%s >\n", argv[1]);
+        return 1;
+    }
+
+    first = alloc(m, R_BORDER, 0);
+    last = alloc(m, R_BORDER, 0);
+    fun_br_l = alloc(m, R_FUN_L, 0);
+    fun_br_r = alloc(m, R_FUN_R, 0);
+    link(first, fun_br_l);
+    link(fun_br_l, alloc(m, R_SYMBOL, (uint32_t)goal));
+    link(fun_br_l->next, fun_br_r);
+    link(fun_br_r, last);
+    pair(fun_br_l, fun_br_r);
+    R_PUSH(m->dots, fun_br_r);
+
+    match_start(m);
+    while (!m->done)
+        m->function(m);
+    if (!m->status) {
+        r_text text = { 0 };
+        objects_between(first, last, &result);
+        render(m, result.items, result.len, &text);
+        if (text.len)
+            printf("%s\n", c_str(&text));
+        free(text.items);
+    }
+    free(result.items);
+    fflush(stdout);
+    return m->status;
+}
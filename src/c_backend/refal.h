@@ -0,0 +1,93 @@
+/*
+ * The runtime of Refal programs compiled to C99 by `refal --emit-c`.
+ *
+ * As in Refal-2, the view is a doubly-linked list of nodes, each bracket pointing to its twin.
+ * A compiled function executes the commands its Refal function was compiled into, from the
+ * address r_address() gives, with one call per command. The matching calls return 0 when
+ * matching fails, after which r_address() tells where to go on.
+ */
+#ifndef REFAL_H
+#define REFAL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* The address r_address() gives once the evaluation has stopped. */
+#define R_DONE SIZE_MAX
+
+/* What r_symbol_l() gives when no symbol is right of the left border. */
+#define R_NO_SYMBOL UINT32_MAX
+
+typedef struct r_machine r_machine;
+
+/* A compiled function. It returns once the call is rewritten, a condition is to be evaluated
+   or the evaluation has stopped. */
+typedef void (*r_function)(r_machine *m);
+
+/* A symbol of the program and the function it names, if any. */
+typedef struct {
+    const char *name;
+    /* The symbol in Refal syntax, and right after `<`. */
+    const char *image;
+    const char *call_image;
+    r_function function;
+} r_symbol;
+
+/* Evaluates `<GOAL>`, GOAL being the first argument, with the functions named by `symbols`
+   and the built-ins. Prints the result and returns 0, or prints an error and returns 1. */
+int r_main(const r_symbol *symbols, size_t count, int argc, char **argv);
+
+size_t r_address(const r_machine *m);
+/* Goes on at `address`. */
+void r_goto(r_machine *m, size_t address);
+
+int r_match_empty(r_machine *m);
+int r_match_str_bracket_l(r_machine *m);
+int r_match_str_bracket_r(r_machine *m);
+int r_match_symbol_l(r_machine *m, uint32_t symbol);
+int r_match_symbol_r(r_machine *m, uint32_t symbol);
+int r_match_number_l(r_machine *m, uint32_t number);
+int r_match_number_r(r_machine *m, uint32_t number);
+int r_match_char_l(r_machine *m, uint32_t c);
+int r_match_char_r(r_machine *m, uint32_t c);
+int r_match_s_var_l(r_machine *m);
+int r_match_s_var_r(r_machine *m);
+int r_match_s_var_l_proj(r_machine *m, size_t n);
+int r_match_s_var_r_proj(r_machine *m, size_t n);
+int r_match_t_var_l(r_machine *m);
+int r_match_t_var_r(r_machine *m);
+/* `address` is that of the MatchEVarPrepare command; matching goes on two commands later. */
+void r_prepare_lengthen(r_machine *m, size_t address);
+void r_match_e_var(r_machine *m);
+/* `address` is that of the MatchEVarLengthen command. */
+int r_lengthen(r_machine *m, size_t address);
+int r_match_e_var_l_proj(r_machine *m, size_t n);
+int r_match_e_var_r_proj(r_machine *m, size_t n);
+void r_match_move_border_l(r_machine *m, size_t n);
+void r_match_move_border_r(r_machine *m, size_t n);
+void r_setup_transition(r_machine *m, size_t address);
+/* The symbol right of the left border, R_NO_SYMBOL if there is none, to switch on. */
+uint32_t r_symbol_l(const r_machine *m);
+void r_constrain_lengthen(r_machine *m, size_t n);
+
+void r_condition_start(r_machine *m);
+/* Suspends the sentence until the result of its condition is evaluated, to go on at
+   `address`. */
+void r_condition_evaluate(r_machine *m, size_t address);
+void r_rewrite_start(r_machine *m);
+/* Replaces the call and finds the next one to evaluate. */
+void r_rewrite_finalize(r_machine *m);
+
+void r_insert_symbol(r_machine *m, uint32_t symbol);
+void r_insert_number(r_machine *m, uint32_t number);
+void r_insert_char(r_machine *m, uint32_t c);
+void r_insert_str_bracket_l(r_machine *m);
+void r_insert_str_bracket_r(r_machine *m);
+void r_insert_fun_bracket_l(r_machine *m);
+void r_insert_fun_bracket_r(r_machine *m);
+void r_copy_symbol(r_machine *m, size_t n);
+void r_copy_expr(r_machine *m, size_t n);
+void r_transplant_object(r_machine *m, size_t n);
+void r_transplant_expr(r_machine *m, size_t n);
+
+#endif
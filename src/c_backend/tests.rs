@@ -0,0 +1,242 @@
+use crate::c_backend::{c_char, c_string, generate, RUNTIME, RUNTIME_HEADER};
+use crate::compiler::{compile, decision_trees};
+use crate::data::Command;
+use crate::host::MemoryHost;
+use crate::program::Program;
+use crate::vm::tests::{ARITHMETIC_PROGRAM, IO_PROGRAM, TEST_PROGRAM};
+use crate::vm::{eval_call_with, render};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, Stdio};
+use std::{env, fs};
+
+#[test]
+fn test_generate() {
+    let defs = compile("$MODULE C; F { $e.1 A $e.2 = ; = B; }").unwrap();
+    assert_eq!(
+        generate(&defs),
+        r#"/* Generated by `refal --emit-c`. */
+#include "refal.h"
+
+static void f0(r_machine *m);
+
+/* The symbols of the program, naming its functions and the built-ins. */
+static const r_symbol symbols[] = {
+    { "C.F", "\"C.F\"", "'C.F'", f0 },
+    { "A", "A", "A", NULL },
+    { "B", "B", "B", NULL },
+    { "Add", "Add", "Add", NULL },
+    { "Sub", "Sub", "Sub", NULL },
+    { "Mul", "Mul", "Mul", NULL },
+    { "Div", "Div", "Div", NULL },
+    { "Mod", "Mod", "Mod", NULL },
+    { "Compare", "Compare", "Compare", NULL },
+    { "Prout", "Prout", "Prout", NULL },
+    { "Print", "Print", "Print", NULL },
+    { "Card", "Card", "Card", NULL },
+    { "Open", "Open", "Open", NULL },
+    { "Get", "Get", "Get", NULL },
+    { "Put", "Put", "Put", NULL },
+    { "Close", "Close", "Close", NULL },
+};
+
+int main(int argc, char **argv)
+{
+    return r_main(symbols, sizeof symbols / sizeof *symbols, argc, argv);
+}
+
+/* 'C.F' */
+static void f0(r_machine *m)
+{
+    for (;;) {
+        switch (r_address(m)) {
+        case 0:
+            r_setup_transition(m, 8);
+            r_prepare_lengthen(m, 1);
+            continue;
+        case 2:
+            if (!r_lengthen(m, 2))
+                continue;
+            /* fall through */
+        case 3:
+            if (!r_match_symbol_l(m, 1))
+                continue;
+            r_match_e_var(m);
+            r_rewrite_start(m);
+            r_rewrite_finalize(m);
+            return;
+        case 8:
+            if (!r_match_empty(m))
+                continue;
+            r_rewrite_start(m);
+            r_insert_symbol(m, 2);
+            r_rewrite_finalize(m);
+            return;
+        default:
+            return;
+        }
+    }
+}
+"#
+    );
+}
+
+#[test]
+fn test_literals() {
+    assert_eq!(c_string("a\"b\\c??=\n"), r#""a\"b\\c\?\?=\012""#);
+    assert_eq!(c_string("Σ"), r#""\316\243""#);
+    assert_eq!(c_char('a'), "'a'");
+    assert_eq!(c_char('\''), r"'\''");
+    assert_eq!(c_char('\n'), "10");
+    assert_eq!(c_char('Σ'), "931");
+}
+
+/// Compiles `defs` to an executable in `dir` with the system C compiler,
+/// or returns `None` if there is none.
+fn build(dir: &Path, defs: &HashMap<String, Vec<Command>>) -> Option<PathBuf> {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("refal.h"), RUNTIME_HEADER).unwrap();
+    fs::write(dir.join("refal.c"), RUNTIME).unwrap();
+    fs::write(dir.join("program.c"), generate(defs)).unwrap();
+    let executable = dir.join("program");
+    let status = Process::new(env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .args(["-std=c99", "-O1", "-Wall", "-Werror", "-o"])
+        .arg(&executable)
+        .arg(dir.join("refal.c"))
+        .arg(dir.join("program.c"))
+        .status();
+    match status {
+        Ok(status) => {
+            assert!(status.success(), "the C compiler failed");
+            Some(executable)
+        }
+        Err(error) => {
+            eprintln!("skipping, no C compiler: {}", error);
+            None
+        }
+    }
+}
+
+/// Checks that the compiled program prints what the VM does when evaluating `goal`
+/// with the lines of `input` on the console.
+fn compare(executable: &Path, program: &Program, goal: &str, input: &[&str]) {
+    let mut host = MemoryHost::new(input);
+    let (stdout, stderr) = match eval_call_with(program, goal, &[], &mut host) {
+        Ok(result) if result.is_empty() => (String::new(), String::new()),
+        Ok(result) => (format!("{}\n", render(&result)), String::new()),
+        Err(error) => (String::new(), format!("{}\n", error)),
+    };
+    let expected: String = host
+        .output
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    let mut child = Process::new(executable)
+        .arg(goal)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    for line in input {
+        writeln!(child.stdin.as_mut().unwrap(), "{}", line).unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        expected + &stdout,
+        "{}",
+        goal
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), stderr, "{}", goal);
+    assert_eq!(output.status.success(), stderr.is_empty(), "{}", goal);
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("refal-c-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn test_test_program() {
+    let dir = temp_dir("test");
+    let executable = match build(&dir, &compile(TEST_PROGRAM).unwrap()) {
+        Some(executable) => executable,
+        None => return,
+    };
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let goals: Vec<_> = program
+        .functions()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| name.starts_with("Test.Test"))
+        .collect();
+    assert!(goals.len() > 100);
+    for goal in goals {
+        compare(&executable, &program, goal, &[]);
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_decision_trees() {
+    let mut defs = compile(TEST_PROGRAM).unwrap();
+    decision_trees(&mut defs);
+    let dir = temp_dir("trees");
+    let executable = match build(&dir, &defs) {
+        Some(executable) => executable,
+        None => return,
+    };
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    for (name, _) in program.functions() {
+        if name.as_str().starts_with("Test.Test") {
+            compare(&executable, &program, name.as_str(), &[]);
+        }
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_builtins() {
+    let source = [
+        ARITHMETIC_PROGRAM,
+        "
+Go {
+    = <Factorial 20> <Sum 1 2 3 4> <Sum <Div (1 0) 2> <Mod 7 3>>
+      <Sign '-' 3> <Sign 0> <Sign <Sub 7 2>> <Sub 2 (1 0)> <Mul '-' 3 (2 5)>;
+}
+
+DivByZero {
+    = <Div 1 0>;
+}",
+    ]
+    .concat();
+    let dir = temp_dir("builtins");
+    let executable = match build(&dir, &compile(&source).unwrap()) {
+        Some(executable) => executable,
+        None => return,
+    };
+    let program = Program::new(compile(&source).unwrap());
+    compare(&executable, &program, "Arith.Go", &[]);
+    compare(&executable, &program, "Arith.DivByZero", &[]);
+    compare(&executable, &program, "Arith.Undefined", &[]);
+    fs::remove_dir_all(dir).ok();
+
+    let source = [
+        IO_PROGRAM,
+        "
+Greet {
+    = <Print Hi 'there' 42 (A)> <Prout 'Σ' <Card>>;
+}",
+    ]
+    .concat();
+    let dir = temp_dir("io");
+    let executable = match build(&dir, &compile(&source).unwrap()) {
+        Some(executable) => executable,
+        None => return,
+    };
+    let program = Program::new(compile(&source).unwrap());
+    compare(&executable, &program, "IO.Echo", &["hello", "", "bye"]);
+    compare(&executable, &program, "IO.Greet", &["wörld"]);
+    fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,256 @@
+#[cfg(test)]
+mod tests;
+
+use crate::builtins;
+use crate::data::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Finds the problems that would make a module fail to compile or misbehave at run time:
+/// unbalanced brackets, unbound or retyped variables, calls to undefined local functions,
+/// duplicate definitions, redefinitions of built-ins and `$EXTERN` declarations of local functions.
+pub fn check_module(file: &str, text: &str, module: &RefalModule) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        file,
+        text,
+        module,
+        diagnostics: Vec::new(),
+    };
+    checker.check_definitions();
+    for f in &module.functions {
+        for sentence in &f.sentences {
+            checker.check_sentence(sentence, &HashMap::new());
+        }
+    }
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    file: &'a str,
+    text: &'a str,
+    module: &'a RefalModule,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, range: Range<usize>, message: String) {
+        let diagnostic = Diagnostic::new(self.file, self.text, range, message);
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn check_definitions(&mut self) {
+        let mut defined = HashMap::<&str, usize>::new();
+        for f in &self.module.functions {
+            if builtins::lookup(&f.name).is_some() {
+                let message = format!(
+                    "function `{}` is a built-in and cannot be redefined",
+                    f.name
+                );
+                self.report(f.location.range.clone(), message);
+            }
+            match defined.get(f.name.as_str()) {
+                Some(line) => {
+                    let message =
+                        format!("function `{}` is already defined on line {}", f.name, line);
+                    self.report(f.location.range.clone(), message);
+                }
+                None => {
+                    defined.insert(&f.name, f.location.line);
+                }
+            }
+        }
+        for declaration in &self.module.externs {
+            if let Some(line) = defined.get(declaration.name.as_str()) {
+                let message = format!(
+                    "function `{}` is declared $EXTERN but defined on line {}",
+                    declaration.name, line
+                );
+                self.report(declaration.location.range.clone(), message);
+            } else if builtins::lookup(&declaration.name).is_some() {
+                let message = format!("function `{}` is a built-in", declaration.name);
+                self.report(declaration.location.range.clone(), message);
+            }
+        }
+    }
+
+    /// Checks a sentence where the variables in `outer` are bound by the enclosing sentences.
+    fn check_sentence(&mut self, sentence: &'a Sentence, outer: &HashMap<&'a str, &'a str>) {
+        let range = &sentence.location.range;
+        let mut bound = outer.clone();
+        self.check_pattern_brackets(&sentence.pattern, range);
+        self.bind(&sentence.pattern, &mut bound, range);
+        for condition in &sentence.conditions {
+            self.check_result(&condition.result, &bound, range);
+            self.check_pattern_brackets(&condition.pattern, range);
+            self.bind(&condition.pattern, &mut bound, range);
+        }
+        self.check_result(&sentence.rewrite, &bound, range);
+        for inner in sentence.block.iter().flatten() {
+            self.check_sentence(inner, &bound);
+        }
+    }
+
+    fn bind(
+        &mut self,
+        pattern: &'a [Object],
+        bound: &mut HashMap<&'a str, &'a str>,
+        range: &Range<usize>,
+    ) {
+        for image in pattern.iter().filter_map(variable) {
+            let name = variable_name(image);
+            match bound.get(name) {
+                Some(other) if *other != image => self.report_conflict(image, other, range),
+                Some(_) => (),
+                None => {
+                    bound.insert(name, image);
+                }
+            }
+        }
+    }
+
+    fn check_result(
+        &mut self,
+        result: &[Object],
+        bound: &HashMap<&str, &str>,
+        range: &Range<usize>,
+    ) {
+        self.check_rewrite_brackets(result, range);
+        let mut reported = HashSet::<&str>::new();
+        for image in result.iter().filter_map(variable) {
+            if !reported.insert(image) {
+                continue;
+            }
+            match bound.get(variable_name(image)) {
+                Some(other) if *other != image => self.report_conflict(image, other, range),
+                Some(_) => (),
+                None => {
+                    let message = format!("variable `{}` is not bound in the pattern", image);
+                    self.report(self.locate(range, image), message);
+                }
+            }
+        }
+        self.check_calls(result, range);
+    }
+
+    fn report_conflict(&mut self, image: &str, other: &str, range: &Range<usize>) {
+        let message = format!("variable `{}` conflicts with `{}`", image, other);
+        self.report(self.locate(range, image), message);
+    }
+
+    fn check_pattern_brackets(&mut self, pattern: &[Object], range: &Range<usize>) {
+        let mut depth: usize = 0;
+        for object in pattern {
+            match object {
+                Object::StrBracketL => depth += 1,
+                Object::StrBracketR if depth > 0 => depth -= 1,
+                Object::StrBracketR => {
+                    self.report(range.clone(), String::from("unbalanced `)` in the pattern"));
+                    return;
+                }
+                Object::FunBracketL | Object::FunBracketR => {
+                    let message = String::from("function calls are not allowed in a pattern");
+                    self.report(range.clone(), message);
+                    return;
+                }
+                _ => (),
+            }
+        }
+        if depth > 0 {
+            self.report(range.clone(), String::from("unclosed `(` in the pattern"));
+        }
+    }
+
+    fn check_rewrite_brackets(&mut self, rewrite: &[Object], range: &Range<usize>) {
+        let mut brackets = Vec::<&Object>::new();
+        for (i, object) in rewrite.iter().enumerate() {
+            let (expected, image) = match object {
+                Object::StrBracketL | Object::FunBracketL => {
+                    brackets.push(object);
+                    continue;
+                }
+                Object::StrBracketR => (Object::StrBracketL, ")"),
+                Object::FunBracketR => (Object::FunBracketL, ">"),
+                _ => continue,
+            };
+            if brackets.pop() != Some(&expected) {
+                let message = format!("unbalanced `{}` in the rewrite", image);
+                self.report(range.clone(), message);
+                return;
+            }
+            if i > 0 && *object == Object::FunBracketR && rewrite[i - 1] == Object::FunBracketL {
+                self.report(
+                    range.clone(),
+                    String::from("expected a function name after `<`"),
+                );
+            }
+        }
+        if let Some(bracket) = brackets.last() {
+            let image = if **bracket == Object::StrBracketL {
+                "("
+            } else {
+                "<"
+            };
+            let message = format!("unclosed `{}` in the rewrite", image);
+            self.report(range.clone(), message);
+        }
+    }
+
+    fn check_calls(&mut self, rewrite: &[Object], range: &Range<usize>) {
+        for pair in rewrite.windows(2) {
+            let name = match pair {
+                [Object::FunBracketL, Object::Symbol(name)] => name,
+                [Object::FunBracketL, object] if *object != Object::FunBracketR => {
+                    let message = String::from("expected a function name after `<`");
+                    self.report(range.clone(), message);
+                    continue;
+                }
+                _ => continue,
+            };
+            let local_name = match name.split_once('.') {
+                None if builtins::lookup(name).is_some() => continue,
+                None if self.module.externs.iter().any(|e| e.name == *name) => continue,
+                None => name.as_str(),
+                Some((module, local_name)) if module == self.module.name => local_name,
+                Some(_) => continue,
+            };
+            if !self.module.functions.iter().any(|f| f.name == local_name) {
+                let message = format!("function `{}` is not defined", name);
+                self.report(self.locate(range, name), message);
+            }
+        }
+    }
+
+    fn locate(&self, range: &Range<usize>, image: &str) -> Range<usize> {
+        locate(self.text, range, image)
+    }
+}
+
+/// Finds the first occurrence of the token `image` inside `range` of `text`, or the whole range.
+pub fn locate(text: &str, range: &Range<usize>, image: &str) -> Range<usize> {
+    let text = &text[range.clone()];
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let mut offset = 0;
+    while let Some(i) = text[offset..].find(image) {
+        let start = offset + i;
+        let end = start + image.len();
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if !before.is_some_and(is_name_char) && !after.is_some_and(is_name_char) {
+            return range.start + start..range.start + end;
+        }
+        offset = end;
+    }
+    range.clone()
+}
+
+fn variable(object: &Object) -> Option<&str> {
+    match object {
+        Object::EVar(image) | Object::SVar(image) | Object::TVar(image) => Some(image),
+        _ => None,
+    }
+}
+
+/// The part of a variable that identifies it regardless of its type: `1` for `$e.1`.
+fn variable_name(image: &str) -> &str {
+    image.split_once('.').map_or(image, |(_, name)| name)
+}
@@ -0,0 +1,171 @@
+use crate::checker::check_module;
+use crate::parser;
+
+#[cfg(test)]
+fn check(input: &str) -> Vec<(usize, String)> {
+    let module = parser::parse_input(input).unwrap();
+    check_module("t.refal", input, &module)
+        .into_iter()
+        .map(|d| (d.location.line, d.message))
+        .collect()
+}
+
+#[test]
+fn test_valid() {
+    let input = "$MODULE T;
+F { $s.1 $e.2 $s.1 = <F $e.2> <'T.G' ($e.2)> <'M.H'>; }
+G { ($e.X) $t.Y = $t.Y $e.X $e.X; }";
+    assert_eq!(check(input), vec![]);
+}
+
+#[test]
+fn test_unbound_variable() {
+    let input = "$MODULE T;
+F {
+    $e.1 = $e.1;
+    $s.1 = $e.2 $e.2 $t.3;
+}";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                4,
+                String::from("variable `$e.2` is not bound in the pattern")
+            ),
+            (
+                4,
+                String::from("variable `$t.3` is not bound in the pattern")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_variable_type_conflict() {
+    let input = "$MODULE T;
+F {
+    $s.1 $e.1 = ;
+    $s.2 = $e.2;
+}";
+    assert_eq!(
+        check(input),
+        vec![
+            (3, String::from("variable `$e.1` conflicts with `$s.1`")),
+            (4, String::from("variable `$e.2` conflicts with `$s.2`")),
+        ]
+    );
+}
+
+#[test]
+fn test_unbalanced_brackets() {
+    let input = "$MODULE T;
+F {
+    ($e.1 = ;
+    $e.1) = ;
+    $e.1 = ($e.1;
+    $e.1 = <F $e.1);
+    $e.1 = <>;
+}";
+    assert_eq!(
+        check(input),
+        vec![
+            (3, String::from("unclosed `(` in the pattern")),
+            (4, String::from("unbalanced `)` in the pattern")),
+            (5, String::from("unclosed `(` in the rewrite")),
+            (6, String::from("unbalanced `)` in the rewrite")),
+            (7, String::from("expected a function name after `<`")),
+        ]
+    );
+}
+
+#[test]
+fn test_undefined_function() {
+    let input = "$MODULE T;
+F { = <G> <'T.H'> <'M.H'> <(A)>; }";
+    assert_eq!(
+        check(input),
+        vec![
+            (2, String::from("function `G` is not defined")),
+            (2, String::from("function `T.H` is not defined")),
+            (2, String::from("expected a function name after `<`")),
+        ]
+    );
+}
+
+#[test]
+fn test_duplicate_function() {
+    let input = "$MODULE T;
+F { = ; }
+G { = ; }
+F { = A; }";
+    assert_eq!(
+        check(input),
+        vec![(4, String::from("function `F` is already defined on line 2"))]
+    );
+}
+
+#[test]
+fn test_location() {
+    let input = "$MODULE T;\nF { $e.10 = $e.1; }";
+    let module = parser::parse_input(input).unwrap();
+    let diagnostics = check_module("t.refal", input, &module);
+    assert_eq!(diagnostics[0].location.column, 13);
+    assert_eq!(&input[diagnostics[0].location.range.clone()], "$e.1");
+}
+
+#[test]
+fn test_builtins() {
+    let input = "$MODULE T;
+F { $e.1 = <Add (<Mul 2 3>) 1> <Compare $e.1>; }
+Sub { = ; }";
+    assert_eq!(
+        check(input),
+        vec![(
+            3,
+            String::from("function `Sub` is a built-in and cannot be redefined")
+        )]
+    );
+}
+
+#[test]
+fn test_externs() {
+    let input = "$MODULE T;
+$EXTERN G, F, Add;
+$ENTRY F { = <G> <H>; }";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                2,
+                String::from("function `F` is declared $EXTERN but defined on line 3")
+            ),
+            (2, String::from("function `Add` is a built-in")),
+            (3, String::from("function `H` is not defined")),
+        ]
+    );
+}
+
+#[test]
+fn test_conditions() {
+    let input = "$MODULE T;
+F {
+    $e.1 , <F $e.1> : $s.2 $e.3 , $e.3 : $e.5 $s.2 = $e.5 $s.2;
+    $e.1 , <F $e.4> : $e.1 = ;
+    $s.1 , $s.1 : $e.1 = ;
+    $e.1 , $e.1 : { A $e.2 = $e.2; $e.3 = $e.1 $e.4; };
+}";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                4,
+                String::from("variable `$e.4` is not bound in the pattern")
+            ),
+            (5, String::from("variable `$e.1` conflicts with `$s.1`")),
+            (
+                6,
+                String::from("variable `$e.4` is not bound in the pattern")
+            ),
+        ]
+    );
+}
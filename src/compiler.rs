@@ -1,24 +1,216 @@
 #[cfg(test)]
 mod tests;
 
+use crate::builtins;
+use crate::checker;
 use crate::data::*;
+use crate::linker::{self, Unit};
 use crate::parser;
-use std::collections::{HashMap, HashSet};
+use crate::runtime;
+use crate::symbol::Symbol;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 pub fn compile(input: &str) -> Result<HashMap<String, Vec<Command>>> {
     let module = parser::parse_input(input)?;
+    check("<input>", input, &module)?;
     Ok(compile_module(&module))
 }
 
+/// Compiles the source text of `file`; diagnostics refer to `file`.
+pub fn compile_file(file: &str, input: &str) -> Result<HashMap<String, Vec<Command>>> {
+    let module = parser::parse_file(file, input)?;
+    check(file, input, &module)?;
+    Ok(compile_module(&module))
+}
+
+/// Compiles the modules of a program, given as pairs of file name and source text,
+/// into one definition table.
+pub fn compile_program(sources: &[(&str, &str)]) -> Result<HashMap<String, Vec<Command>>> {
+    let mut units = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (file, text) in sources {
+        match parser::parse_file(file, text) {
+            Ok(module) => units.push(Unit {
+                file: file.to_string(),
+                text: text.to_string(),
+                module,
+            }),
+            Err(Error::Parsing(problems)) => diagnostics.extend(problems),
+            Err(error) => return Err(error),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(Error::Parsing(diagnostics));
+    }
+    compile_units(units)
+}
+
+/// Checks, links and compiles parsed modules.
+pub(crate) fn compile_units(mut units: Vec<Unit>) -> Result<HashMap<String, Vec<Command>>> {
+    let diagnostics: Vec<_> = units
+        .iter()
+        .flat_map(|u| checker::check_module(&u.file, &u.text, &u.module))
+        .collect();
+    if !diagnostics.is_empty() {
+        return Err(Error::Semantic(diagnostics));
+    }
+    let diagnostics = linker::link(&mut units);
+    if !diagnostics.is_empty() {
+        return Err(Error::Linking(diagnostics));
+    }
+    let mut defs = HashMap::<String, Vec<Command>>::new();
+    for unit in &units {
+        defs.extend(compile_module(&unit.module));
+    }
+    Ok(defs)
+}
+
+fn check(file: &str, input: &str, module: &RefalModule) -> Result<()> {
+    let diagnostics = checker::check_module(file, input, module);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Semantic(diagnostics))
+    }
+}
+
 pub fn compile_module(m: &RefalModule) -> HashMap<String, Vec<Command>> {
     let mut defs = HashMap::<String, Vec<Command>>::new();
-    let module = &m.name;
-    for f in &m.functions {
-        defs.insert(qualify(module, &f.name), compile_function(module, f));
+    for (name, f) in compiled_functions(m) {
+        defs.insert(name, compile_function(&m.name, &f));
     }
     defs
 }
 
+/// The functions `compile_module` compiles, blocks lifted into functions of their own,
+/// with their qualified names. Their sentences are in the order of the compiled sentences.
+pub(crate) fn compiled_functions(m: &RefalModule) -> Vec<(String, Function)> {
+    let mut functions = Vec::new();
+    for f in &m.functions {
+        let mut blocks = Vec::<Function>::new();
+        let sentences = lift_blocks(&f.name, &f.sentences, &mut blocks);
+        blocks.push(Function {
+            name: f.name.clone(),
+            sentences,
+            location: f.location.clone(),
+            entry: f.entry,
+        });
+        for f in blocks {
+            functions.push((qualify(&m.name, &f.name), f));
+        }
+    }
+    functions
+}
+
+/// Translates a ground expression in Refal syntax into objects accepted by `vm::eval_call`.
+/// Unqualified function names are resolved in `module`.
+pub fn compile_expression(module: &str, input: &str) -> Result<Vec<runtime::Object>> {
+    let expression = parser::parse_expression(input)?;
+    let mut objects = Vec::<runtime::Object>::new();
+    let mut brackets = Vec::<&Object>::new();
+    let mut prev_fun_br = false;
+    for obj in &expression {
+        let object = match obj {
+            Object::Symbol(image) if prev_fun_br => {
+                runtime::Object::Symbol(Symbol::intern(&qualify(module, image)))
+            }
+            Object::Symbol(image) => runtime::Object::Symbol(Symbol::intern(image)),
+            Object::Char(c) => runtime::Object::Char(*c),
+            Object::Number(n) => runtime::Object::Number(*n),
+            Object::StrBracketL => runtime::Object::StrBracketL,
+            Object::StrBracketR => runtime::Object::StrBracketR,
+            Object::FunBracketL => runtime::Object::FunBracketL,
+            Object::FunBracketR => runtime::Object::FunBracketR,
+            Object::EVar(_) | Object::SVar(_) | Object::TVar(_) => {
+                return Err(expression_error(input, "variables are not allowed here"))
+            }
+        };
+        match obj {
+            Object::StrBracketL | Object::FunBracketL => brackets.push(obj),
+            Object::StrBracketR if brackets.pop() == Some(&Object::StrBracketL) => (),
+            Object::FunBracketR if brackets.pop() == Some(&Object::FunBracketL) => (),
+            Object::StrBracketR | Object::FunBracketR => {
+                return Err(expression_error(input, "unbalanced brackets"))
+            }
+            _ => (),
+        }
+        objects.push(object);
+        prev_fun_br = *obj == Object::FunBracketL;
+    }
+    if brackets.is_empty() {
+        Ok(objects)
+    } else {
+        Err(expression_error(input, "unbalanced brackets"))
+    }
+}
+
+fn expression_error(input: &str, message: &str) -> Error {
+    let diagnostic = Diagnostic::new("<expression>", input, 0..input.len(), message.to_string());
+    Error::Parsing(vec![diagnostic])
+}
+
+/// Replaces each block `, result : { sentences }` of a sentence of function `name` by a call to
+/// an auxiliary function `name:N` taking the variables bound so far followed by the result.
+fn lift_blocks(name: &str, sentences: &[Sentence], functions: &mut Vec<Function>) -> Vec<Sentence> {
+    let mut lifted = Vec::new();
+    for sentence in sentences {
+        let block = match &sentence.block {
+            Some(block) => block,
+            None => {
+                lifted.push(sentence.clone());
+                continue;
+            }
+        };
+        let mut bound = Vec::<Object>::new();
+        let patterns = std::iter::once(&sentence.pattern)
+            .chain(sentence.conditions.iter().map(|c| &c.pattern));
+        for object in patterns.flatten() {
+            match object {
+                Object::EVar(_) | Object::SVar(_) | Object::TVar(_) if !bound.contains(object) => {
+                    bound.push(object.clone())
+                }
+                _ => (),
+            }
+        }
+        let mut arguments = Vec::new();
+        for var in bound {
+            if let Object::EVar(_) = var {
+                arguments.extend([Object::StrBracketL, var, Object::StrBracketR]);
+            } else {
+                arguments.push(var);
+            }
+        }
+
+        let block_name = format!("{}:{}", name, functions.len() + 1);
+        let block_sentences: Vec<_> = block
+            .iter()
+            .map(|s| Sentence {
+                pattern: [&arguments[..], &s.pattern[..]].concat(),
+                ..s.clone()
+            })
+            .collect();
+        functions.push(Function {
+            name: block_name.clone(),
+            sentences: Vec::new(),
+            location: sentence.location.clone(),
+            entry: false,
+        });
+        let index = functions.len() - 1;
+        functions[index].sentences = lift_blocks(name, &block_sentences, functions);
+
+        let mut rewrite = vec![Object::FunBracketL, Object::Symbol(block_name)];
+        rewrite.extend(arguments);
+        rewrite.extend(sentence.rewrite.iter().cloned());
+        rewrite.push(Object::FunBracketR);
+        lifted.push(Sentence {
+            rewrite,
+            block: None,
+            ..sentence.clone()
+        });
+    }
+    lifted
+}
+
 fn compile_function(module: &str, f: &Function) -> Vec<Command> {
     let mut sentence_commands = Vec::<Vec<Command>>::new();
     for sentence in &f.sentences {
@@ -40,12 +232,173 @@ fn flatten(mut sentence_commands: Vec<Vec<Command>>) -> Vec<Command> {
     result
 }
 
+/// Turns the chain of sentences of each function into a decision tree: adjacent sentences
+/// beginning with the same matching command share it, so a call failing to match it skips
+/// all of them at once, and adjacent sentences beginning with different symbols are selected
+/// by the symbol of the argument instead of being tried in turn. The sentences keep their
+/// order, and are still found at `sentence_starts`. Functions that are not a chain of
+/// sentences are left as they are.
+pub fn decision_trees(defs: &mut HashMap<String, Vec<Command>>) {
+    for commands in defs.values_mut() {
+        if let Some(sentences) = split_sentences(commands) {
+            let mut tree = Vec::with_capacity(commands.len());
+            merge_sentences(&sentences, &mut tree);
+            *commands = tree;
+        }
+    }
+}
+
+/// The commands of each sentence of a function `flatten` made, without the transitions
+/// chaining them.
+fn split_sentences(commands: &[Command]) -> Option<Vec<&[Command]>> {
+    let starts = sentence_starts(commands);
+    let mut sentences = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied();
+        let sentence = match end {
+            Some(end) if commands[start] == Command::SetupTransition(end) => {
+                &commands[start + 1..end]
+            }
+            Some(_) => return None,
+            None => &commands[start..],
+        };
+        if sentence
+            .iter()
+            .any(|c| matches!(c, Command::SetupTransition(_) | Command::SwitchSymbolL(_)))
+        {
+            return None;
+        }
+        sentences.push(sentence);
+    }
+    Some(sentences)
+}
+
+/// Appends the alternatives `sentences`, each run of them beginning with the same shareable
+/// command matching it once before trying the rest of each in turn. Adjacent runs beginning
+/// with different symbols are not tried in turn: a `SwitchSymbolL` goes to the run beginning
+/// with the symbol of the argument, the first run following it for any other.
+fn merge_sentences(sentences: &[&[Command]], commands: &mut Vec<Command>) {
+    let mut rest = sentences;
+    while !rest.is_empty() {
+        let runs = runs(rest);
+        let length: usize = runs.iter().sum();
+        let transition = commands.len();
+        if length < rest.len() {
+            commands.push(Command::SetupTransition(0));
+        }
+        let switch = commands.len();
+        if runs.len() > 1 {
+            commands.push(Command::SwitchSymbolL(Vec::new()));
+        }
+        let mut table = Vec::new();
+        let mut run_start = 0;
+        for &run in &runs {
+            let run_sentences = &rest[run_start..run_start + run];
+            if let (true, Some(Command::MatchSymbolL(symbol))) =
+                (run_start > 0, run_sentences[0].first())
+            {
+                table.push((*symbol, commands.len()));
+            }
+            merge_run(run_sentences, commands);
+            run_start += run;
+        }
+        if runs.len() > 1 {
+            commands[switch] = Command::SwitchSymbolL(table);
+        }
+        if length < rest.len() {
+            commands[transition] = Command::SetupTransition(commands.len());
+        }
+        rest = &rest[length..];
+    }
+}
+
+/// Appends the run `sentences`, all beginning with the same command.
+fn merge_run(sentences: &[&[Command]], commands: &mut Vec<Command>) {
+    match sentences {
+        [sentence] => commands.extend_from_slice(sentence),
+        _ => {
+            commands.push(sentences[0][0].clone());
+            let tails: Vec<_> = sentences.iter().map(|sentence| &sentence[1..]).collect();
+            merge_sentences(&tails, commands);
+        }
+    }
+}
+
+/// The lengths of the runs of sentences `merge_sentences` begins with: the sentences beginning
+/// with the same shareable command, and the next runs too as long as each begins with
+/// a `MatchSymbolL` of a symbol no run before begins with.
+fn runs(sentences: &[&[Command]]) -> Vec<usize> {
+    let mut runs = Vec::new();
+    let mut symbols = HashSet::new();
+    let mut rest = sentences;
+    while let Some(first) = rest.first() {
+        let run = match first.first() {
+            Some(command) if shareable(command) => rest
+                .iter()
+                .take_while(|sentence| sentence.first() == Some(command))
+                .count(),
+            _ => 1,
+        };
+        match first.first() {
+            Some(Command::MatchSymbolL(symbol)) if symbols.insert(*symbol) => runs.push(run),
+            _ if runs.is_empty() => return vec![run],
+            _ => break,
+        }
+        rest = &rest[run..];
+    }
+    runs
+}
+
+/// Whether sentences may match `command` once for all: it matches without leaving
+/// alternatives to come back to, and changes only what transitions restore.
+fn shareable(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::MatchEmpty
+            | Command::MatchStrBracketL
+            | Command::MatchStrBracketR
+            | Command::MatchSymbolL(_)
+            | Command::MatchSymbolR(_)
+            | Command::MatchNumberL(_)
+            | Command::MatchNumberR(_)
+            | Command::MatchCharL(_)
+            | Command::MatchCharR(_)
+            | Command::MatchSVarL
+            | Command::MatchSVarR
+            | Command::MatchSVarLProj(_)
+            | Command::MatchSVarRProj(_)
+            | Command::MatchTVarL
+            | Command::MatchTVarR
+            | Command::MatchEVar
+            | Command::MatchEVarLProj(_)
+            | Command::MatchEVarRProj(_)
+            | Command::MatchMoveBorderL(_)
+            | Command::MatchMoveBorderR(_)
+    )
+}
+
 fn compile_sentence(module: &str, sentence: &Sentence) -> Vec<Command> {
-    let mut commands = Vec::<Command>::new();
     let pattern: Vec<&Object> = sentence.pattern.iter().collect();
     let expression: Vec<&Object> = sentence.rewrite.iter().collect();
-    let mut result = compile_pattern(&pattern);
+    // Without conditions, alternative matches of independent holes cannot make a difference.
+    let constrain = sentence.conditions.is_empty();
+    let mut result = compile_pattern(&pattern, (1, 2), HashMap::new(), constrain);
+    let mut commands = Vec::<Command>::new();
     commands.append(&mut result.commands);
+    for (i, condition) in sentence.conditions.iter().enumerate() {
+        let expression: Vec<&Object> = condition.result.iter().collect();
+        commands.append(&mut compile_condition(
+            module,
+            &expression,
+            &result.projected_vars,
+        ));
+        // The result is put between two new projections.
+        let borders = (result.next_element, result.next_element + 1);
+        let pattern: Vec<&Object> = condition.pattern.iter().collect();
+        let constrain = i + 1 == sentence.conditions.len();
+        result = compile_pattern(&pattern, borders, result.projected_vars, constrain);
+        commands.append(&mut result.commands);
+    }
     commands.append(&mut compile_rewrite(
         module,
         &expression,
@@ -54,18 +407,27 @@ fn compile_sentence(module: &str, sentence: &Sentence) -> Vec<Command> {
     commands
 }
 
-fn compile_pattern(pattern: &[&Object]) -> PatternCompile {
+/// Compiles matching against `pattern` between the projections `borders`;
+/// `projected_vars` are the variables already bound.
+fn compile_pattern(
+    pattern: &[&Object],
+    borders: (usize, usize),
+    projected_vars: HashMap<String, usize>,
+    constrain: bool,
+) -> PatternCompile {
+    let (border_l, border_r) = borders;
     let mut state = State {
-        border_l: 1,
-        border_r: 2,
-        next_element: 3,
+        border_l,
+        border_r,
+        next_element: border_r + 1,
         transition_depth: 0,
-        projected_vars: HashMap::new(),
+        constrain,
+        projected_vars,
         holes_stack: Vec::new(),
         transition_depth_stack: Vec::new(),
         holes: vec![Hole {
-            border_l: 1,
-            border_r: 2,
+            border_l,
+            border_r,
             objects: pattern.to_vec(),
         }],
         commands: Vec::new(),
@@ -100,6 +462,7 @@ fn compile_pattern(pattern: &[&Object]) -> PatternCompile {
     PatternCompile {
         commands: state.commands,
         projected_vars: state.projected_vars,
+        next_element: state.next_element,
     }
 }
 
@@ -204,8 +567,14 @@ fn match_s_l(state: &mut State, index: usize) -> bool {
     match state.holes.get(index) {
         Some(hole) => {
             let first_in_hole = hole.objects[0];
-            if let Object::Symbol(s) = first_in_hole {
-                state.commands.push(Command::MatchSymbolL(s.clone()));
+            let symbol_command = match first_in_hole {
+                Object::Symbol(s) => Some(Command::MatchSymbolL(Symbol::intern(s))),
+                Object::Char(c) => Some(Command::MatchCharL(*c)),
+                Object::Number(n) => Some(Command::MatchNumberL(*n)),
+                _ => None,
+            };
+            if let Some(command) = symbol_command {
+                state.commands.push(command);
                 state.border_l = state.next_element;
                 state.border_r = hole.border_r;
                 // replace current hole
@@ -338,7 +707,15 @@ fn match_s_r(state: &mut State, index: usize) -> bool {
             let last_obj_index = hole.objects.len() - 1;
             let last = hole.objects[last_obj_index];
             let found = if let Object::Symbol(s) = last {
-                state.commands.push(Command::MatchSymbolR(s.clone()));
+                state
+                    .commands
+                    .push(Command::MatchSymbolR(Symbol::intern(s)));
+                true
+            } else if let Object::Char(c) = last {
+                state.commands.push(Command::MatchCharR(*c));
+                true
+            } else if let Object::Number(n) = last {
+                state.commands.push(Command::MatchNumberR(*n));
                 true
             } else if let Object::SVar(v) = last {
                 match state.projected_vars.get(v) {
@@ -481,8 +858,10 @@ fn handle_holes(state: &mut State) {
 fn constrain_lengthen(state: &mut State) {
     if let (Some(td0), Some(holes)) = (state.transition_depth_stack.pop(), state.holes_stack.pop())
     {
-        let cmd = Command::ConstrainLengthen(state.transition_depth - td0);
-        state.commands.push(cmd);
+        if state.constrain {
+            let cmd = Command::ConstrainLengthen(state.transition_depth - td0);
+            state.commands.push(cmd);
+        }
         state.transition_depth = td0;
         state.holes = holes;
     }
@@ -497,26 +876,16 @@ fn compile_rewrite(
     let mut commands = vec![Command::RewriteStart];
     let mut prev_fun_br = false;
     for obj in expression {
-        match obj {
-            Object::Symbol(image) if prev_fun_br => {
-                commands.push(Command::InsertSymbol(qualify(module, image)))
-            }
-            Object::Symbol(image) => commands.push(Command::InsertSymbol(image.clone())),
-            Object::StrBracketL => commands.push(Command::InsertStrBracketL),
-            Object::StrBracketR => commands.push(Command::InsertStrBracketR),
-            Object::FunBracketL => commands.push(Command::InsertFunBracketL),
-            Object::FunBracketR => commands.push(Command::InsertFunBracketR),
-            Object::SVar(v) if vars.remove(v) => {
-                commands.push(Command::TransplantObject(projected_vars[v]))
-            }
-            Object::SVar(v) => commands.push(Command::CopySymbol(projected_vars[v])),
+        let command = match obj {
+            Object::SVar(v) if vars.remove(v) => Command::TransplantObject(projected_vars[v]),
+            Object::SVar(v) => Command::CopySymbol(projected_vars[v]),
             Object::EVar(v) | Object::TVar(v) if vars.remove(v) => {
-                commands.push(Command::TransplantExpr(projected_vars[v]))
-            }
-            Object::EVar(v) | Object::TVar(v) => {
-                commands.push(Command::CopyExpr(projected_vars[v]))
+                Command::TransplantExpr(projected_vars[v])
             }
-        }
+            Object::EVar(v) | Object::TVar(v) => Command::CopyExpr(projected_vars[v]),
+            _ => insert_command(module, obj, prev_fun_br),
+        };
+        commands.push(command);
         prev_fun_br = **obj == Object::FunBracketL;
     }
     commands.push(Command::RewriteFinalize);
@@ -524,8 +893,46 @@ fn compile_rewrite(
     commands
 }
 
+/// Compiles the result of a condition. Variables are copied, as the matched view
+/// must stay intact in case the condition fails.
+fn compile_condition(
+    module: &str,
+    expression: &[&Object],
+    projected_vars: &HashMap<String, usize>,
+) -> Vec<Command> {
+    let mut commands = vec![Command::ConditionStart];
+    let mut prev_fun_br = false;
+    for obj in expression {
+        let command = match obj {
+            Object::SVar(v) => Command::CopySymbol(projected_vars[v]),
+            Object::EVar(v) | Object::TVar(v) => Command::CopyExpr(projected_vars[v]),
+            _ => insert_command(module, obj, prev_fun_br),
+        };
+        commands.push(command);
+        prev_fun_br = **obj == Object::FunBracketL;
+    }
+    commands.push(Command::ConditionEvaluate);
+    commands
+}
+
+fn insert_command(module: &str, obj: &Object, prev_fun_br: bool) -> Command {
+    match obj {
+        Object::Symbol(image) if prev_fun_br => {
+            Command::InsertSymbol(Symbol::intern(&qualify(module, image)))
+        }
+        Object::Symbol(image) => Command::InsertSymbol(Symbol::intern(image)),
+        Object::Char(c) => Command::InsertChar(*c),
+        Object::Number(n) => Command::InsertNumber(*n),
+        Object::StrBracketL => Command::InsertStrBracketL,
+        Object::StrBracketR => Command::InsertStrBracketR,
+        Object::FunBracketL => Command::InsertFunBracketL,
+        Object::FunBracketR => Command::InsertFunBracketR,
+        Object::EVar(_) | Object::SVar(_) | Object::TVar(_) => unreachable!(),
+    }
+}
+
 fn qualify(module: &str, fun: &str) -> String {
-    if fun.contains('.') {
+    if fun.contains('.') || builtins::lookup(fun).is_some() {
         fun.to_string()
     } else {
         module.to_owned() + "." + fun
@@ -623,6 +1030,8 @@ struct State<'a> {
     border_r: usize,
     next_element: usize,
     transition_depth: usize,
+    /// Whether to drop the alternative matches of a class of holes once it is matched.
+    constrain: bool,
     projected_vars: HashMap<String, usize>,
     holes_stack: Vec<Vec<Hole<'a>>>,
     holes: Vec<Hole<'a>>,
@@ -633,6 +1042,7 @@ struct State<'a> {
 struct PatternCompile {
     commands: Vec<Command>,
     projected_vars: HashMap<String, usize>,
+    next_element: usize,
 }
 
 struct Decomposition {
@@ -645,3 +1055,48 @@ struct Hole<'a> {
     border_r: usize,
     objects: Vec<&'a Object>,
 }
+
+/// The names of the symbols the code a backend generates uses, indexed in the order they are met.
+#[derive(Default)]
+pub(crate) struct Symbols {
+    pub(crate) names: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl Symbols {
+    pub(crate) fn index(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.indices.get(name) {
+            return index;
+        }
+        self.names.push(name.to_string());
+        self.indices.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
+
+/// The addresses matching may go on at after a jump or a condition: the start, the targets of
+/// transitions and switches, the lengthening of e-variables and the commands following it, and the commands
+/// following the evaluation of conditions.
+pub(crate) fn arm_starts(commands: &[Command]) -> BTreeSet<usize> {
+    let mut starts = BTreeSet::new();
+    starts.insert(0);
+    for (address, command) in commands.iter().enumerate() {
+        match command {
+            Command::SetupTransition(target) if *target < commands.len() => {
+                starts.insert(*target);
+            }
+            Command::SwitchSymbolL(table) => {
+                starts.extend(table.iter().map(|&(_, target)| target));
+            }
+            Command::MatchEVarPrepare => {
+                starts.insert(address + 1);
+                starts.insert(address + 2);
+            }
+            Command::ConditionEvaluate => {
+                starts.insert(address + 1);
+            }
+            _ => (),
+        }
+    }
+    starts
+}
@@ -1,6 +1,10 @@
-use crate::compiler::compile_function;
-use crate::data::Command;
+use crate::compiler::{compile, compile_function, decision_trees};
+use crate::data::{sentence_starts, Command};
 use crate::parser;
+use crate::program::Program;
+use crate::symbol::Symbol;
+use crate::vm::eval_main;
+use crate::vm::tests::TEST_PROGRAM;
 
 #[cfg(test)]
 fn check_compile(fun_input: &str, commands: Vec<Command>) {
@@ -36,7 +40,7 @@ fn test02() {
             Command::MatchMoveBorderR(2),
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("A")),
+            Command::InsertSymbol(Symbol::intern("A")),
             Command::RewriteFinalize,
             Command::MatchStart,
         ],
@@ -49,23 +53,23 @@ fn test60() {
         "Clauses { A = B; B = C; C = A; }",
         vec![
             Command::SetupTransition(7),
-            Command::MatchSymbolL(String::from("A")),
+            Command::MatchSymbolL(Symbol::intern("A")),
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("B")),
+            Command::InsertSymbol(Symbol::intern("B")),
             Command::RewriteFinalize,
             Command::MatchStart,
             Command::SetupTransition(14),
-            Command::MatchSymbolL(String::from("B")),
+            Command::MatchSymbolL(Symbol::intern("B")),
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("C")),
+            Command::InsertSymbol(Symbol::intern("C")),
             Command::RewriteFinalize,
             Command::MatchStart,
-            Command::MatchSymbolL(String::from("C")),
+            Command::MatchSymbolL(Symbol::intern("C")),
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("A")),
+            Command::InsertSymbol(Symbol::intern("A")),
             Command::RewriteFinalize,
             Command::MatchStart,
         ],
@@ -80,14 +84,14 @@ fn test_palindrome() {
             Command::SetupTransition(6),
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("T")),
+            Command::InsertSymbol(Symbol::intern("T")),
             Command::RewriteFinalize,
             Command::MatchStart,
             Command::SetupTransition(13),
             Command::MatchSVarL,
             Command::MatchEmpty,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("T")),
+            Command::InsertSymbol(Symbol::intern("T")),
             Command::RewriteFinalize,
             Command::MatchStart,
             Command::SetupTransition(24),
@@ -96,16 +100,149 @@ fn test_palindrome() {
             Command::MatchEVar,
             Command::RewriteStart,
             Command::InsertFunBracketL,
-            Command::InsertSymbol(String::from("T.P")),
+            Command::InsertSymbol(Symbol::intern("T.P")),
             Command::TransplantExpr(6),
             Command::InsertFunBracketR,
             Command::RewriteFinalize,
             Command::MatchStart,
             Command::MatchEVar,
             Command::RewriteStart,
-            Command::InsertSymbol(String::from("F")),
+            Command::InsertSymbol(Symbol::intern("F")),
             Command::RewriteFinalize,
             Command::MatchStart,
         ],
     )
 }
+
+#[test]
+fn test_compile_expression() {
+    use crate::compiler::compile_expression;
+    use crate::runtime::Object;
+    assert_eq!(
+        compile_expression("T", "A (<F 'b'>) <'M.G'>").unwrap(),
+        vec![
+            Object::Symbol(Symbol::intern("A")),
+            Object::StrBracketL,
+            Object::FunBracketL,
+            Object::Symbol(Symbol::intern("T.F")),
+            Object::Char('b'),
+            Object::FunBracketR,
+            Object::StrBracketR,
+            Object::FunBracketL,
+            Object::Symbol(Symbol::intern("M.G")),
+            Object::FunBracketR,
+        ]
+    );
+    assert!(compile_expression("T", "$e.1").is_err());
+}
+
+#[test]
+fn test_render_round_trip() {
+    use crate::compiler::compile_expression;
+    use crate::runtime::{render, Object};
+    let objects = vec![
+        Object::Char('c'),
+        Object::Char('\''),
+        Object::Char('\\'),
+        Object::StrBracketL,
+        Object::Char('\t'),
+        Object::Char('\u{1}'),
+        Object::StrBracketR,
+        Object::Symbol(Symbol::intern("Word")),
+        Object::Number(42),
+        Object::Char('!'),
+    ];
+    let text = render(&objects);
+    assert_eq!(text, "'c\\'\\\\' ('\\t\\x01') Word 42 '!'");
+    assert_eq!(compile_expression("T", &text).unwrap(), objects);
+}
+
+#[test]
+fn test_decision_trees() {
+    let mut defs = compile("$MODULE T; F { (A) = 1; (A B) = 2; C = 3; }").unwrap();
+    decision_trees(&mut defs);
+    let tree = vec![
+        Command::SetupTransition(21),
+        Command::MatchStrBracketL,
+        Command::MatchSymbolL(Symbol::intern("A")),
+        Command::SetupTransition(12),
+        Command::MatchEmpty,
+        Command::MatchMoveBorderL(4),
+        Command::MatchMoveBorderR(2),
+        Command::MatchEmpty,
+        Command::RewriteStart,
+        Command::InsertNumber(1),
+        Command::RewriteFinalize,
+        Command::MatchStart,
+        Command::MatchSymbolL(Symbol::intern("B")),
+        Command::MatchEmpty,
+        Command::MatchMoveBorderL(4),
+        Command::MatchMoveBorderR(2),
+        Command::MatchEmpty,
+        Command::RewriteStart,
+        Command::InsertNumber(2),
+        Command::RewriteFinalize,
+        Command::MatchStart,
+        Command::MatchSymbolL(Symbol::intern("C")),
+        Command::MatchEmpty,
+        Command::RewriteStart,
+        Command::InsertNumber(3),
+        Command::RewriteFinalize,
+        Command::MatchStart,
+    ];
+    assert_eq!(defs["T.F"], tree);
+    assert_eq!(sentence_starts(&tree), vec![0, 12, 21]);
+    // A tree is not a chain of sentences to merge again.
+    decision_trees(&mut defs);
+    assert_eq!(defs["T.F"], tree);
+}
+
+#[test]
+fn test_decision_trees_switch() {
+    let mut defs = compile("$MODULE T; F { A = 1; B = 2; (A) = 3; A $e.1 = 4; }").unwrap();
+    decision_trees(&mut defs);
+    let sentence = |symbol: &str, number: u32| {
+        vec![
+            Command::MatchSymbolL(Symbol::intern(symbol)),
+            Command::MatchEmpty,
+            Command::RewriteStart,
+            Command::InsertNumber(number),
+            Command::RewriteFinalize,
+            Command::MatchStart,
+        ]
+    };
+    let mut tree = vec![
+        Command::SetupTransition(14),
+        Command::SwitchSymbolL(vec![(Symbol::intern("B"), 8)]),
+    ];
+    tree.extend(sentence("A", 1));
+    tree.extend(sentence("B", 2));
+    assert_eq!(defs["T.F"][..14], tree);
+    // The bracket ends the run of symbols, the symbol already switched on another.
+    assert_eq!(defs["T.F"][14], Command::SetupTransition(25));
+    assert_eq!(sentence_starts(&defs["T.F"]), vec![0, 8, 14, 25]);
+}
+
+#[test]
+fn test_decision_trees_evaluation() {
+    let chains = Program::new(compile(TEST_PROGRAM).unwrap());
+    let mut defs = compile(TEST_PROGRAM).unwrap();
+    decision_trees(&mut defs);
+    let switches = defs
+        .values()
+        .flatten()
+        .filter(|command| matches!(command, Command::SwitchSymbolL(_)))
+        .count();
+    assert!(switches > 0);
+    let trees = Program::new(defs);
+    for (name, _) in chains.functions() {
+        if name.as_str().starts_with("Test.Test") {
+            assert_eq!(
+                eval_main(&trees, name.as_str()),
+                eval_main(&chains, name.as_str()),
+                "{}",
+                name
+            );
+        }
+    }
+}
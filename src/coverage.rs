@@ -0,0 +1,175 @@
+#[cfg(test)]
+mod tests;
+
+use crate::compiler;
+use crate::data::{sentence_starts, Command, RefalModule};
+use crate::symbol::Symbol;
+use std::collections::{BTreeMap, HashMap};
+
+/// How many times each sentence of each function rewrote a call, recorded by the VM.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    functions: HashMap<Symbol, Hits>,
+}
+
+#[derive(Debug)]
+struct Hits {
+    /// The addresses of the sentences in the commands.
+    starts: Vec<usize>,
+    counts: Vec<u64>,
+}
+
+/// Where the sentences of the compiled functions are in the source files.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug)]
+struct SourceFile {
+    name: String,
+    functions: Vec<SourceFunction>,
+}
+
+#[derive(Debug)]
+struct SourceFunction {
+    /// The qualified name of the compiled function.
+    name: String,
+    line: usize,
+    /// The line of each sentence, in the order of the compiled sentences.
+    sentences: Vec<usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the sentence of `function`, compiled into `commands`, at `address` rewrote a call.
+    pub(crate) fn record(&mut self, function: Symbol, commands: &[Command], address: usize) {
+        let hits = self.functions.entry(function).or_insert_with(|| {
+            let starts = sentence_starts(commands);
+            let counts = vec![0; starts.len()];
+            Hits { starts, counts }
+        });
+        let sentence = hits.starts.partition_point(|&start| start <= address);
+        if let Some(sentence) = sentence.checked_sub(1) {
+            hits.counts[sentence] += 1;
+        }
+    }
+
+    /// How many times each sentence of `function` rewrote a call, if any did.
+    pub fn hits(&self, function: &str) -> Option<&[u64]> {
+        let hits = self.functions.get(&Symbol::intern(function))?;
+        Some(&hits.counts)
+    }
+
+    /// The coverage of the sentences in `sources`, in the lcov tracefile format:
+    /// each sentence counts as the line it starts on, each compiled function as a function.
+    pub fn lcov(&self, sources: &SourceMap) -> String {
+        let mut lines = vec![String::from("TN:")];
+        for file in &sources.files {
+            lines.push(format!("SF:{}", file.name));
+            let mut hit_lines = BTreeMap::<usize, u64>::new();
+            let mut functions_hit = 0;
+            for function in &file.functions {
+                let counts = self.counts(function);
+                let calls: u64 = counts.iter().sum();
+                functions_hit += (calls > 0) as usize;
+                lines.push(format!("FN:{},{}", function.line, function.name));
+                lines.push(format!("FNDA:{},{}", calls, function.name));
+                for (&line, count) in function.sentences.iter().zip(counts) {
+                    *hit_lines.entry(line).or_default() += count;
+                }
+            }
+            lines.push(format!("FNF:{}", file.functions.len()));
+            lines.push(format!("FNH:{}", functions_hit));
+            for (line, count) in &hit_lines {
+                lines.push(format!("DA:{},{}", line, count));
+            }
+            lines.push(format!("LF:{}", hit_lines.len()));
+            lines.push(format!(
+                "LH:{}",
+                hit_lines.values().filter(|&&count| count > 0).count()
+            ));
+            lines.push(String::from("end_of_record"));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// The share of the sentences in `sources` that rewrote a call, by file,
+    /// followed by those that never did.
+    pub fn summary(&self, sources: &SourceMap) -> String {
+        let mut lines = Vec::new();
+        let mut never = Vec::new();
+        let (mut covered, mut total) = (0, 0);
+        for file in &sources.files {
+            let (mut file_covered, mut file_total) = (0, 0);
+            for function in &file.functions {
+                let counts = self.counts(function);
+                for (i, (line, count)) in function.sentences.iter().zip(counts).enumerate() {
+                    file_total += 1;
+                    if count > 0 {
+                        file_covered += 1;
+                    } else {
+                        never.push(format!(
+                            "{}:{}: {} sentence {} never selected",
+                            file.name,
+                            line,
+                            function.name,
+                            i + 1
+                        ));
+                    }
+                }
+            }
+            lines.push(sentences_covered(&file.name, file_covered, file_total));
+            covered += file_covered;
+            total += file_total;
+        }
+        lines.push(sentences_covered("total", covered, total));
+        lines.extend(never);
+        lines.join("\n")
+    }
+
+    /// The hits of the sentences of `function`, none if it never rewrote a call.
+    fn counts(&self, function: &SourceFunction) -> Vec<u64> {
+        match self.functions.get(&Symbol::intern(&function.name)) {
+            Some(hits) => hits.counts.clone(),
+            None => vec![0; function.sentences.len()],
+        }
+    }
+}
+
+fn sentences_covered(name: &str, covered: usize, total: usize) -> String {
+    let percent = if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    };
+    format!(
+        "{}: {} of {} sentences covered ({:.1}%)",
+        name, covered, total, percent
+    )
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the functions of `module`, parsed from `file`.
+    pub fn add(&mut self, file: &str, module: &RefalModule) {
+        let functions = compiler::compiled_functions(module)
+            .into_iter()
+            .map(|(name, f)| SourceFunction {
+                name,
+                line: f.location.line,
+                sentences: f.sentences.iter().map(|s| s.location.line).collect(),
+            })
+            .collect();
+        self.files.push(SourceFile {
+            name: file.to_string(),
+            functions,
+        });
+    }
+}
@@ -0,0 +1,80 @@
+use crate::compiler::{compile, compile_expression};
+use crate::coverage::{Coverage, SourceFile, SourceFunction, SourceMap};
+use crate::host::MemoryHost;
+use crate::parser::parse_input;
+use crate::program::Program;
+use crate::vm::{eval_expression, Limits, Object};
+
+#[cfg(test)]
+fn evaluate(program: &Program, expression: &[Object]) -> Coverage {
+    let mut coverage = Coverage::new();
+    let result = eval_expression(
+        program,
+        expression,
+        &mut MemoryHost::default(),
+        Limits::default(),
+        None,
+        None,
+        Some(&mut coverage),
+    );
+    assert!(result.is_ok());
+    coverage
+}
+
+#[test]
+fn test_coverage() {
+    let source = "$MODULE C; F { = A; $e.1 = $e.1 $e.1; } G { = A; $e.1 = $e.1 $e.1; }";
+    let program = Program::new(compile(source).unwrap());
+    let expression = compile_expression("C", "<F 'a'> <F 'a'>").unwrap();
+    let coverage = evaluate(&program, &expression);
+    assert_eq!(coverage.hits("C.F"), Some(&[0, 2][..]));
+    assert_eq!(coverage.hits("C.G"), None);
+
+    let function = |name: &str, line| SourceFunction {
+        name: name.to_string(),
+        line,
+        sentences: vec![line + 1, line + 2],
+    };
+    let sources = SourceMap {
+        files: vec![SourceFile {
+            name: String::from("C.refal"),
+            functions: vec![function("C.F", 1), function("C.G", 5)],
+        }],
+    };
+    assert_eq!(
+        coverage.lcov(&sources),
+        "TN:\nSF:C.refal\nFN:1,C.F\nFNDA:2,C.F\nFN:5,C.G\nFNDA:0,C.G\nFNF:2\nFNH:1\n\
+         DA:2,0\nDA:3,2\nDA:6,0\nDA:7,0\nLF:4\nLH:1\nend_of_record\n"
+    );
+    assert_eq!(
+        coverage.summary(&sources),
+        "C.refal: 1 of 4 sentences covered (25.0%)\n\
+         total: 1 of 4 sentences covered (25.0%)\n\
+         C.refal:2: C.F sentence 1 never selected\n\
+         C.refal:6: C.G sentence 1 never selected\n\
+         C.refal:7: C.G sentence 2 never selected"
+    );
+}
+
+#[test]
+fn test_source_lines() {
+    let source = "$MODULE Cov;
+Sign {
+  0 = Zero;
+  $s.1 , <Compare $s.1 0> : {
+    '-' = Negative;
+    '+' = Positive;
+  };
+}";
+    let program = Program::new(compile(source).unwrap());
+    let mut sources = SourceMap::new();
+    sources.add("Cov.refal", &parse_input(source).unwrap());
+    let expression = compile_expression("Cov", "<Sign 0> <Sign 5>").unwrap();
+    let coverage = evaluate(&program, &expression);
+    assert_eq!(
+        coverage.summary(&sources),
+        "Cov.refal: 3 of 4 sentences covered (75.0%)\n\
+         total: 3 of 4 sentences covered (75.0%)\n\
+         Cov.refal:5: Cov.Sign:1 sentence 1 never selected"
+    );
+}
@@ -1,16 +1,208 @@
+use crate::symbol::Symbol;
+use std::fmt;
+use std::ops::Range;
 use std::result;
 
 pub type Result<T> = result::Result<T, Error>;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Error {
-    Parsing,
+    Parsing(Vec<Diagnostic>),
+    /// The program is syntactically correct but meaningless, e.g. uses an unbound variable.
+    Semantic(Vec<Diagnostic>),
+    /// The modules of a program do not fit together, e.g. a call refers to a missing module.
+    Linking(Vec<Diagnostic>),
+    /// A module named on the command line is not found in the search path.
+    ModuleNotFound(String),
+    Io(String),
+    /// A compiled program file is corrupt or of another version.
+    Bytecode(String),
+    /// A compiled program calls functions it does not define.
+    UnresolvedCalls(Vec<String>),
     IllegalState,
+    /// No sentence of `function` matches `argument`.
+    RecognitionImpossible {
+        function: String,
+        argument: String,
+    },
+    /// `function` is called but has no definition.
+    UndefinedFunction {
+        function: String,
+        argument: String,
+    },
+    /// The view is not a well-formed expression, e.g. a call without a function symbol.
+    MalformedView(String),
+    /// Evaluation was stopped after `steps` steps, going on would exceed `limit`.
+    LimitExceeded {
+        limit: Limit,
+        steps: u64,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+/// A bound on the resources taken by an evaluation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    /// The number of steps, i.e. of calls evaluated.
+    Steps(u64),
+    /// The number of nodes of the view.
+    Nodes(usize),
+    /// The number of calls waiting to be evaluated.
+    Calls(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Steps(n) => write!(f, "{} steps", n),
+            Limit::Nodes(n) => write!(f, "{} view nodes", n),
+            Limit::Calls(n) => write!(f, "{} pending calls", n),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parsing(diagnostics)
+            | Error::Semantic(diagnostics)
+            | Error::Linking(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Error::ModuleNotFound(module) => write!(f, "module `{}` not found", module),
+            Error::Io(message) => write!(f, "{}", message),
+            Error::Bytecode(message) => write!(f, "invalid compiled program: {}", message),
+            Error::UnresolvedCalls(names) => {
+                write!(f, "calls to undefined functions: {}", names.join(", "))
+            }
+            Error::IllegalState => write!(f, "illegal state"),
+            Error::RecognitionImpossible { function, argument } => {
+                write!(f, "recognition impossible: <{} {}>", function, argument)
+            }
+            Error::UndefinedFunction { function, argument } => {
+                write!(f, "undefined function: <{} {}>", function, argument)
+            }
+            Error::MalformedView(view) => write!(f, "malformed view: {}", view),
+            Error::LimitExceeded { limit, steps } => {
+                write!(f, "limit of {} exceeded after {} steps", limit, steps)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A position in source text. `line` and `column` are 1-based, `range` is in bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub range: Range<usize>,
+}
+
+impl Location {
+    pub fn new(text: &str, range: Range<usize>) -> Self {
+        let before = &text[..range.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            range,
+        }
+    }
+}
+
+/// Whether a diagnostic stops the compilation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    /// The program compiles but likely does not do what was meant, e.g. has a dead sentence.
+    Warning,
+}
+
+/// A message about a place in a source file, with the offending line underlined.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub location: Location,
+    pub severity: Severity,
+    pub message: String,
+    pub snippet: String,
+}
+
+impl Diagnostic {
+    pub fn new(file: &str, text: &str, range: Range<usize>, message: String) -> Self {
+        let location = Location::new(text, range);
+        let snippet = snippet(text, &location);
+        Diagnostic {
+            file: file.to_string(),
+            location,
+            severity: Severity::Error,
+            message,
+            snippet,
+        }
+    }
+
+    pub fn warning(file: &str, text: &str, range: Range<usize>, message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(file, text, range, message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}\n{}",
+            self.file,
+            self.location.line,
+            self.location.column,
+            match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            self.message,
+            self.snippet
+        )
+    }
+}
+
+fn snippet(text: &str, location: &Location) -> String {
+    let start = location.range.start;
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+    let line = &text[line_start..line_end];
+    let end = location.range.end.min(line_end).max(start);
+    let width = text[start..end].chars().count().max(1);
+    let padding: String = text[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let number = location.line.to_string();
+    format!(
+        "{} | {}\n{} | {}{}",
+        number,
+        line,
+        " ".repeat(number.len()),
+        padding,
+        "^".repeat(width)
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Object {
     Symbol(String),
+    /// A character, written between single quotes.
+    Char(char),
+    /// A macrodigit, i.e. a number below 2^32.
+    Number(u32),
     StrBracketL,
     StrBracketR,
     FunBracketL,
@@ -20,30 +212,82 @@ pub enum Object {
     TVar(String),
 }
 
-#[derive(Debug, PartialEq)]
+/// A sentence `pattern, conditions = rewrite;`, or `pattern, conditions, rewrite : { block };`
+/// where the block is applied to the rewrite.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sentence {
     pub pattern: Vec<Object>,
+    pub conditions: Vec<Condition>,
     pub rewrite: Vec<Object>,
+    pub block: Option<Vec<Sentence>>,
+    pub location: Location,
+}
+
+/// A condition `, result : pattern` that must hold for a sentence to apply.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub result: Vec<Object>,
+    pub pattern: Vec<Object>,
+}
+
+impl Sentence {
+    /// The expressions that may contain calls: the results of the conditions and the rewrite,
+    /// including those of the sentences of the block.
+    pub fn results(&self) -> Vec<&Vec<Object>> {
+        let mut results: Vec<_> = self.conditions.iter().map(|c| &c.result).collect();
+        results.push(&self.rewrite);
+        for sentence in self.block.iter().flatten() {
+            results.extend(sentence.results());
+        }
+        results
+    }
+
+    pub fn results_mut(&mut self) -> Vec<&mut Vec<Object>> {
+        let mut results: Vec<_> = self.conditions.iter_mut().map(|c| &mut c.result).collect();
+        results.push(&mut self.rewrite);
+        for sentence in self.block.iter_mut().flatten() {
+            results.extend(sentence.results_mut());
+        }
+        results
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Function {
     pub name: String,
     pub sentences: Vec<Sentence>,
+    /// Location of the function name.
+    pub location: Location,
+    /// Whether the function is declared `$ENTRY`, i.e. may be called from other modules.
+    pub entry: bool,
+}
+
+/// A function declared `$EXTERN`, i.e. an entry of another module called by its bare name.
+#[derive(Debug, PartialEq)]
+pub struct Extern {
+    pub name: String,
+    pub location: Location,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct RefalModule {
     pub name: String,
     pub functions: Vec<Function>,
+    pub externs: Vec<Extern>,
+    /// Location of the module name.
+    pub location: Location,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Command {
     MatchStart,
     MatchEmpty,
-    MatchSymbolL(String),
-    MatchSymbolR(String),
+    MatchSymbolL(Symbol),
+    MatchSymbolR(Symbol),
+    MatchNumberL(u32),
+    MatchNumberR(u32),
+    MatchCharL(char),
+    MatchCharR(char),
     MatchStrBracketL,
     MatchStrBracketR,
     MatchSVarL,
@@ -60,17 +304,43 @@ pub enum Command {
     MatchMoveBorderL(usize),
     MatchMoveBorderR(usize),
     SetupTransition(usize),
+    /// Goes on at the address paired with the symbol right of the left border, if any,
+    /// without matching it; goes on with the next command otherwise.
+    SwitchSymbolL(Vec<(Symbol, usize)>),
     ConstrainLengthen(usize),
 
+    /// Starts building the result of a condition, evaluated at `ConditionEvaluate`.
+    ConditionStart,
+    ConditionEvaluate,
+
     RewriteStart,
     InsertStrBracketL,
     InsertStrBracketR,
     InsertFunBracketL,
     InsertFunBracketR,
-    InsertSymbol(String),
+    InsertSymbol(Symbol),
+    InsertNumber(u32),
+    InsertChar(char),
     CopySymbol(usize),
     CopyExpr(usize),
     TransplantObject(usize),
     TransplantExpr(usize),
     RewriteFinalize,
 }
+
+/// The addresses where the sentences of a compiled function start, in order: the first command,
+/// the targets of `SetupTransition`, where matching goes on when a sentence fails, and those of
+/// `SwitchSymbolL`.
+pub fn sentence_starts(commands: &[Command]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for command in commands {
+        match command {
+            Command::SetupTransition(target) => starts.push(*target),
+            Command::SwitchSymbolL(table) => starts.extend(table.iter().map(|&(_, target)| target)),
+            _ => (),
+        }
+    }
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests;
+
+use crate::data::{sentence_starts, Command, Object};
+use crate::printer::render;
+use std::collections::HashMap;
+
+/// Lists the commands of all functions of a definition table, functions sorted by name.
+pub fn disassemble(defs: &HashMap<String, Vec<Command>>) -> String {
+    let mut names: Vec<_> = defs.keys().collect();
+    names.sort();
+    let functions: Vec<_> = names
+        .into_iter()
+        .map(|name| disassemble_function(name, &defs[name]))
+        .collect();
+    functions.join("\n")
+}
+
+/// Lists the commands of function `name` one per line with their addresses,
+/// under a header for each sentence.
+pub fn disassemble_function(name: &str, commands: &[Command]) -> String {
+    let starts = sentence_starts(commands);
+    let sentence = |address: usize| starts.partition_point(|&start| start <= address);
+    let width = commands.len().saturating_sub(1).to_string().len();
+
+    let mut text = format!("{} {{\n", name);
+    for (address, command) in commands.iter().enumerate() {
+        if starts.binary_search(&address).is_ok() {
+            text.push_str(&format!("  sentence {}:\n", sentence(address)));
+        }
+        let line = match command {
+            Command::SetupTransition(target) if *target < commands.len() => {
+                format!(
+                    "{:0w$}  (sentence {})",
+                    target,
+                    sentence(*target),
+                    w = width
+                )
+            }
+            Command::SetupTransition(target) => format!("{:0w$}  (none)", target, w = width),
+            Command::SwitchSymbolL(table) => table
+                .iter()
+                .map(|(symbol, target)| {
+                    format!(
+                        "{} {:0w$} (sentence {})",
+                        render(&[Object::Symbol(symbol.to_string())]),
+                        target,
+                        sentence(*target),
+                        w = width
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => operand(command),
+        };
+        let mnemonic = format!("{:?}", command);
+        let mnemonic = mnemonic.split('(').next().unwrap_or_default();
+        text.push_str(
+            format!("    {:0w$}  {:<18} {}", address, mnemonic, line, w = width).trim_end(),
+        );
+        text.push('\n');
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// The operand of a command: an object to match or insert, a projection number `#n`,
+/// or the number of alternatives a `ConstrainLengthen` drops.
+fn operand(command: &Command) -> String {
+    match command {
+        Command::MatchSymbolL(s) | Command::MatchSymbolR(s) | Command::InsertSymbol(s) => {
+            render(&[Object::Symbol(s.to_string())])
+        }
+        Command::MatchNumberL(n) | Command::MatchNumberR(n) | Command::InsertNumber(n) => {
+            n.to_string()
+        }
+        Command::MatchCharL(c) | Command::MatchCharR(c) | Command::InsertChar(c) => {
+            render(&[Object::Char(*c)])
+        }
+        Command::MatchSVarLProj(n)
+        | Command::MatchSVarRProj(n)
+        | Command::MatchEVarLProj(n)
+        | Command::MatchEVarRProj(n)
+        | Command::MatchMoveBorderL(n)
+        | Command::MatchMoveBorderR(n)
+        | Command::CopySymbol(n)
+        | Command::CopyExpr(n)
+        | Command::TransplantObject(n)
+        | Command::TransplantExpr(n) => format!("#{}", n),
+        Command::ConstrainLengthen(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
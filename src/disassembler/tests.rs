@@ -0,0 +1,72 @@
+use crate::compiler::compile;
+use crate::disassembler::disassemble;
+
+#[test]
+fn test_disassemble() {
+    let defs = compile("$MODULE T; P { = T; $s.1 = T; $s.1 $e.2 $s.1 = <P $e.2>; $e.1 = F; }");
+    assert_eq!(
+        disassemble(&defs.unwrap()),
+        r#"T.P {
+  sentence 1:
+    00  SetupTransition    06  (sentence 2)
+    01  MatchEmpty
+    02  RewriteStart
+    03  InsertSymbol       T
+    04  RewriteFinalize
+    05  MatchStart
+  sentence 2:
+    06  SetupTransition    13  (sentence 3)
+    07  MatchSVarL
+    08  MatchEmpty
+    09  RewriteStart
+    10  InsertSymbol       T
+    11  RewriteFinalize
+    12  MatchStart
+  sentence 3:
+    13  SetupTransition    24  (sentence 4)
+    14  MatchSVarL
+    15  MatchSVarRProj     #3
+    16  MatchEVar
+    17  RewriteStart
+    18  InsertFunBracketL
+    19  InsertSymbol       "T.P"
+    20  TransplantExpr     #6
+    21  InsertFunBracketR
+    22  RewriteFinalize
+    23  MatchStart
+  sentence 4:
+    24  MatchEVar
+    25  RewriteStart
+    26  InsertSymbol       F
+    27  RewriteFinalize
+    28  MatchStart
+}
+"#
+    );
+}
+
+#[test]
+fn test_disassemble_sorted() {
+    let defs = compile("$MODULE T; B { = 'b' 7; } A { = ; }").unwrap();
+    assert_eq!(
+        disassemble(&defs),
+        "T.A {
+  sentence 1:
+    0  MatchEmpty
+    1  RewriteStart
+    2  RewriteFinalize
+    3  MatchStart
+}
+
+T.B {
+  sentence 1:
+    0  MatchEmpty
+    1  RewriteStart
+    2  InsertChar         'b'
+    3  InsertNumber       7
+    4  RewriteFinalize
+    5  MatchStart
+}
+"
+    );
+}
@@ -0,0 +1,341 @@
+#[cfg(test)]
+mod tests;
+
+use crate::data::*;
+use crate::parser;
+use crate::printer::{push_expression, render};
+use std::ops::Range;
+
+/// The width lines are wrapped at.
+const WIDTH: usize = 100;
+/// The widest pattern that the `=` of the other sentences of its body is aligned with.
+const ALIGN_LIMIT: usize = 40;
+
+pub fn format(text: &str) -> Result<String> {
+    format_file("<input>", text)
+}
+
+/// Formats the module in the source text of `file`: one sentence per line, indented by four
+/// spaces, with the `=` of the sentences of a body aligned and long rewrites wrapped. Comments
+/// are kept before the sentence or function they precede, or at the end of the line they end.
+/// Since sentences are printed from their objects, a comment inside a sentence is moved after
+/// its `;`, or to the start of its block if it comes before the block.
+pub fn format_file(file: &str, text: &str) -> Result<String> {
+    let module = parser::parse_file(file, text)?;
+    let mut formatter = Formatter {
+        text,
+        comments: comments(text),
+        next_comment: 0,
+        lines: Vec::new(),
+        last: 0,
+    };
+    formatter.module(&module);
+    let mut formatted = formatter.lines.join("\n");
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+struct Formatter<'a> {
+    text: &'a str,
+    comments: Vec<Range<usize>>,
+    /// The first comment not yet printed.
+    next_comment: usize,
+    lines: Vec<String>,
+    /// Where the last part of the source printed ends.
+    last: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn module(&mut self, module: &RefalModule) {
+        self.comments_before(module.location.range.start, "");
+        self.lines.push(format!("$MODULE {};", module.name));
+        self.last = module.location.range.end;
+        let header_end = module
+            .externs
+            .iter()
+            .map(|e| e.location.range.start)
+            .chain(module.functions.iter().map(|f| f.location.range.start))
+            .min()
+            .unwrap_or(self.text.len());
+        self.comments_before(header_end, "");
+        if !module.externs.is_empty() {
+            let names: Vec<_> = module.externs.iter().map(|e| e.name.as_str()).collect();
+            self.lines.push(format!("$EXTERN {};", names.join(", ")));
+        }
+        for f in &module.functions {
+            let start = f.location.range.start;
+            if self
+                .pending_comment(start)
+                .is_some_and(|c| !self.trailing(&c))
+            {
+                self.lines.push(String::new());
+            }
+            self.comments_before(start, "");
+            // A comment right above a function stays attached to it.
+            if !self.lines.last().is_some_and(|line| line.is_empty())
+                && (!self.after_comment() || self.blank_between(self.last, start))
+            {
+                self.lines.push(String::new());
+            }
+            self.function(f);
+        }
+        if self
+            .pending_comment(self.text.len())
+            .is_some_and(|c| !self.trailing(&c))
+        {
+            self.lines.push(String::new());
+        }
+        self.comments_before(self.text.len(), "");
+    }
+
+    fn function(&mut self, f: &Function) {
+        let entry = if f.entry { "$ENTRY " } else { "" };
+        self.lines.push(format!("{}{} {{", entry, f.name));
+        let end = f.sentences.last().map_or(f.location.range.end, |s| {
+            s.location.range.end.max(f.location.range.end)
+        });
+        let close = self.find_close(end);
+        self.last = f.location.range.end;
+        self.sentences(&f.sentences, "    ", close);
+        self.lines.push(String::from("}"));
+        self.last = close + 1;
+    }
+
+    /// Prints the sentences of a function or a block, whose `}` is at `close`.
+    fn sentences(&mut self, sentences: &[Sentence], indent: &str, close: usize) {
+        let column = sentences
+            .iter()
+            .filter(|s| s.conditions.is_empty() && s.block.is_none())
+            .map(|s| width(&left_side(s)))
+            .filter(|&width| width <= ALIGN_LIMIT)
+            .max()
+            .unwrap_or(0);
+        for (i, sentence) in sentences.iter().enumerate() {
+            let start = sentence.location.range.start;
+            self.comments_before(start, indent);
+            if i > 0
+                && !self.after_comment()
+                && self.blank_between(self.last, start)
+                && !self.lines.last().is_some_and(|line| line.is_empty())
+            {
+                self.lines.push(String::new());
+            }
+            self.sentence(sentence, indent, column);
+            self.last = self.last.max(sentence.location.range.end);
+        }
+        self.comments_before(close, indent);
+    }
+
+    fn sentence(&mut self, sentence: &Sentence, indent: &str, column: usize) {
+        let mut line = format!("{}{}", indent, left_side(sentence));
+        let block = match &sentence.block {
+            Some(block) => block,
+            None => {
+                let padding = column.saturating_sub(width(&line) - indent.len());
+                line.push_str(&" ".repeat(padding));
+                line.push_str(if width(&line) > indent.len() {
+                    " ="
+                } else {
+                    "="
+                });
+                self.rewrite(line, &sentence.rewrite, indent);
+                return;
+            }
+        };
+        let one_line = format!("{}{}", indent, sentence);
+        let range = &sentence.location.range;
+        if width(&one_line) <= WIDTH && self.pending_comment(range.end).is_none() {
+            self.lines.push(one_line);
+            self.last = range.end;
+            return;
+        }
+        line.push(',');
+        push_expression(&mut line, &sentence.rewrite);
+        line.push_str(" : {");
+        self.lines.push(line);
+        let end = block.last().map_or(range.start, |s| s.location.range.end);
+        let close = self.find_close(end);
+        self.sentences(block, &format!("{}    ", indent), close);
+        self.lines.push(format!("{}}};", indent));
+        self.last = close + 1;
+    }
+
+    /// Prints `line`, the start of a sentence up to the `=`, followed by `rewrite`, wrapped
+    /// between its terms when too long. The lines continued are indented under its first term.
+    fn rewrite(&mut self, mut line: String, rewrite: &[Object], indent: &str) {
+        let terms = terms(rewrite);
+        let one_line = width(&line) + terms.iter().map(|t| width(t) + 1).sum::<usize>() + 1;
+        if one_line <= WIDTH {
+            for term in &terms {
+                line.push(' ');
+                line.push_str(term);
+            }
+            line.push_str(if terms.is_empty() { " ;" } else { ";" });
+            self.lines.push(line);
+            return;
+        }
+        let continuation = match width(&line) + 1 {
+            column if column <= WIDTH / 2 => column,
+            _ => indent.len() + 8,
+        };
+        let mut first = true;
+        for (i, term) in terms.iter().enumerate() {
+            let semicolon = usize::from(i + 1 == terms.len());
+            if !first && width(&line) + 1 + width(term) + semicolon > WIDTH {
+                self.lines.push(line);
+                line = " ".repeat(continuation - 1);
+            }
+            line.push(' ');
+            line.push_str(term);
+            first = false;
+        }
+        line.push(';');
+        self.lines.push(line);
+    }
+
+    /// Prints the comments before `pos`: those ending a line at its end, the others on lines
+    /// of their own indented by `indent`.
+    fn comments_before(&mut self, pos: usize, indent: &str) {
+        while let Some(comment) = self.pending_comment(pos) {
+            let image = &self.text[comment.clone()];
+            let trailing = self.trailing(&comment);
+            match self.lines.last_mut() {
+                Some(line) if trailing && !line.is_empty() => {
+                    line.push_str("  ");
+                    line.push_str(image);
+                }
+                _ => {
+                    if self.blank_between(self.last, comment.start)
+                        && self
+                            .lines
+                            .last()
+                            .is_some_and(|line| !line.is_empty() && !line.ends_with('{'))
+                    {
+                        self.lines.push(String::new());
+                    }
+                    self.lines.push(format!("{}{}", indent, image));
+                }
+            }
+            self.last = comment.end;
+            self.next_comment += 1;
+        }
+    }
+
+    /// Whether `comment` ends a line of the source rather than being on a line of its own.
+    fn trailing(&self, comment: &Range<usize>) -> bool {
+        let line_start = self.text[..comment.start].rfind('\n').map_or(0, |i| i + 1);
+        !self.text[line_start..comment.start].trim().is_empty()
+    }
+
+    fn pending_comment(&self, pos: usize) -> Option<Range<usize>> {
+        self.comments
+            .get(self.next_comment)
+            .filter(|comment| comment.start < pos)
+            .cloned()
+    }
+
+    /// Whether the last part printed is a comment on a line of its own.
+    fn after_comment(&self) -> bool {
+        self.next_comment > 0 && {
+            let comment = &self.comments[self.next_comment - 1];
+            comment.end == self.last && !self.trailing(comment)
+        }
+    }
+
+    /// Whether there is an empty line in the source between `start` and `end`.
+    fn blank_between(&self, start: usize, end: usize) -> bool {
+        let lines: Vec<_> = self.text[start.min(end)..end].split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|l| l.trim().is_empty())
+    }
+
+    /// The position of the first `}` from `pos` on, outside comments.
+    fn find_close(&self, pos: usize) -> usize {
+        let mut offset = pos;
+        for comment in &self.comments {
+            if comment.end <= offset {
+                continue;
+            }
+            match self.text[offset..comment.start.max(offset)].find('}') {
+                Some(i) => return offset + i,
+                None => offset = comment.end,
+            }
+        }
+        self.text[offset..]
+            .find('}')
+            .map_or(self.text.len(), |i| offset + i)
+    }
+}
+
+/// The part of a sentence before its `=` or its block: the pattern and the conditions.
+fn left_side(sentence: &Sentence) -> String {
+    let mut text = render(&sentence.pattern);
+    for condition in &sentence.conditions {
+        text.push(',');
+        push_expression(&mut text, &condition.result);
+        text.push_str(" :");
+        push_expression(&mut text, &condition.pattern);
+    }
+    text
+}
+
+/// Renders the terms of an expression, runs of characters being one term.
+fn terms(objects: &[Object]) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    for (i, object) in objects.iter().enumerate() {
+        match object {
+            Object::StrBracketL | Object::FunBracketL => depth += 1,
+            Object::StrBracketR | Object::FunBracketR => depth -= 1,
+            Object::Char(_) if matches!(objects.get(i + 1), Some(Object::Char(_))) => continue,
+            _ => (),
+        }
+        if depth == 0 {
+            terms.push(render(&objects[start..=i]));
+            start = i + 1;
+        }
+    }
+    terms
+}
+
+/// The comments in `text`: `/* ... */`, and lines starting with `*`.
+fn comments(text: &str) -> Vec<Range<usize>> {
+    let mut comments = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                let end = text[i + 2..]
+                    .find("*/")
+                    .map_or(text.len(), |j| i + 2 + j + 2);
+                comments.push(i..end);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            }
+            '*' if i == 0 || text[..i].ends_with('\n') => {
+                let end = text[i..].find('\n').map_or(text.len(), |j| i + j);
+                comments.push(i..end);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            }
+            '\'' | '"' => {
+                while let Some((_, next)) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if next == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    comments
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
@@ -0,0 +1,112 @@
+use crate::data::Error;
+use crate::formatter::format;
+use crate::parser;
+use crate::vm::tests::TEST_PROGRAM;
+
+#[test]
+fn test_format() {
+    let input = "/* header */
+$MODULE   M ;  /* the module */
+$EXTERN Add,Prout;
+/* Go is the entry */
+$ENTRY Go{=<Prout <F 1 2>>;}
+/* F does things */
+
+F {
+  /* first */
+  A =B;   /* trailing */
+
+  $s.X $e.1 , <Compare $s.X 10>:'+'=($s.X)<F $e.1>;
+    ($e.1)$s.2 = ;
+    $e.1, <G $e.1> : { A = B; ($e.3) = 'it\\'s'; };
+  $e.1, <G $e.1 $e.1 $e.1 $e.1 $e.1 $e.1 $e.1 $e.1> : { A = <LongFunctionName $e.1 $e.1 $e.1 $e.1>; $e.2 = $e.2 /* inner */; };
+  /* before close */
+}
+G { = 'a long rewrite of many words' <G 'and more words'> <G 'and more words'> <G 'and even more'> (A B C); }
+H{}
+
+/* the end */
+";
+    let expected = "/* header */
+$MODULE M;  /* the module */
+$EXTERN Add, Prout;
+
+/* Go is the entry */
+$ENTRY Go {
+    = <Prout <F 1 2>>;
+}
+
+/* F does things */
+
+F {
+    /* first */
+    A           = B;  /* trailing */
+
+    $s.X $e.1, <Compare $s.X 10> : '+' = ($s.X) <F $e.1>;
+    ($e.1) $s.2 = ;
+    $e.1, <G $e.1> : { A = B; ($e.3) = 'it\\'s'; };
+    $e.1, <G $e.1 $e.1 $e.1 $e.1 $e.1 $e.1 $e.1 $e.1> : {
+        A    = <LongFunctionName $e.1 $e.1 $e.1 $e.1>;
+        $e.2 = $e.2;  /* inner */
+    };
+    /* before close */
+}
+
+G {
+    = 'a long rewrite of many words' <G 'and more words'> <G 'and more words'> <G 'and even more'>
+      (A B C);
+}
+
+H {
+}
+
+/* the end */
+";
+    assert_eq!(format(input).unwrap(), expected);
+    assert_eq!(format(expected).unwrap(), expected);
+}
+
+#[test]
+fn test_comments_in_sentences() {
+    let input = "$MODULE M;
+F {
+  A /* a */ B = /* b */ C;
+  D = E /* e */;
+  $e.1, <F
+    /* f */ $e.1> : { = ; };
+}
+";
+    let expected = "$MODULE M;
+
+F {
+    A B = C;  /* a */  /* b */
+    D   = E;  /* e */
+    $e.1, <F $e.1> : {
+        /* f */
+        = ;
+    };
+}
+";
+    assert_eq!(format(input).unwrap(), expected);
+    assert_eq!(format(expected).unwrap(), expected);
+}
+
+#[test]
+fn test_test_program() {
+    let formatted = format(TEST_PROGRAM).unwrap();
+    assert_eq!(format(&formatted).unwrap(), formatted);
+    assert_eq!(
+        parser::parse_input(&formatted).unwrap().to_string(),
+        parser::parse_input(TEST_PROGRAM).unwrap().to_string()
+    );
+    let comments = |text: &str| text.matches("/*").count();
+    assert_eq!(comments(&formatted), comments(TEST_PROGRAM));
+}
+
+#[test]
+fn test_syntax_error() {
+    assert!(matches!(
+        format("$MODULE M; F { A = "),
+        Err(Error::Parsing(_))
+    ));
+}
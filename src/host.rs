@@ -0,0 +1,179 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+/// How a file is opened by `<Open s.Mode s.Channel e.FileName>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Read,
+    Write,
+    Append,
+}
+
+/// Line-oriented access to the console and to files bound to numbered channels.
+/// Lines are passed without their terminators.
+pub trait Host {
+    /// Reads the next line of the console, or `None` at the end of input.
+    fn read_line(&mut self) -> Option<String>;
+    fn write_line(&mut self, line: &str) -> io::Result<()>;
+    /// Binds `channel` to the file at `path`, closing whatever it was bound to.
+    fn open(&mut self, channel: u32, path: &str, mode: Mode) -> io::Result<()>;
+    /// Reads the next line of the file bound to `channel`, or `None` at the end of the file.
+    fn get(&mut self, channel: u32) -> io::Result<Option<String>>;
+    fn put(&mut self, channel: u32, line: &str) -> io::Result<()>;
+    fn close(&mut self, channel: u32);
+}
+
+fn not_open(channel: u32) -> io::Error {
+    let message = format!("channel {} is not open for this operation", channel);
+    io::Error::other(message)
+}
+
+enum Channel {
+    Reader(BufReader<File>),
+    Writer(File),
+}
+
+/// The process's standard streams and the file system.
+#[derive(Default)]
+pub struct StdHost {
+    channels: HashMap<u32, Channel>,
+}
+
+impl StdHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn read_line(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+impl Host for StdHost {
+    fn read_line(&mut self) -> Option<String> {
+        read_line(&mut io::stdin().lock()).ok().flatten()
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(io::stdout(), "{}", line)
+    }
+
+    fn open(&mut self, channel: u32, path: &str, mode: Mode) -> io::Result<()> {
+        self.channels.remove(&channel);
+        let file = match mode {
+            Mode::Read => Channel::Reader(BufReader::new(File::open(path)?)),
+            Mode::Write => Channel::Writer(File::create(path)?),
+            Mode::Append => {
+                Channel::Writer(OpenOptions::new().append(true).create(true).open(path)?)
+            }
+        };
+        self.channels.insert(channel, file);
+        Ok(())
+    }
+
+    fn get(&mut self, channel: u32) -> io::Result<Option<String>> {
+        match self.channels.get_mut(&channel) {
+            Some(Channel::Reader(reader)) => read_line(reader),
+            _ => Err(not_open(channel)),
+        }
+    }
+
+    fn put(&mut self, channel: u32, line: &str) -> io::Result<()> {
+        match self.channels.get_mut(&channel) {
+            Some(Channel::Writer(writer)) => writeln!(writer, "{}", line),
+            _ => Err(not_open(channel)),
+        }
+    }
+
+    fn close(&mut self, channel: u32) {
+        self.channels.remove(&channel);
+    }
+}
+
+/// A host keeping the console and the files in memory, e.g. to test programs doing I/O.
+#[derive(Debug, Default)]
+pub struct MemoryHost {
+    /// Lines still to be read from the console.
+    pub input: VecDeque<String>,
+    /// Lines written to the console.
+    pub output: Vec<String>,
+    /// The lines of each file by path.
+    pub files: HashMap<String, Vec<String>>,
+    /// The path, mode and next line to read of each open channel.
+    channels: HashMap<u32, (String, Mode, usize)>,
+}
+
+impl MemoryHost {
+    pub fn new(input: &[&str]) -> Self {
+        MemoryHost {
+            input: input.iter().map(|line| line.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Host for MemoryHost {
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.output.push(line.to_string());
+        Ok(())
+    }
+
+    fn open(&mut self, channel: u32, path: &str, mode: Mode) -> io::Result<()> {
+        match mode {
+            Mode::Read if !self.files.contains_key(path) => {
+                let message = format!("no such file: {}", path);
+                return Err(io::Error::new(io::ErrorKind::NotFound, message));
+            }
+            Mode::Read => (),
+            Mode::Write => {
+                self.files.insert(path.to_string(), Vec::new());
+            }
+            Mode::Append => {
+                self.files.entry(path.to_string()).or_default();
+            }
+        }
+        self.channels.insert(channel, (path.to_string(), mode, 0));
+        Ok(())
+    }
+
+    fn get(&mut self, channel: u32) -> io::Result<Option<String>> {
+        match self.channels.get_mut(&channel) {
+            Some((path, Mode::Read, position)) => {
+                let line = self.files[path.as_str()].get(*position).cloned();
+                *position += 1;
+                Ok(line)
+            }
+            _ => Err(not_open(channel)),
+        }
+    }
+
+    fn put(&mut self, channel: u32, line: &str) -> io::Result<()> {
+        match self.channels.get(&channel) {
+            Some((path, Mode::Write, _)) | Some((path, Mode::Append, _)) => {
+                let file = self.files.get_mut(path.as_str()).unwrap();
+                file.push(line.to_string());
+                Ok(())
+            }
+            _ => Err(not_open(channel)),
+        }
+    }
+
+    fn close(&mut self, channel: u32) {
+        self.channels.remove(&channel);
+    }
+}
@@ -1,5 +1,22 @@
+mod builtins;
+pub mod bytecode;
+pub mod c_backend;
+mod checker;
 pub mod compiler;
+pub mod coverage;
 pub mod data;
+pub mod disassembler;
+pub mod formatter;
+pub mod host;
+mod linker;
+pub mod lint;
+pub mod loader;
 mod parser;
+pub mod printer;
+pub mod profile;
+pub mod program;
+pub mod repl;
 mod runtime;
+pub mod rust_backend;
+pub mod symbol;
 pub mod vm;
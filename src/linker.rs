@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests;
+
+use crate::checker::locate;
+use crate::data::*;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A parsed module with the source it comes from.
+pub struct Unit {
+    pub file: String,
+    pub text: String,
+    pub module: RefalModule,
+}
+
+/// Resolves the calls between the modules of a program: calls to `$EXTERN` functions
+/// are rewritten to qualified names, qualified calls to other modules must name their entries.
+pub fn link(units: &mut [Unit]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut modules = HashMap::<&str, &Unit>::new();
+    let mut providers = HashMap::<&str, Vec<&str>>::new();
+    for unit in units.iter() {
+        let module = &unit.module;
+        if let Some(other) = modules.get(module.name.as_str()) {
+            let message = format!(
+                "module `{}` is already defined in {}",
+                module.name, other.file
+            );
+            diagnostics.push(unit.diagnostic(module.location.range.clone(), message));
+            continue;
+        }
+        modules.insert(&module.name, unit);
+        for f in module.functions.iter().filter(|f| f.entry) {
+            providers.entry(&f.name).or_default().push(&module.name);
+        }
+    }
+
+    let mut resolutions = Vec::new();
+    for unit in units.iter() {
+        let mut externs = HashMap::<String, String>::new();
+        for declaration in &unit.module.externs {
+            let candidates = providers
+                .get(declaration.name.as_str())
+                .map_or(&[][..], |v| &v[..]);
+            let message = match candidates {
+                [module] => {
+                    let qualified = format!("{}.{}", module, declaration.name);
+                    externs.insert(declaration.name.clone(), qualified);
+                    continue;
+                }
+                [] => format!(
+                    "function `{}` is declared $EXTERN but no module has it as an entry",
+                    declaration.name
+                ),
+                _ => format!(
+                    "function `{}` is declared $EXTERN but is an entry of several modules: {}",
+                    declaration.name,
+                    candidates.join(", ")
+                ),
+            };
+            diagnostics.push(unit.diagnostic(declaration.location.range.clone(), message));
+        }
+        for f in &unit.module.functions {
+            for sentence in &f.sentences {
+                let range = &sentence.location.range;
+                for result in sentence.results() {
+                    diagnostics.extend(check_calls(unit, &modules, result, range));
+                }
+            }
+        }
+        resolutions.push(externs);
+    }
+
+    for (unit, externs) in units.iter_mut().zip(resolutions) {
+        for f in &mut unit.module.functions {
+            for sentence in &mut f.sentences {
+                for result in sentence.results_mut() {
+                    resolve_externs(result, &externs);
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+impl Unit {
+    fn diagnostic(&self, range: Range<usize>, message: String) -> Diagnostic {
+        Diagnostic::new(&self.file, &self.text, range, message)
+    }
+}
+
+/// Checks that the qualified calls to other modules in `rewrite` refer to their entries.
+fn check_calls(
+    unit: &Unit,
+    modules: &HashMap<&str, &Unit>,
+    rewrite: &[Object],
+    range: &Range<usize>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for pair in rewrite.windows(2) {
+        let name = match pair {
+            [Object::FunBracketL, Object::Symbol(name)] => name,
+            _ => continue,
+        };
+        let (module_name, local_name) = match name.split_once('.') {
+            Some((module_name, _)) if module_name == unit.module.name => continue,
+            Some(split) => split,
+            None => continue,
+        };
+        let message = match modules.get(module_name) {
+            None => format!(
+                "function `{}` refers to unknown module `{}`",
+                name, module_name
+            ),
+            Some(other) => match other.module.functions.iter().find(|f| f.name == local_name) {
+                Some(f) if f.entry => continue,
+                Some(_) => format!(
+                    "function `{}` is not an entry of module `{}`",
+                    local_name, module_name
+                ),
+                None => format!("function `{}` is not defined", name),
+            },
+        };
+        let location = locate(&unit.text, range, name);
+        diagnostics.push(unit.diagnostic(location, message));
+    }
+    diagnostics
+}
+
+fn resolve_externs(rewrite: &mut [Object], externs: &HashMap<String, String>) {
+    for i in 1..rewrite.len() {
+        if rewrite[i - 1] != Object::FunBracketL {
+            continue;
+        }
+        if let Object::Symbol(name) = &rewrite[i] {
+            if let Some(qualified) = externs.get(name) {
+                rewrite[i] = Object::Symbol(qualified.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::compiler::{compile_expression, compile_program};
+use crate::data::Error;
+use crate::program::Program;
+use crate::vm::eval_call;
+
+#[cfg(test)]
+const LISTS: &str = "$MODULE Lists;
+$ENTRY Reverse {
+    = ;
+    $t.1 $e.2 = <Reverse $e.2> $t.1;
+}
+Helper { = ; }";
+
+#[cfg(test)]
+const STRINGS: &str = "$MODULE Strings;
+$EXTERN Reverse;
+$ENTRY Mirror { $e.1 = $e.1 <Reverse $e.1>; }";
+
+#[cfg(test)]
+fn link_errors(sources: &[(&str, &str)]) -> Vec<(String, usize, String)> {
+    match compile_program(sources) {
+        Err(Error::Linking(diagnostics)) => diagnostics
+            .into_iter()
+            .map(|d| (d.file, d.location.line, d.message))
+            .collect(),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_link() {
+    let main = "$MODULE Main;
+Go { $e.1 = <'Strings.Mirror' $e.1> <'Lists.Reverse' A B>; }";
+    let sources = [
+        ("main.refal", main),
+        ("lists.refal", LISTS),
+        ("strings.refal", STRINGS),
+    ];
+    let defs = compile_program(&sources).unwrap();
+    let arg = compile_expression("Main", "X (Y Z)").unwrap();
+    let expected = compile_expression("Main", "X (Y Z) (Y Z) X B A").unwrap();
+    assert_eq!(
+        eval_call(&Program::new(defs), "Main.Go", &arg).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_unresolved() {
+    let main = "$MODULE Main;
+$EXTERN Mirror, Missing;
+Go {
+    = <Mirror> <Missing>;
+    = <'Lists.Helper'> <'Lists.Sort'> <'Other.F'>;
+}";
+    let sources = [
+        ("main.refal", main),
+        ("lists.refal", LISTS),
+        ("strings.refal", STRINGS),
+    ];
+    let message = |line, message: &str| (String::from("main.refal"), line, message.to_string());
+    assert_eq!(
+        link_errors(&sources),
+        vec![
+            message(
+                2,
+                "function `Missing` is declared $EXTERN but no module has it as an entry"
+            ),
+            message(5, "function `Helper` is not an entry of module `Lists`"),
+            message(5, "function `Lists.Sort` is not defined"),
+            message(5, "function `Other.F` refers to unknown module `Other`"),
+        ]
+    );
+}
+
+#[test]
+fn test_ambiguous() {
+    let other = "$MODULE Other;\n$ENTRY Reverse { $e.1 = $e.1; }";
+    let sources = [
+        ("lists.refal", LISTS),
+        ("other.refal", other),
+        ("strings.refal", STRINGS),
+    ];
+    assert_eq!(
+        link_errors(&sources),
+        vec![(
+            String::from("strings.refal"),
+            2,
+            String::from(
+                "function `Reverse` is declared $EXTERN but is an entry of several modules: Lists, Other"
+            )
+        )]
+    );
+}
+
+#[test]
+fn test_duplicate_module() {
+    let sources = [("a.refal", LISTS), ("b.refal", LISTS)];
+    assert_eq!(
+        link_errors(&sources),
+        vec![(
+            String::from("b.refal"),
+            1,
+            String::from("module `Lists` is already defined in a.refal")
+        )]
+    );
+}
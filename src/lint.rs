@@ -0,0 +1,218 @@
+#[cfg(test)]
+mod tests;
+
+use crate::checker::locate;
+use crate::data::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Finds what compiles but likely does not do what was meant: sentences never selected because
+/// an earlier sentence matches every argument they do, and variables bound but never used.
+/// The module is expected to pass `checker::check_module`.
+pub fn lint_module(file: &str, text: &str, module: &RefalModule) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        file,
+        text,
+        diagnostics: Vec::new(),
+    };
+    for f in &module.functions {
+        linter.lint_sentences(&f.sentences, &HashSet::new());
+    }
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    file: &'a str,
+    text: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, range: Range<usize>, message: String) {
+        let diagnostic = Diagnostic::warning(self.file, self.text, range, message);
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Lints the sentences of a function or a block, where the variables in `outer`
+    /// are bound by the enclosing sentences.
+    fn lint_sentences(&mut self, sentences: &'a [Sentence], outer: &HashSet<&'a str>) {
+        let patterns: Vec<_> = sentences.iter().map(|s| terms(&s.pattern)).collect();
+        for (j, sentence) in sentences.iter().enumerate() {
+            let shadowing = (0..j).find(|&i| {
+                // A condition may fail and let the next sentences be tried.
+                sentences[i].conditions.is_empty()
+                    && outer_free(&sentences[i].pattern, outer)
+                    && subsumes(vec![(&patterns[i], &patterns[j])], HashMap::new())
+            });
+            if let Some(i) = shadowing {
+                let message = format!(
+                    "sentence is never selected, the sentence on line {} matches first",
+                    sentences[i].location.line
+                );
+                self.report(sentence.location.range.clone(), message);
+            }
+            self.lint_variables(sentence, outer);
+        }
+    }
+
+    fn lint_variables(&mut self, sentence: &'a Sentence, outer: &HashSet<&'a str>) {
+        let patterns = std::iter::once(&sentence.pattern)
+            .chain(sentence.conditions.iter().map(|c| &c.pattern));
+        let mut bound = Vec::<&str>::new();
+        let mut occurrences = HashMap::<&str, usize>::new();
+        for image in patterns.flatten().filter_map(variable) {
+            *occurrences.entry(image).or_default() += 1;
+            if !outer.contains(image) && !bound.contains(&image) {
+                bound.push(image);
+            }
+        }
+        let mut used = HashSet::<&str>::new();
+        let results = sentence.conditions.iter().map(|c| &c.result);
+        for image in results
+            .chain([&sentence.rewrite])
+            .flatten()
+            .filter_map(variable)
+        {
+            used.insert(image);
+        }
+        for inner in sentence.block.iter().flatten() {
+            used.extend(objects(inner).filter_map(variable));
+        }
+        let range = &sentence.location.range;
+        for &image in &bound {
+            // A variable occurring twice in the patterns requires the occurrences to be equal.
+            if !used.contains(image) && occurrences[image] == 1 {
+                let message = format!("variable `{}` is bound but never used", image);
+                self.report(locate(self.text, range, image), message);
+            }
+        }
+
+        let mut inner_outer = outer.clone();
+        inner_outer.extend(bound);
+        if let Some(block) = &sentence.block {
+            self.lint_sentences(block, &inner_outer);
+        }
+    }
+}
+
+/// An expression as a tree of brackets.
+#[derive(Debug, PartialEq)]
+enum Term<'a> {
+    Object(&'a Object),
+    Bracket(Vec<Term<'a>>),
+}
+
+fn terms(objects: &[Object]) -> Vec<Term<'_>> {
+    let mut stack = vec![Vec::new()];
+    for object in objects {
+        match object {
+            Object::StrBracketL => stack.push(Vec::new()),
+            Object::StrBracketR if stack.len() > 1 => {
+                let inner = stack.pop().unwrap();
+                stack.last_mut().unwrap().push(Term::Bracket(inner));
+            }
+            _ => stack.last_mut().unwrap().push(Term::Object(object)),
+        }
+    }
+    while stack.len() > 1 {
+        let inner = stack.pop().unwrap();
+        stack.last_mut().unwrap().push(Term::Bracket(inner));
+    }
+    stack.pop().unwrap()
+}
+
+type Bindings<'p, 'e> = HashMap<&'p str, &'e [Term<'e>]>;
+
+/// Whether each pair of a pattern and an expression, whose variables stand for whatever
+/// their type allows, can be matched at once, i.e. the pattern matches every argument
+/// the expression does, with the variables of the pattern bound in `bindings`.
+fn subsumes<'p, 'e>(
+    mut goals: Vec<(&'p [Term<'p>], &'e [Term<'e>])>,
+    mut bindings: Bindings<'p, 'e>,
+) -> bool {
+    let (pattern, expression) = match goals.pop() {
+        Some(goal) => goal,
+        None => return true,
+    };
+    let (first, rest) = match pattern.split_first() {
+        Some(split) => split,
+        None => return expression.is_empty() && subsumes(goals, bindings),
+    };
+    if let Term::Object(Object::EVar(v)) = first {
+        if let Some(&value) = bindings.get(v.as_str()) {
+            if !expression.starts_with(value) {
+                return false;
+            }
+            goals.push((rest, &expression[value.len()..]));
+            return subsumes(goals, bindings);
+        }
+        return (0..=expression.len()).any(|length| {
+            let mut goals = goals.clone();
+            let mut bindings = bindings.clone();
+            bindings.insert(v, &expression[..length]);
+            goals.push((rest, &expression[length..]));
+            subsumes(goals, bindings)
+        });
+    }
+    let (term, tail) = match expression.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    goals.push((rest, tail));
+    match (first, term) {
+        (Term::Bracket(inner), Term::Bracket(term_inner)) => goals.push((inner, term_inner)),
+        (Term::Object(Object::SVar(v)), Term::Object(object))
+            if !matches!(object, Object::EVar(_) | Object::TVar(_)) =>
+        {
+            if !bind(&mut bindings, v, &expression[..1]) {
+                return false;
+            }
+        }
+        (Term::Object(Object::TVar(v)), _) if !matches!(term, Term::Object(Object::EVar(_))) => {
+            if !bind(&mut bindings, v, &expression[..1]) {
+                return false;
+            }
+        }
+        (Term::Object(object), Term::Object(term_object)) if object == term_object => (),
+        _ => return false,
+    }
+    subsumes(goals, bindings)
+}
+
+/// Binds `v` to `value`, unless it is bound to something else.
+fn bind<'p, 'e>(bindings: &mut Bindings<'p, 'e>, v: &'p str, value: &'e [Term<'e>]) -> bool {
+    *bindings.entry(v).or_insert(value) == value
+}
+
+/// Whether none of the variables of `pattern` is bound by the enclosing sentences,
+/// such a variable matching only the value it is bound to.
+fn outer_free(pattern: &[Object], outer: &HashSet<&str>) -> bool {
+    !pattern
+        .iter()
+        .filter_map(variable)
+        .any(|image| outer.contains(image))
+}
+
+/// The objects of a sentence and of the sentences of its block.
+fn objects(sentence: &Sentence) -> Box<dyn Iterator<Item = &Object> + '_> {
+    let conditions = sentence
+        .conditions
+        .iter()
+        .flat_map(|c| c.result.iter().chain(&c.pattern));
+    let block = sentence.block.iter().flatten().flat_map(objects);
+    Box::new(
+        sentence
+            .pattern
+            .iter()
+            .chain(conditions)
+            .chain(&sentence.rewrite)
+            .chain(block),
+    )
+}
+
+fn variable(object: &Object) -> Option<&str> {
+    match object {
+        Object::EVar(image) | Object::SVar(image) | Object::TVar(image) => Some(image),
+        _ => None,
+    }
+}
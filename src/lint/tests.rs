@@ -0,0 +1,128 @@
+use crate::data::Severity;
+use crate::lint::lint_module;
+use crate::parser;
+
+#[cfg(test)]
+fn lint(input: &str) -> Vec<(usize, String)> {
+    let module = parser::parse_input(input).unwrap();
+    lint_module("t.refal", input, &module)
+        .into_iter()
+        .inspect(|d| assert_eq!(d.severity, Severity::Warning))
+        .map(|d| (d.location.line, d.message))
+        .collect()
+}
+
+#[test]
+fn test_clean() {
+    let input = "$MODULE T;
+Reverse {
+    = ;
+    $t.1 $e.2 = <Reverse $e.2> $t.1;
+}
+Insert1 {
+    (True) $e.1 = $e.1;
+    (False) $e.1 $t.3 $t.2 = <Insert $e.1 $t.2> $t.3;
+}";
+    assert_eq!(lint(input), vec![]);
+}
+
+#[test]
+fn test_shadowed_sentences() {
+    let input = "$MODULE T;
+F {
+    $e.1 = False;
+    A = True;
+}
+G {
+    $s.1 $e.2 = 1;
+    A B = 2;
+    ($e.3) = 3;
+    $t.4 $t.4 = 4;
+    ($e.5) ($e.5) = 5;
+    ($e.6 A) = 6;
+}
+H {
+    $e.1 $s.2 $e.1 = 1;
+    (A) $s.3 (A) = 2;
+    (A) $s.3 (B) = 3;
+}";
+    assert_eq!(
+        lint(input),
+        vec![
+            (3, String::from("variable `$e.1` is bound but never used")),
+            (
+                4,
+                String::from("sentence is never selected, the sentence on line 3 matches first")
+            ),
+            (7, String::from("variable `$s.1` is bound but never used")),
+            (7, String::from("variable `$e.2` is bound but never used")),
+            (
+                8,
+                String::from("sentence is never selected, the sentence on line 7 matches first")
+            ),
+            (9, String::from("variable `$e.3` is bound but never used")),
+            (
+                11,
+                String::from("sentence is never selected, the sentence on line 10 matches first")
+            ),
+            (
+                12,
+                String::from("sentence is never selected, the sentence on line 9 matches first")
+            ),
+            (12, String::from("variable `$e.6` is bound but never used")),
+            (15, String::from("variable `$s.2` is bound but never used")),
+            (
+                16,
+                String::from("sentence is never selected, the sentence on line 15 matches first")
+            ),
+            (16, String::from("variable `$s.3` is bound but never used")),
+            (17, String::from("variable `$s.3` is bound but never used")),
+        ]
+    );
+}
+
+#[test]
+fn test_conditions_and_blocks() {
+    let input = "$MODULE T;
+F {
+    $e.1, <G $e.1> : True = A;
+    $e.1 = B;
+    $s.2 = C;
+}
+G {
+    $e.1 $s.2, <H $s.2> : $e.3 $s.4 = $e.1;
+    $e.1, <H> : {
+        $e.1 = A;
+        $e.2 = B;
+        $s.3 = $s.3;
+    };
+}";
+    assert_eq!(
+        lint(input),
+        vec![
+            (4, String::from("variable `$e.1` is bound but never used")),
+            (
+                5,
+                String::from("sentence is never selected, the sentence on line 4 matches first")
+            ),
+            (5, String::from("variable `$s.2` is bound but never used")),
+            (8, String::from("variable `$e.3` is bound but never used")),
+            (8, String::from("variable `$s.4` is bound but never used")),
+            (11, String::from("variable `$e.2` is bound but never used")),
+            (
+                12,
+                String::from("sentence is never selected, the sentence on line 11 matches first")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_display() {
+    let input = "$MODULE T;\nF { $e.1 = ; }";
+    let module = parser::parse_input(input).unwrap();
+    assert_eq!(
+        lint_module("t.refal", input, &module)[0].to_string(),
+        "t.refal:2:5: warning: variable `$e.1` is bound but never used\n2 | F { $e.1 = ; }\n  |     ^^^^"
+    );
+}
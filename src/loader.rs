@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod tests;
+
+use crate::compiler;
+use crate::coverage::SourceMap;
+use crate::data::*;
+use crate::linker::Unit;
+use crate::lint;
+use crate::parser;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A program compiled by `Loader::load_with_source_map`.
+pub struct Loaded {
+    pub defs: HashMap<String, Vec<Command>>,
+    pub source_map: SourceMap,
+    /// The warnings about the modules compiled, see `lint::lint_module`.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Finds the modules of a program in a list of directories; module `M` lives in `M.refal`.
+pub struct Loader {
+    search_paths: Vec<PathBuf>,
+}
+
+impl Loader {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Loader { search_paths }
+    }
+
+    /// Finds the file of `module` in the search path.
+    pub fn find(&self, module: &str) -> Option<PathBuf> {
+        self.search_paths
+            .iter()
+            .map(|dir| dir.join(format!("{}.refal", module)))
+            .find(|path| path.is_file())
+    }
+
+    /// Compiles the module in `file` together with `modules` and, transitively, the modules
+    /// their qualified calls refer to. The directory of `file` is searched first.
+    pub fn load(&self, file: &Path, modules: &[String]) -> Result<HashMap<String, Vec<Command>>> {
+        compiler::compile_units(self.read_units(&[file.to_path_buf()], modules)?)
+    }
+
+    /// Like `load`, also mapping the sentences of the compiled functions to their source lines
+    /// and linting the modules.
+    pub fn load_with_source_map(&self, file: &Path, modules: &[String]) -> Result<Loaded> {
+        let units = self.read_units(&[file.to_path_buf()], modules)?;
+        let mut source_map = SourceMap::new();
+        let mut warnings = Vec::new();
+        for unit in &units {
+            source_map.add(&unit.file, &unit.module);
+            warnings.extend(lint::lint_module(&unit.file, &unit.text, &unit.module));
+        }
+        Ok(Loaded {
+            defs: compiler::compile_units(units)?,
+            source_map,
+            warnings,
+        })
+    }
+
+    /// Parses the modules in `files`, first and in order, and those `load` would compile
+    /// with them. The directories of `files` are searched first.
+    pub fn read_units(&self, files: &[PathBuf], modules: &[String]) -> Result<Vec<Unit>> {
+        let dirs = files
+            .iter()
+            .map(|file| file.parent().unwrap_or_else(|| Path::new("")).to_path_buf());
+        let loader = Loader::new(dirs.chain(self.search_paths.iter().cloned()).collect());
+
+        let mut pending = modules
+            .iter()
+            .rev()
+            .map(|m| {
+                loader
+                    .find(m)
+                    .ok_or_else(|| Error::ModuleNotFound(m.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut units = Vec::<Unit>::new();
+        let mut requested = HashSet::<String>::new();
+        let mut loaded = HashSet::<PathBuf>::new();
+        let mut files = files.iter().cloned();
+        while let Some(path) = files.next().or_else(|| pending.pop()) {
+            if !loaded.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+                continue;
+            }
+            let unit = read_unit(&path)?;
+            requested.insert(unit.module.name.clone());
+            for module in referenced_modules(&unit.module) {
+                if requested.insert(module.to_string()) {
+                    // A missing module is reported by the linker at the call referring to it.
+                    if let Some(path) = loader.find(module) {
+                        pending.push(path);
+                    }
+                }
+            }
+            units.push(unit);
+        }
+        Ok(units)
+    }
+}
+
+fn read_unit(path: &Path) -> Result<Unit> {
+    let file = path.display().to_string();
+    let text =
+        fs::read_to_string(path).map_err(|error| Error::Io(format!("{}: {}", file, error)))?;
+    let module = parser::parse_file(&file, &text)?;
+    Ok(Unit { file, text, module })
+}
+
+/// The other modules whose functions `module` calls by qualified names.
+fn referenced_modules(module: &RefalModule) -> Vec<&str> {
+    let mut names = Vec::new();
+    for f in &module.functions {
+        for sentence in &f.sentences {
+            for pair in sentence.results().into_iter().flat_map(|r| r.windows(2)) {
+                if let [Object::FunBracketL, Object::Symbol(name)] = pair {
+                    match name.split_once('.') {
+                        Some((other, _)) if other != module.name => names.push(other),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    names
+}
@@ -0,0 +1,99 @@
+use crate::compiler::compile_expression;
+use crate::data::Error;
+use crate::loader::Loader;
+use crate::program::Program;
+use crate::vm::eval_call;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+fn write_modules(name: &str, modules: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("refal-loader-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, text) in modules {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_load() {
+    let dir = write_modules(
+        "load",
+        &[
+            (
+                "main/Main.refal",
+                "$MODULE Main;\n$EXTERN Twice;\nGo { $e.1 = <Twice <'Lib.Id' $e.1>>; }",
+            ),
+            ("lib/Lib.refal", "$MODULE Lib;\n$ENTRY Id { $e.1 = $e.1; }"),
+            (
+                "lib/Twice.refal",
+                "$MODULE Twice;\n$ENTRY Twice { $e.1 = $e.1 $e.1; }",
+            ),
+        ],
+    );
+    let loader = Loader::new(vec![dir.join("lib")]);
+    let defs = loader
+        .load(&dir.join("main/Main.refal"), &[String::from("Twice")])
+        .unwrap();
+    let arg = compile_expression("Main", "A").unwrap();
+    let expected = compile_expression("Main", "A A").unwrap();
+    assert_eq!(
+        eval_call(&Program::new(defs), "Main.Go", &arg).unwrap(),
+        expected
+    );
+
+    let result = loader.load(&dir.join("main/Main.refal"), &[]);
+    assert!(matches!(result, Err(Error::Linking(_))));
+    let result = loader.load(&dir.join("main/Main.refal"), &[String::from("Nope")]);
+    assert_eq!(
+        result.unwrap_err(),
+        Error::ModuleNotFound(String::from("Nope"))
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_load_test_program() {
+    let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("Test.refal");
+    let defs = Loader::new(Vec::new()).load(&file, &[]).unwrap();
+    let arg = compile_expression("Test", "A (B C) 'de'").unwrap();
+    let expected = compile_expression("Test", "4").unwrap();
+    assert_eq!(
+        eval_call(&Program::new(defs), "Test.CountTerms", &arg).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_lint() {
+    let dir = write_modules(
+        "lint",
+        &[
+            ("Main.refal", "$MODULE Main;\nGo { $e.1 = <'Lib.F'>; }"),
+            ("Lib.refal", "$MODULE Lib;\n$ENTRY F { $e.1 = ; A = ; }"),
+        ],
+    );
+    let warnings: Vec<_> = Loader::new(vec![])
+        .load_with_source_map(&dir.join("Main.refal"), &[])
+        .unwrap()
+        .warnings
+        .into_iter()
+        .map(|d| {
+            let file = Path::new(&d.file).file_name().unwrap();
+            (file.to_string_lossy().into_owned(), d.message)
+        })
+        .collect();
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[0].0, "Main.refal");
+    assert_eq!(
+        warnings[2],
+        (
+            String::from("Lib.refal"),
+            String::from("sentence is never selected, the sentence on line 2 matches first")
+        )
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
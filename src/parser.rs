@@ -1,116 +1,546 @@
 use crate::data::Object::*;
-use crate::data::{Error, Function, Object, RefalModule, Result, Sentence};
-use tree_sitter::{Node, TreeCursor};
+use crate::data::{
+    Condition, Diagnostic, Error, Extern, Function, Location, Object, RefalModule, Result, Sentence,
+};
+use std::ops::Range;
+use std::result;
+
+/// A syntax problem found in the source: the offending byte range and a message.
+type Problem = (Range<usize>, String);
 
 pub fn parse_input(text: &str) -> Result<RefalModule> {
-    let mut parser = tree_sitter::Parser::new();
-    parser.set_language(tree_sitter_refal::language()).unwrap();
-    let tree = parser.parse(text, None).unwrap();
-    if tree.root_node().has_error() {
-        Err(Error::Parsing)
-    } else {
-        Ok(translate_module(&mut tree.walk(), text))
-    }
-}
-
-fn translate_module(cursor: &mut TreeCursor, text: &str) -> RefalModule {
-    let root_node = cursor.node();
-    let module_node = root_node.child_by_field_id(MODULE).unwrap();
-    let name = get_name(&module_node, text);
-    let function_nodes: Vec<_> = root_node.children_by_field_id(FUNCTION, cursor).collect();
-    let functions: Vec<_> = function_nodes
-        .iter()
-        .map(|n| translate_function(n, cursor, text))
-        .collect();
-    RefalModule { name, functions }
+    parse_file("<input>", text)
 }
 
-fn translate_function<'a>(node: &Node<'a>, cursor: &mut TreeCursor<'a>, text: &str) -> Function {
-    let name = get_name(node, text);
-    let sentence_nodes: Vec<_> = node.children_by_field_id(SENTENCE, cursor).collect();
-    let sentences: Vec<_> = sentence_nodes
-        .iter()
-        .map(|n| translate_sentence(n, cursor, text))
-        .collect();
-    Function { name, sentences }
+/// Parses a module, reporting every syntax error in it against `file`.
+pub fn parse_file(file: &str, text: &str) -> Result<RefalModule> {
+    parse(text).map_err(|problems| to_error(file, text, problems))
 }
 
-fn translate_sentence<'a>(node: &Node<'a>, cursor: &mut TreeCursor<'a>, text: &str) -> Sentence {
-    let pattern: Vec<_> = node
-        .children_by_field_id(PATTERN, cursor)
-        .map(|n| translate_object(n, text))
-        .collect();
-    let rewrite: Vec<_> = node
-        .children_by_field_id(REWRITE, cursor)
-        .map(|n| translate_object(n, text))
+/// Parses a standalone expression by wrapping it into the rewrite of a single sentence.
+pub fn parse_expression(text: &str) -> Result<Vec<Object>> {
+    const PREFIX: &str = "$MODULE E; E { = ";
+    const FILE: &str = "<expression>";
+    let input = format!("{}{} ; }}", PREFIX, text);
+    let unwrap = |i: usize| i.saturating_sub(PREFIX.len()).min(text.len());
+    let mut module = parse(&input).map_err(|problems| {
+        let problems = problems
+            .into_iter()
+            .map(|(range, message)| (unwrap(range.start)..unwrap(range.end), message))
+            .collect();
+        to_error(FILE, text, problems)
+    })?;
+    match module.functions.pop() {
+        Some(Function { mut sentences, .. })
+            if module.functions.is_empty()
+                && sentences.len() == 1
+                && sentences[0].conditions.is_empty()
+                && sentences[0].block.is_none() =>
+        {
+            Ok(sentences.pop().unwrap().rewrite)
+        }
+        _ => {
+            let message = String::from("expected a single expression");
+            Err(to_error(FILE, text, vec![(0..text.len(), message)]))
+        }
+    }
+}
+
+/// Parses a module. A syntax error in a function is recovered from at the `}` closing it,
+/// so that the errors in the following functions are reported too.
+fn parse(text: &str) -> result::Result<RefalModule, Vec<Problem>> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        problems: Vec::new(),
+    };
+    match parser.module() {
+        Ok(module) if parser.problems.is_empty() => Ok(module),
+        Ok(_) => Err(parser.problems),
+        Err(problem) => {
+            parser.problems.push(problem);
+            Err(parser.problems)
+        }
+    }
+}
+
+fn to_error(file: &str, text: &str, problems: Vec<Problem>) -> Error {
+    let diagnostics = problems
+        .into_iter()
+        .map(|(range, message)| Diagnostic::new(file, text, range, message))
         .collect();
-    Sentence { pattern, rewrite }
+    Error::Parsing(diagnostics)
+}
+
+/// The tokens of the source, separated by blanks and comments: `/* ... */`, and lines
+/// starting with `*`.
+#[derive(Debug, PartialEq)]
+enum Token<'t> {
+    /// `$MODULE`, `$ENTRY` or `$EXTERN`.
+    Keyword(&'t str),
+    Identifier(&'t str),
+    Number(&'t str),
+    /// A literal in single or double quotes, quotes included.
+    Quoted(&'t str),
+    /// A variable such as `$e.1`, with its type.
+    Variable(char, &'t str),
+    Punctuation(char),
+    /// Text starting no token, such as `$x` or a literal not closed, with the message about it.
+    Invalid(String),
+    End,
 }
 
-fn translate_object(node: tree_sitter::Node, text: &str) -> Object {
-    match node.kind_id() {
-        E_VAR => EVar(get_string(&node, text)),
-        S_VAR => SVar(get_string(&node, text)),
-        T_VAR => TVar(get_string(&node, text)),
-        ID => Symbol(get_string(&node, text)),
-        Q_SYMBOL => Symbol(get_string_stripped(&node, text)),
-        STR_BR_L => StrBracketL,
-        STR_BR_R => StrBracketR,
-        FUN_BR_L => FunBracketL,
-        FUN_BR_R => FunBracketR,
-        _ => panic!(),
+struct Parser<'t> {
+    text: &'t str,
+    /// Where the last token read ends.
+    pos: usize,
+    /// The problems in the functions recovered from.
+    problems: Vec<Problem>,
+}
+
+impl<'t> Parser<'t> {
+    fn module(&mut self) -> result::Result<RefalModule, Problem> {
+        self.expect(Token::Keyword("$MODULE"), "`$MODULE`")?;
+        let (name, range) = self.identifier()?;
+        self.expect(Token::Punctuation(';'), "`;`")?;
+        let mut module = RefalModule {
+            name: name.to_string(),
+            functions: Vec::new(),
+            externs: Vec::new(),
+            location: Location::new(self.text, range),
+        };
+        loop {
+            match self.peek() {
+                (Token::End, _) => return Ok(module),
+                (Token::Keyword("$EXTERN"), _) => {
+                    self.next();
+                    self.externs(&mut module.externs)?;
+                }
+                (_, range) => match self.function() {
+                    Ok(function) => module.functions.push(function),
+                    Err(problem) => {
+                        self.problems.push(problem);
+                        self.recover(range.start);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Reads the names declared by `$EXTERN`, up to the `;`.
+    fn externs(&mut self, externs: &mut Vec<Extern>) -> result::Result<(), Problem> {
+        loop {
+            let (name, range) = self.identifier()?;
+            externs.push(Extern {
+                name: name.to_string(),
+                location: Location::new(self.text, range),
+            });
+            match self.next() {
+                (Token::Punctuation(','), _) => (),
+                (Token::Punctuation(';'), _) => return Ok(()),
+                (token, range) => return Err(unexpected(self.text, token, range)),
+            }
+        }
     }
+
+    fn function(&mut self) -> result::Result<Function, Problem> {
+        let entry = self.peek().0 == Token::Keyword("$ENTRY");
+        if entry {
+            self.next();
+        }
+        let (name, range) = self.identifier()?;
+        self.expect(Token::Punctuation('{'), "`{`")?;
+        Ok(Function {
+            name: name.to_string(),
+            sentences: self.body()?,
+            location: Location::new(self.text, range),
+            entry,
+        })
+    }
+
+    /// Reads the sentences of a function or a block, up to the `}`.
+    fn body(&mut self) -> result::Result<Vec<Sentence>, Problem> {
+        let mut sentences = Vec::new();
+        while self.peek().0 != Token::Punctuation('}') {
+            sentences.push(self.sentence()?);
+        }
+        self.next();
+        Ok(sentences)
+    }
+
+    /// Reads a sentence and the `;` ending it, which may be left out before a `}`.
+    fn sentence(&mut self) -> result::Result<Sentence, Problem> {
+        let start = self.peek().1.start;
+        let pattern = self.expression()?;
+        let mut conditions = Vec::new();
+        let (rewrite, block) = loop {
+            match self.next() {
+                (Token::Punctuation('='), _) => break (self.expression()?, None),
+                (Token::Punctuation(','), _) => {
+                    let result = self.expression()?;
+                    self.expect(Token::Punctuation(':'), "`:`")?;
+                    if self.peek().0 == Token::Punctuation('{') {
+                        self.next();
+                        break (result, Some(self.body()?));
+                    }
+                    let pattern = self.expression()?;
+                    conditions.push(Condition { result, pattern });
+                }
+                (token, range) => return Err(unexpected(self.text, token, range)),
+            }
+        };
+        let location = Location::new(self.text, start..self.pos);
+        if self.peek().0 != Token::Punctuation('}') {
+            self.expect(Token::Punctuation(';'), "`;`")?;
+        }
+        Ok(Sentence {
+            pattern,
+            conditions,
+            rewrite,
+            block,
+            location,
+        })
+    }
+
+    /// Reads the objects up to the first token that is not one. A quoted literal stands for
+    /// its characters, except right after `<`, where it is the name of the function called.
+    fn expression(&mut self) -> result::Result<Vec<Object>, Problem> {
+        let mut objects = Vec::new();
+        loop {
+            let (token, range) = self.peek();
+            match token {
+                Token::Identifier(name) => objects.push(Symbol(name.to_string())),
+                Token::Number(image) => match image.parse::<u32>() {
+                    Ok(n) => objects.push(Number(n)),
+                    Err(_) => {
+                        let message = format!("number `{}` does not fit in a macrodigit", image);
+                        return Err((range, message));
+                    }
+                },
+                Token::Quoted(image) => {
+                    let chars = unescape(&image[1..image.len() - 1])
+                        .map_err(|message| (range.clone(), message))?;
+                    if objects.last() == Some(&FunBracketL) {
+                        objects.push(Symbol(chars.into_iter().collect()));
+                    } else {
+                        objects.extend(chars.into_iter().map(Char));
+                    }
+                }
+                Token::Variable('e', image) => objects.push(EVar(image.to_string())),
+                Token::Variable('s', image) => objects.push(SVar(image.to_string())),
+                Token::Variable(_, image) => objects.push(TVar(image.to_string())),
+                Token::Punctuation('(') => objects.push(StrBracketL),
+                Token::Punctuation(')') => objects.push(StrBracketR),
+                Token::Punctuation('<') => objects.push(FunBracketL),
+                Token::Punctuation('>') => objects.push(FunBracketR),
+                _ => return Ok(objects),
+            }
+            self.next();
+        }
+    }
+
+    fn identifier(&mut self) -> result::Result<(&'t str, Range<usize>), Problem> {
+        match self.next() {
+            (Token::Identifier(name), range) => Ok((name, range)),
+            (token, range) => Err(unexpected(self.text, token, range)),
+        }
+    }
+
+    /// Reads `expected`, described by `image`.
+    fn expect(&mut self, expected: Token, image: &str) -> result::Result<(), Problem> {
+        match self.next() {
+            (token, _) if token == expected => Ok(()),
+            (Token::Invalid(message), range) => Err((range, message)),
+            (_, range) => Err((range.start..range.start, format!("missing {}", image))),
+        }
+    }
+
+    /// Skips the function starting at `start`, up to the `}` closing its body.
+    fn recover(&mut self, start: usize) {
+        self.pos = start;
+        let mut depth = 0;
+        loop {
+            match self.next().0 {
+                Token::Punctuation('{') => depth += 1,
+                Token::Punctuation('}') if depth <= 1 => return,
+                Token::Punctuation('}') => depth -= 1,
+                Token::End => return,
+                _ => (),
+            }
+        }
+    }
+
+    fn next(&mut self) -> (Token<'t>, Range<usize>) {
+        let (token, range) = self.peek();
+        self.pos = range.end;
+        (token, range)
+    }
+
+    fn peek(&self) -> (Token<'t>, Range<usize>) {
+        let start = skip_blanks(self.text, self.pos);
+        let (token, length) = token(&self.text[start..]);
+        (token, start..start + length)
+    }
+}
+
+/// The position of the first token from `pos` on, past blanks and comments.
+fn skip_blanks(text: &str, mut pos: usize) -> usize {
+    loop {
+        let rest = &text[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        let line_start = pos == 0 || text[..pos].ends_with('\n');
+        if let Some(comment) = trimmed.strip_prefix("/*") {
+            pos = comment.find("*/").map_or(text.len(), |i| pos + i + 4);
+        } else if trimmed.starts_with('*') && line_start {
+            pos = trimmed.find('\n').map_or(text.len(), |i| pos + i);
+        } else {
+            return pos;
+        }
+    }
+}
+
+/// The token at the start of `text` and its length.
+fn token(text: &str) -> (Token<'_>, usize) {
+    let c = match text.chars().next() {
+        Some(c) => c,
+        None => return (Token::End, 0),
+    };
+    let run = |from: usize, accepts: fn(char) -> bool| {
+        text[from..]
+            .find(|c| !accepts(c))
+            .map_or(text.len(), |i| from + i)
+    };
+    match c {
+        '$' => {
+            let letters = run(1, char::is_alphabetic);
+            let kind = &text[1..letters];
+            let name = match text[letters..].strip_prefix('.') {
+                Some(_) if matches!(kind, "e" | "s" | "t") => {
+                    run(letters + 1, |c| c.is_alphanumeric() || c == '_')
+                }
+                _ => letters,
+            };
+            if name > letters + 1 {
+                let kind = kind.chars().next().unwrap();
+                (Token::Variable(kind, &text[..name]), name)
+            } else if matches!(&text[..letters], "$MODULE" | "$ENTRY" | "$EXTERN") {
+                (Token::Keyword(&text[..letters]), letters)
+            } else {
+                let end = run(letters, is_word_char).max(1);
+                let message = format!("unexpected `{}`", &text[..end]);
+                (Token::Invalid(message), end)
+            }
+        }
+        '\'' | '"' => {
+            let mut chars = text.char_indices().skip(1);
+            while let Some((i, next)) = chars.next() {
+                match next {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '\n' => break,
+                    _ if next == c => return (Token::Quoted(&text[..i + 1]), i + 1),
+                    _ => (),
+                }
+            }
+            let end = text.find('\n').unwrap_or(text.len());
+            (Token::Invalid(format!("missing closing `{}`", c)), end)
+        }
+        _ if is_word_char(c) => {
+            let end = run(0, is_word_char);
+            let word = &text[..end];
+            if word.bytes().all(|b| b.is_ascii_digit()) {
+                (Token::Number(word), end)
+            } else if c.is_ascii_digit() {
+                (Token::Invalid(format!("unexpected `{}`", word)), end)
+            } else {
+                (Token::Identifier(word), end)
+            }
+        }
+        _ => (Token::Punctuation(c), c.len_utf8()),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// The problem with `token`, found where another one was expected.
+fn unexpected(text: &str, token: Token, range: Range<usize>) -> Problem {
+    let image = text[range.clone()].lines().next().unwrap_or_default();
+    let message = match token {
+        Token::End => String::from("unexpected end of input"),
+        Token::Invalid(message) => message,
+        _ if image.chars().count() > 20 => {
+            let prefix: String = image.chars().take(20).collect();
+            format!("unexpected `{}...`", prefix)
+        }
+        _ => format!("unexpected `{}`", image),
+    };
+    (range, message)
+}
+
+/// Replaces the escape sequences `\n`, `\t`, `\r`, `\\`, `\'`, `\"` and `\xHH`
+/// in the text of a quoted literal.
+fn unescape(image: &str) -> result::Result<Vec<char>, String> {
+    let mut chars = Vec::new();
+    let mut rest = image.chars();
+    while let Some(c) = rest.next() {
+        if c != '\\' {
+            chars.push(c);
+            continue;
+        }
+        let escaped = match rest.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some(c @ ('\\' | '\'' | '"')) => c,
+            Some('x') => {
+                let digits: String = rest.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(code)
+                        if digits.len() == 2 && digits.chars().all(|c| c.is_ascii_hexdigit()) =>
+                    {
+                        char::from(code)
+                    }
+                    _ => return Err(format!("invalid escape sequence `\\x{}`", digits)),
+                }
+            }
+            Some(c) => return Err(format!("unknown escape sequence `\\{}`", c)),
+            None => return Err(String::from("unterminated escape sequence")),
+        };
+        chars.push(escaped);
+    }
+    Ok(chars)
 }
 
-fn get_name(node: &tree_sitter::Node, text: &str) -> String {
-    let name_node = node.child_by_field_id(NAME).unwrap();
-    text[name_node.byte_range()].to_string()
+#[test]
+fn test_syntax_errors() {
+    let input = "$MODULE T;\nF { = A; }\nG { A = $x; }\nH { B = ; }\nK { ; }\n";
+    match parse_file("t.refal", input) {
+        Err(Error::Parsing(diagnostics)) => {
+            assert!(diagnostics.iter().all(|d| d.file == "t.refal"));
+            let lines: Vec<_> = diagnostics.iter().map(|d| d.location.line).collect();
+            assert!(lines.contains(&3), "{:?}", diagnostics);
+            assert!(lines.contains(&5), "{:?}", diagnostics);
+            assert!(!lines.contains(&2) && !lines.contains(&4));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
 }
 
-fn get_string(node: &tree_sitter::Node, text: &str) -> String {
-    text[node.byte_range()].to_string()
+#[test]
+fn test_tokens() {
+    let input = "* T\n$MODULE T; /* a\n*/ F { 'a /* b' $s.x = ; }";
+    let sentence = &parse_input(input).unwrap().functions[0].sentences[0];
+    assert_eq!(sentence.pattern.len(), 7);
+    assert_eq!(sentence.pattern[6], SVar(String::from("$s.x")));
+
+    let messages = |input: &str| match parse_input(input) {
+        Err(Error::Parsing(diagnostics)) => diagnostics
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>(),
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(
+        messages("$MODULE T;\nG { = 12ab; }\nH { = $y.1; }\nF { = 'ab; }"),
+        vec![
+            "unexpected `12ab`",
+            "unexpected `$y.1`",
+            "missing closing `'`"
+        ]
+    );
+    assert_eq!(
+        messages("$MODULE T;\nF { = A;"),
+        vec!["unexpected end of input"]
+    );
+    assert_eq!(messages("$MODULE T\nF { }"), vec!["missing `;`"]);
 }
 
-fn get_string_stripped(node: &tree_sitter::Node, text: &str) -> String {
-    let mut range = node.byte_range();
-    range.start += 1;
-    range.end -= 1;
-    text[range].to_string()
+#[test]
+fn test_diagnostic() {
+    let input = "$MODULE T;\nG { A =\t$x; }\n";
+    let start = input.find("$x").unwrap();
+    let diagnostic = Diagnostic::new("t.refal", input, start..start + 2, String::from("oops"));
+    assert_eq!(diagnostic.location.line, 2);
+    assert_eq!(diagnostic.location.column, 9);
+    assert_eq!(
+        diagnostic.to_string(),
+        "t.refal:2:9: error: oops\n2 | G { A =\t$x; }\n  |        \t^^"
+    );
 }
 
-const MODULE: u16 = 2;
-const FUNCTION: u16 = 1;
-const SENTENCE: u16 = 6;
-const PATTERN: u16 = 4;
-const REWRITE: u16 = 5;
-const E_VAR: u16 = 11;
-const S_VAR: u16 = 12;
-const T_VAR: u16 = 13;
-const ID: u16 = 14;
-const Q_SYMBOL: u16 = 10;
-const STR_BR_L: u16 = 6;
-const STR_BR_R: u16 = 7;
-const FUN_BR_L: u16 = 8;
-const FUN_BR_R: u16 = 9;
-const NAME: u16 = 3;
+#[test]
+fn test_numbers() {
+    let module = parse_input("$MODULE T;\nF { 12 = 0 '7' 4294967295; }").unwrap();
+    let sentence = &module.functions[0].sentences[0];
+    assert_eq!(sentence.pattern, vec![Number(12)]);
+    assert_eq!(
+        sentence.rewrite,
+        vec![Number(0), Char('7'), Number(4294967295)]
+    );
+    assert!(parse_input("$MODULE T;\nF { = 4294967296; }").is_err());
+}
+
+#[test]
+fn test_entries() {
+    let module = parse_input("$MODULE T;\n$EXTERN A, B;\n$ENTRY F { = ; }\nG { = ; }").unwrap();
+    let externs: Vec<_> = module.externs.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(externs, vec!["A", "B"]);
+    assert_eq!(module.externs[1].location.line, 2);
+    let entries: Vec<_> = module.functions.iter().map(|f| f.entry).collect();
+    assert_eq!(entries, vec![true, false]);
+}
+
+#[test]
+fn test_conditions() {
+    let input = "$MODULE T;\nF { $e.1 , <G $e.1> : A $e.2 , $e.2 : { B = C; = ; }; }";
+    let sentence = &parse_input(input).unwrap().functions[0].sentences[0];
+    assert_eq!(sentence.conditions.len(), 1);
+    assert_eq!(
+        sentence.conditions[0].pattern,
+        vec![Symbol(String::from("A")), EVar(String::from("$e.2"))]
+    );
+    assert_eq!(sentence.rewrite, vec![EVar(String::from("$e.2"))]);
+    let block = sentence.block.as_ref().unwrap();
+    assert_eq!(block.len(), 2);
+    assert_eq!(block[0].rewrite, vec![Symbol(String::from("C"))]);
+
+    let input = "$MODULE T;\nF {\n  $e.1, $e.1 : A, <G> : B,\n    <G> : { C, <G> : { = D } };\n}";
+    let sentence = &parse_input(input).unwrap().functions[0].sentences[0];
+    assert_eq!(sentence.conditions.len(), 2);
+    assert_eq!(sentence.location.line, 3);
+    assert_eq!(sentence.location.range.end, input.find("};").unwrap() + 1);
+    let inner = &sentence.block.as_ref().unwrap()[0];
+    assert_eq!(inner.conditions, vec![]);
+    assert_eq!(inner.block.as_ref().unwrap()[0].pattern, vec![]);
+    assert!(parse_input("$MODULE T;\nF { $e.1, $e.1 A = ; }").is_err());
+    assert!(parse_input("$MODULE T;\nF { $e.1, $e.1 : { A = ; }").is_err());
+}
 
 #[test]
-fn test_mapping() {
-    let language = tree_sitter_refal::language();
-    assert_eq!(E_VAR, language.id_for_node_kind("e_var", true));
-    assert_eq!(S_VAR, language.id_for_node_kind("s_var", true));
-    assert_eq!(T_VAR, language.id_for_node_kind("t_var", true));
-    assert_eq!(ID, language.id_for_node_kind("id", true));
-    assert_eq!(Q_SYMBOL, language.id_for_node_kind("q_symbol", true));
-    assert_eq!(STR_BR_L, language.id_for_node_kind("str_br_l", true));
-    assert_eq!(STR_BR_R, language.id_for_node_kind("str_br_r", true));
-    assert_eq!(FUN_BR_L, language.id_for_node_kind("fun_br_l", true));
-    assert_eq!(FUN_BR_R, language.id_for_node_kind("fun_br_r", true));
-
-    assert_eq!(MODULE, language.field_id_for_name("module").unwrap());
-    assert_eq!(FUNCTION, language.field_id_for_name("function").unwrap());
-    assert_eq!(SENTENCE, language.field_id_for_name("sentence").unwrap());
-    assert_eq!(PATTERN, language.field_id_for_name("pattern").unwrap());
-    assert_eq!(REWRITE, language.field_id_for_name("rewrite").unwrap());
-    assert_eq!(NAME, language.field_id_for_name("name").unwrap());
+fn test_strings() {
+    let input = "$MODULE T;\nF { 'ca' 'ne' = <'T.G' 'a\\'b\\n\\x41'>; }";
+    let sentence = &parse_input(input).unwrap().functions[0].sentences[0];
+    assert_eq!(
+        sentence.pattern,
+        vec![Char('c'), Char('a'), Char('n'), Char('e')]
+    );
+    assert_eq!(
+        sentence.rewrite,
+        vec![
+            FunBracketL,
+            Symbol(String::from("T.G")),
+            Char('a'),
+            Char('\''),
+            Char('b'),
+            Char('\n'),
+            Char('A'),
+            FunBracketR
+        ]
+    );
+    assert!(parse_input("$MODULE T;\nF { = '\\q'; }").is_err());
+    assert!(parse_input("$MODULE T;\nF { = '\\x4'; }").is_err());
 }
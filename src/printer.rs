@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests;
+
+use crate::data::*;
+use std::fmt;
+
+/// Renders objects in Refal syntax: runs of characters as single quoted literals,
+/// function names that are not identifiers as quoted literals after `<`.
+pub fn render(objects: &[Object]) -> String {
+    let mut text = String::new();
+    let mut prev: Option<&Object> = None;
+    let mut objects = objects.iter().peekable();
+    while let Some(object) = objects.next() {
+        let image = match object {
+            Object::Symbol(s) if is_identifier(s) => s.clone(),
+            Object::Symbol(s) if prev == Some(&Object::FunBracketL) => {
+                format!("'{}'", s.chars().map(escape).collect::<String>())
+            }
+            Object::Symbol(s) => format!("\"{}\"", s.escape_default()),
+            Object::Char(c) => {
+                let mut image = format!("'{}", escape(*c));
+                while let Some(Object::Char(c)) = objects.peek() {
+                    image.push_str(&escape(*c));
+                    objects.next();
+                }
+                image.push('\'');
+                image
+            }
+            Object::Number(n) => n.to_string(),
+            Object::StrBracketL => String::from("("),
+            Object::StrBracketR => String::from(")"),
+            Object::FunBracketL => String::from("<"),
+            Object::FunBracketR => String::from(">"),
+            Object::EVar(name) | Object::SVar(name) | Object::TVar(name) => name.clone(),
+        };
+        let opening = matches!(prev, None | Some(Object::StrBracketL | Object::FunBracketL));
+        let closing = matches!(object, Object::StrBracketR | Object::FunBracketR);
+        if !opening && !closing {
+            text.push(' ');
+        }
+        text.push_str(&image);
+        prev = Some(object);
+    }
+    text
+}
+
+/// Escapes a character for a quoted literal: quotes, backslashes and control characters.
+fn escape(c: char) -> String {
+    match c {
+        '\n' => String::from("\\n"),
+        '\t' => String::from("\\t"),
+        '\r' => String::from("\\r"),
+        '\\' | '\'' => format!("\\{}", c),
+        c if (c as u32) < 0x20 || c as u32 == 0x7f => format!("\\x{:02X}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(char::is_alphabetic)
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Appends an expression to a sentence being printed, separated by a space.
+pub(crate) fn push_expression(text: &mut String, objects: &[Object]) {
+    if !objects.is_empty() {
+        text.push(' ');
+        text.push_str(&render(objects));
+    }
+}
+
+/// Prints a sentence on one line, the sentences of its block included.
+impl fmt::Display for Sentence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut text = render(&self.pattern);
+        for condition in &self.conditions {
+            text.push(',');
+            push_expression(&mut text, &condition.result);
+            text.push_str(" :");
+            push_expression(&mut text, &condition.pattern);
+        }
+        match &self.block {
+            Some(sentences) => {
+                text.push(',');
+                push_expression(&mut text, &self.rewrite);
+                text.push_str(" : {");
+                for sentence in sentences {
+                    text.push_str(&format!(" {}", sentence));
+                }
+                text.push_str(" };");
+            }
+            None => {
+                text.push_str(if text.is_empty() { "=" } else { " =" });
+                push_expression(&mut text, &self.rewrite);
+                text.push_str(if self.rewrite.is_empty() { " ;" } else { ";" });
+            }
+        }
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.entry {
+            write!(f, "$ENTRY ")?;
+        }
+        writeln!(f, "{} {{", self.name)?;
+        for sentence in &self.sentences {
+            writeln!(f, "    {}", sentence)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for RefalModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "$MODULE {};", self.name)?;
+        if !self.externs.is_empty() {
+            let names: Vec<_> = self.externs.iter().map(|e| e.name.as_str()).collect();
+            writeln!(f, "$EXTERN {};", names.join(", "))?;
+        }
+        for function in &self.functions {
+            write!(f, "\n{}\n", function)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::data::*;
+use crate::parser;
+use crate::printer::render;
+
+#[cfg(test)]
+const PROGRAM: &str = "$MODULE T;
+$EXTERN Add, Prout;
+
+$ENTRY Go {
+    = <Prout 'a line\\n' <'T.F' 1 2>>;
+}
+
+F {
+    $s.X $e.1, <Compare $s.X 10> : '+' = ($s.X) <F $e.1>;
+    $t.1 $e.2, <G $e.2> : { A = B; ($e.3) = 'it\\'s'; };
+    , <G> : = ;
+}
+
+G {
+}
+";
+
+/// Forgets where the parts of a module are, to compare modules parsed from different texts.
+#[cfg(test)]
+fn without_locations(mut module: RefalModule) -> RefalModule {
+    fn clear(sentences: &mut [Sentence]) {
+        for sentence in sentences {
+            sentence.location = Location::default();
+            clear(sentence.block.as_deref_mut().unwrap_or_default());
+        }
+    }
+    module.location = Location::default();
+    for declaration in &mut module.externs {
+        declaration.location = Location::default();
+    }
+    for f in &mut module.functions {
+        f.location = Location::default();
+        clear(&mut f.sentences);
+    }
+    module
+}
+
+#[test]
+fn test_print_module() {
+    let module = parser::parse_input(PROGRAM).unwrap();
+    assert_eq!(module.to_string(), PROGRAM);
+}
+
+#[test]
+fn test_round_trip() {
+    let module = parser::parse_input(PROGRAM).unwrap();
+    let printed = parser::parse_input(&module.to_string()).unwrap();
+    assert_eq!(without_locations(printed), without_locations(module));
+}
+
+#[test]
+fn test_render() {
+    let objects = vec![
+        Object::FunBracketL,
+        Object::Symbol(String::from("F")),
+        Object::StrBracketL,
+        Object::Char('a'),
+        Object::Char('\t'),
+        Object::StrBracketR,
+        Object::EVar(String::from("$e.1")),
+        Object::Number(7),
+        Object::FunBracketR,
+    ];
+    assert_eq!(render(&objects), "<F ('a\\t') $e.1 7>");
+    assert_eq!(
+        parser::parse_expression(&render(&objects)).unwrap(),
+        objects
+    );
+}
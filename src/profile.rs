@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests;
+
+use crate::data::{sentence_starts, Command};
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What the evaluation of the calls of a function took.
+#[derive(Clone, Debug, Default)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// The time spent in the steps evaluating the calls.
+    pub time: Duration,
+    /// By sentence, how many times it was matched against an argument and how many times
+    /// it was selected to rewrite the call.
+    pub sentences: Vec<SentenceProfile>,
+    /// The steps taken lengthening e-variables.
+    pub lengthenings: u64,
+    /// The nodes of the result copied from matched variables.
+    pub copied: u64,
+    /// The nodes of the result moved from the argument.
+    pub transplanted: u64,
+    starts: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SentenceProfile {
+    pub attempts: u64,
+    pub successes: u64,
+}
+
+/// Counts gathered by the VM while it evaluates, by function, built-ins included.
+#[derive(Debug, Default)]
+pub struct Profile {
+    functions: HashMap<Symbol, FunctionProfile>,
+    /// The function of the step being evaluated and when the step started.
+    step: Option<(Symbol, Instant)>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The functions called, by name.
+    pub fn functions(&self) -> &HashMap<Symbol, FunctionProfile> {
+        &self.functions
+    }
+
+    /// Starts a step evaluating a call of `function`, compiled into `commands` unless built in.
+    pub(crate) fn enter(&mut self, function: Symbol, commands: Option<&[Command]>) {
+        self.finish();
+        self.step = Some((function, Instant::now()));
+        let profile = self.functions.entry(function).or_default();
+        if profile.calls == 0 {
+            if let Some(commands) = commands {
+                profile.starts = sentence_starts(commands);
+                profile.sentences = vec![SentenceProfile::default(); profile.starts.len()];
+            }
+        }
+        profile.calls += 1;
+        if let Some(sentence) = profile.sentences.first_mut() {
+            sentence.attempts += 1;
+        }
+    }
+
+    /// Ends the step being evaluated.
+    pub(crate) fn finish(&mut self) {
+        if let Some((function, start)) = self.step.take() {
+            self.function(function).time += start.elapsed();
+        }
+    }
+
+    /// Records that matching `function` goes on at `address`, after a failure.
+    pub(crate) fn jump(&mut self, function: Symbol, address: usize) {
+        let profile = self.function(function);
+        if let Ok(sentence) = profile.starts.binary_search(&address) {
+            profile.sentences[sentence].attempts += 1;
+        }
+    }
+
+    /// Records that a switch of `function` at `address` goes to the sentence at `target`:
+    /// the sentence containing the switch, counted as attempted, is not after all.
+    pub(crate) fn switch(&mut self, function: Symbol, address: usize, target: usize) {
+        let profile = self.function(function);
+        let sentence = profile.starts.partition_point(|&start| start <= address);
+        if let (Some(sentence), Ok(target)) = (
+            sentence.checked_sub(1),
+            profile.starts.binary_search(&target),
+        ) {
+            profile.sentences[sentence].attempts -= 1;
+            profile.sentences[target].attempts += 1;
+        }
+    }
+
+    /// Records that the sentence of `function` containing `address` rewrites the call.
+    pub(crate) fn rewrite(&mut self, function: Symbol, address: usize) {
+        let profile = self.function(function);
+        let sentence = profile.starts.partition_point(|&start| start <= address);
+        if let Some(sentence) = sentence.checked_sub(1) {
+            profile.sentences[sentence].successes += 1;
+        }
+    }
+
+    pub(crate) fn function(&mut self, function: Symbol) -> &mut FunctionProfile {
+        self.functions.entry(function).or_default()
+    }
+
+    /// Lists the functions, those that took most time first, with the counts of their sentences.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, p)| (name.as_str(), p))
+            .collect();
+        functions.sort_by(|(a, p), (b, q)| {
+            q.time
+                .cmp(&p.time)
+                .then(q.calls.cmp(&p.calls))
+                .then(a.cmp(b))
+        });
+        let total: Duration = functions.iter().map(|(_, p)| p.time).sum();
+        let mut lines = vec![format!(
+            "{:<24} {:>10} {:>12} {:>10} {:>10} {:>12}",
+            "function", "calls", "time (ms)", "lengthen", "copied", "transplanted"
+        )];
+        for (name, p) in functions {
+            lines.push(format!(
+                "{:<24} {:>10} {:>12.3} {:>10} {:>10} {:>12}",
+                name,
+                p.calls,
+                p.time.as_secs_f64() * 1000.0,
+                p.lengthenings,
+                p.copied,
+                p.transplanted
+            ));
+            for (i, sentence) in p.sentences.iter().enumerate() {
+                lines.push(format!(
+                    "  sentence {:<4} {:>10} attempts {:>10} successes",
+                    i + 1,
+                    sentence.attempts,
+                    sentence.successes
+                ));
+            }
+        }
+        lines.push(format!(
+            "total time: {:.3} ms",
+            total.as_secs_f64() * 1000.0
+        ));
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,75 @@
+use crate::compiler::{compile, compile_expression, decision_trees};
+use crate::host::MemoryHost;
+use crate::profile::{Profile, SentenceProfile};
+use crate::program::Program;
+use crate::symbol::Symbol;
+use crate::vm::{eval_expression, render, Limits};
+
+#[test]
+fn test_profile() {
+    let program = Program::new(compile("$MODULE P; F { = A; $e.1 = $e.1 $e.1; }").unwrap());
+    let expression = compile_expression("P", "<F> <F 'ab'> <F <Add 1 2>>").unwrap();
+    let mut profile = Profile::new();
+    let result = eval_expression(
+        &program,
+        &expression,
+        &mut MemoryHost::default(),
+        Limits::default(),
+        None,
+        Some(&mut profile),
+        None,
+    );
+    assert_eq!(result.unwrap().len(), 7);
+
+    let f = &profile.functions()[&Symbol::intern("P.F")];
+    assert_eq!(f.calls, 3);
+    assert_eq!(
+        f.sentences,
+        [
+            SentenceProfile {
+                attempts: 3,
+                successes: 1
+            },
+            SentenceProfile {
+                attempts: 2,
+                successes: 2
+            }
+        ]
+    );
+    assert_eq!((f.copied, f.transplanted, f.lengthenings), (3, 3, 0));
+    let add = &profile.functions()[&Symbol::intern("Add")];
+    assert_eq!(add.calls, 1);
+    assert!(add.sentences.is_empty());
+
+    let report = profile.report();
+    assert!(report.starts_with("function"));
+    assert!(report.contains("\n  sentence 2             2 attempts          2 successes\n"));
+}
+
+#[test]
+fn test_profile_switch() {
+    let source = "$MODULE P; F { A = 1; B = 2; C = 3; D = 4; $e.1 = 0; }";
+    let mut defs = compile(source).unwrap();
+    decision_trees(&mut defs);
+    let program = Program::new(defs);
+    let expression = compile_expression("P", "<F C> <F D> <F D> <F E>").unwrap();
+    let mut profile = Profile::new();
+    let result = eval_expression(
+        &program,
+        &expression,
+        &mut MemoryHost::default(),
+        Limits::default(),
+        None,
+        Some(&mut profile),
+        None,
+    );
+    assert_eq!(render(&result.unwrap()), "3 4 4 0");
+
+    // The sentences before those selected are not attempted.
+    let counts: Vec<_> = profile.functions()[&Symbol::intern("P.F")]
+        .sentences
+        .iter()
+        .map(|sentence| (sentence.attempts, sentence.successes))
+        .collect();
+    assert_eq!(counts, [(1, 0), (0, 0), (1, 1), (2, 2), (1, 1)]);
+}
@@ -0,0 +1,152 @@
+#[cfg(test)]
+mod tests;
+
+use crate::builtins::{self, Builtin};
+use crate::data::{Command, Error, Result};
+use crate::symbol::Symbol;
+use crate::vm::native::NativeFn;
+use std::collections::HashMap;
+
+/// What a call may refer to.
+pub(crate) enum Callee {
+    Compiled(Vec<Command>),
+    Native(NativeFn),
+    Builtin(Builtin),
+}
+
+/// A definition table linked for evaluation: every function, compiled or built-in, has an index,
+/// and the symbols naming functions are mapped to their indices once, so that a call finds its
+/// function without hashing. Since a call may name its function through a variable, any symbol
+/// met at run time is resolved the same way.
+pub struct Program {
+    /// The compiled or native functions sorted by name, then the built-ins.
+    functions: Vec<(Symbol, Callee)>,
+    /// The indices of the compiled or native functions.
+    defined: Indices,
+    /// The indices of the built-ins, found when no function is defined by the name.
+    builtins: Indices,
+    unresolved: Vec<String>,
+}
+
+/// The indices of some functions by the ids of their names, in a table spanning those ids
+/// only rather than every symbol of the process.
+struct Indices {
+    /// The smallest id.
+    base: usize,
+    indices: Vec<Option<usize>>,
+}
+
+impl Indices {
+    fn new(functions: &[(Symbol, Callee)], offset: usize) -> Indices {
+        let ids = || functions.iter().map(|(name, _)| name.index());
+        let base = ids().min().unwrap_or(0);
+        let size = ids().max().map_or(0, |max| max + 1 - base);
+        let mut indices = vec![None; size];
+        for (index, id) in ids().enumerate() {
+            indices[id - base] = Some(offset + index);
+        }
+        Indices { base, indices }
+    }
+
+    fn get(&self, name: Symbol) -> Option<usize> {
+        *self.indices.get(name.index().checked_sub(self.base)?)?
+    }
+}
+
+impl Program {
+    /// Links `defs`. A call to an undefined function fails when it is evaluated;
+    /// `unresolved` lists those written in the commands beforehand.
+    pub fn new(defs: HashMap<String, Vec<Command>>) -> Program {
+        let mut defs: Vec<_> = defs.into_iter().collect();
+        defs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let functions = defs
+            .into_iter()
+            .map(|(name, commands)| (Symbol::intern(&name), Callee::Compiled(commands)))
+            .collect();
+        Program::with(functions)
+    }
+
+    /// Links functions compiled to native code, such as those `rust_backend` generates.
+    pub fn native(functions: &[(&str, NativeFn)]) -> Program {
+        let mut functions = functions.to_vec();
+        functions.sort_by_key(|&(name, _)| name);
+        let functions = functions
+            .into_iter()
+            .map(|(name, native)| (Symbol::intern(name), Callee::Native(native)))
+            .collect();
+        Program::with(functions)
+    }
+
+    /// Links `functions`, sorted by name, with the built-ins.
+    fn with(mut functions: Vec<(Symbol, Callee)>) -> Program {
+        let defined = Indices::new(&functions, 0);
+        let first_builtin = functions.len();
+        for &(name, builtin) in builtins::all() {
+            functions.push((Symbol::intern(name), Callee::Builtin(builtin)));
+        }
+        let builtins = Indices::new(&functions[first_builtin..], first_builtin);
+        let mut program = Program {
+            functions,
+            defined,
+            builtins,
+            unresolved: Vec::new(),
+        };
+
+        let mut unresolved = Vec::new();
+        for (_, callee) in &program.functions {
+            if let Callee::Compiled(commands) = callee {
+                for pair in commands.windows(2) {
+                    if let [Command::InsertFunBracketL, Command::InsertSymbol(name)] = pair {
+                        if program.lookup(*name).is_none() {
+                            unresolved.push(name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        unresolved.sort();
+        unresolved.dedup();
+        program.unresolved = unresolved;
+        program
+    }
+
+    /// Links `defs`, failing with `Error::UnresolvedCalls` if their commands call undefined functions.
+    pub fn link(defs: HashMap<String, Vec<Command>>) -> Result<Program> {
+        let program = Program::new(defs);
+        if program.unresolved.is_empty() {
+            Ok(program)
+        } else {
+            Err(Error::UnresolvedCalls(program.unresolved))
+        }
+    }
+
+    /// The functions called in the commands but neither defined nor built in, sorted.
+    pub fn unresolved(&self) -> &[String] {
+        &self.unresolved
+    }
+
+    /// The compiled functions with their commands, sorted by name.
+    pub fn functions(&self) -> impl Iterator<Item = (Symbol, &[Command])> {
+        self.functions
+            .iter()
+            .filter_map(|(name, callee)| match callee {
+                Callee::Compiled(commands) => Some((*name, &commands[..])),
+                Callee::Native(_) | Callee::Builtin(_) => None,
+            })
+    }
+
+    /// The commands of the compiled function `name`.
+    pub fn commands(&self, name: &str) -> Option<&[Command]> {
+        match self.lookup(Symbol::intern(name)) {
+            Some(Callee::Compiled(commands)) => Some(commands),
+            _ => None,
+        }
+    }
+
+    /// The function named by the symbol `name`. A function defined by the name of a built-in
+    /// hides it.
+    pub(crate) fn lookup(&self, name: Symbol) -> Option<&Callee> {
+        let index = self.defined.get(name).or_else(|| self.builtins.get(name))?;
+        Some(&self.functions[index].1)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::data::{Command, Error};
+use crate::program::Program;
+use crate::symbol::Symbol;
+use crate::vm::{eval_main, Object};
+use std::collections::HashMap;
+
+/// `F { = <G e.Arg>; }`
+#[cfg(test)]
+fn call(g: &str, arg: &[Command]) -> Vec<Command> {
+    let mut commands = vec![
+        Command::MatchEmpty,
+        Command::RewriteStart,
+        Command::InsertFunBracketL,
+        Command::InsertSymbol(Symbol::intern(g)),
+    ];
+    commands.extend_from_slice(arg);
+    commands.extend([
+        Command::InsertFunBracketR,
+        Command::RewriteFinalize,
+        Command::MatchStart,
+    ]);
+    commands
+}
+
+#[test]
+fn test_link() {
+    let mut defs = HashMap::new();
+    let arg = [Command::InsertNumber(2), Command::InsertNumber(3)];
+    defs.insert(String::from("P.Go"), call("Add", &arg));
+    defs.insert(String::from("P.Id"), call("P.Go", &[]));
+    let program = Program::link(defs).unwrap();
+    assert!(program.unresolved().is_empty());
+    assert_eq!(eval_main(&program, "P.Id"), Ok(vec![Object::Number(5)]));
+    let names: Vec<_> = program.functions().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["P.Go", "P.Id"]);
+    assert_eq!(program.commands("P.Id"), Some(&call("P.Go", &[])[..]));
+    assert_eq!(program.commands("Add"), None);
+}
+
+#[test]
+fn test_unresolved() {
+    let mut defs = HashMap::new();
+    defs.insert(String::from("P.Go"), call("P.Missing", &[]));
+    defs.insert(String::from("P.Again"), call("P.Missing", &[]));
+    defs.insert(String::from("P.Other"), call("Q.Other", &[]));
+    let program = Program::new(defs.clone());
+    assert_eq!(program.unresolved(), ["P.Missing", "Q.Other"]);
+    assert_eq!(
+        eval_main(&program, "P.Go"),
+        Err(Error::UndefinedFunction {
+            function: String::from("P.Missing"),
+            argument: String::new(),
+        })
+    );
+    let error = Program::link(defs).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "calls to undefined functions: P.Missing, Q.Other"
+    );
+}
+
+#[test]
+fn test_hide_builtin() {
+    let mut defs = HashMap::new();
+    defs.insert(String::from("Add"), call("P.Seven", &[]));
+    defs.insert(
+        String::from("P.Seven"),
+        vec![
+            Command::MatchEmpty,
+            Command::RewriteStart,
+            Command::InsertNumber(7),
+            Command::RewriteFinalize,
+            Command::MatchStart,
+        ],
+    );
+    defs.insert(String::from("P.Go"), call("Add", &[]));
+    let program = Program::link(defs).unwrap();
+    assert_eq!(eval_main(&program, "P.Go"), Ok(vec![Object::Number(7)]));
+    assert_eq!(program.commands("Add"), Some(&call("P.Seven", &[])[..]));
+}
@@ -0,0 +1,254 @@
+#[cfg(test)]
+mod tests;
+
+use crate::compiler;
+use crate::data::*;
+use crate::disassembler;
+use crate::host::Host;
+use crate::loader::Loader;
+use crate::program::Program;
+use crate::vm::{self, Limits, Trace};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub const HELP: &str = "\
+EXPR          evaluate an expression, e.g. <F 'abc'>
+:load FILE... load modules, along with those loaded before
+:reload       reload all modules from their files
+:dump F       list the commands of function F
+:trace        switch tracing of calls on or off
+:limit [steps|nodes|calls N|none]
+              show the limits of an evaluation, or change one
+:history      list the lines entered so far
+:help         show this help
+:quit         leave";
+
+/// The limits of an evaluation until changed with `:limit`, so that an expression
+/// evaluating forever fails instead of hanging the session.
+pub const DEFAULT_LIMITS: Limits = Limits {
+    steps: Some(10_000_000),
+    nodes: Some(10_000_000),
+    calls: None,
+};
+
+/// The number of lines kept in the history file.
+const HISTORY_SIZE: usize = 1000;
+
+/// An interactive session: evaluates expressions against the loaded modules.
+/// Unqualified function names refer to the module loaded last.
+pub struct Repl {
+    loader: Loader,
+    /// The modules loaded besides the files, found in the search path.
+    modules: Vec<String>,
+    files: Vec<PathBuf>,
+    module: String,
+    program: Program,
+    trace: bool,
+    limits: Limits,
+    history: Vec<String>,
+    /// The file the lines executed are added to.
+    history_file: Option<PathBuf>,
+}
+
+impl Repl {
+    pub fn new(loader: Loader, modules: Vec<String>) -> Self {
+        Repl {
+            loader,
+            modules,
+            files: Vec::new(),
+            module: String::new(),
+            program: Program::new(HashMap::new()),
+            trace: false,
+            limits: DEFAULT_LIMITS,
+            history: Vec::new(),
+            history_file: None,
+        }
+    }
+
+    /// Keeps the history in `file`, its last lines coming before those executed, which are
+    /// added to it. The file is cut down to its last `HISTORY_SIZE` lines.
+    pub fn open_history(&mut self, file: PathBuf) -> Result<()> {
+        let io_error = |error: io::Error| Error::Io(format!("{}: {}", file.display(), error));
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(io_error(error)),
+        };
+        let lines: Vec<_> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let start = lines.len().saturating_sub(HISTORY_SIZE);
+        if start > 0 {
+            let kept: String = lines[start..]
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect();
+            fs::write(&file, kept).map_err(io_error)?;
+        }
+        let mut history: Vec<_> = lines[start..].iter().map(|line| line.to_string()).collect();
+        history.append(&mut self.history);
+        self.history = history;
+        self.history_file = Some(file);
+        Ok(())
+    }
+
+    /// Adds `files` to the loaded ones and reloads them all. Nothing changes on error.
+    pub fn load(&mut self, files: &[PathBuf]) -> Result<()> {
+        let mut all = self.files.clone();
+        all.extend(files.iter().filter(|f| !self.files.contains(f)).cloned());
+        let (module, program) = self.compile(&all)?;
+        self.files = all;
+        self.module = module;
+        self.program = program;
+        Ok(())
+    }
+
+    /// Recompiles the loaded files, e.g. after they were edited. Nothing changes on error.
+    pub fn reload(&mut self) -> Result<()> {
+        let (module, program) = self.compile(&self.files)?;
+        self.module = module;
+        self.program = program;
+        Ok(())
+    }
+
+    /// The module name of the last of `files` and the program made of all of them,
+    /// linked together.
+    fn compile(&self, files: &[PathBuf]) -> Result<(String, Program)> {
+        let units = self.loader.read_units(files, &self.modules)?;
+        let last = files.last().map(|file| file.display().to_string());
+        let module = units
+            .iter()
+            .find(|unit| Some(&unit.file) == last.as_ref())
+            .map(|unit| unit.module.name.clone())
+            .unwrap_or_default();
+        Ok((module, Program::new(compiler::compile_units(units)?)))
+    }
+
+    /// Executes a line: a command or an expression to evaluate, with I/O through `host`.
+    /// Returns the text to show, the trace of calls followed by the result.
+    pub fn execute(&mut self, line: &str, host: &mut dyn Host) -> Result<String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(String::new());
+        }
+        self.history.push(line.to_string());
+        if let Some(file) = &self.history_file {
+            // The line is executed even if the history cannot be saved.
+            let _ = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .and_then(|mut file| writeln!(file, "{}", line));
+        }
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        match command {
+            ":load" if !argument.is_empty() => {
+                let files: Vec<_> = argument.split_whitespace().map(PathBuf::from).collect();
+                self.load(&files)?;
+                Ok(format!(
+                    "loaded {} functions",
+                    self.program.functions().count()
+                ))
+            }
+            ":reload" => {
+                self.reload()?;
+                Ok(format!(
+                    "loaded {} functions",
+                    self.program.functions().count()
+                ))
+            }
+            ":dump" if !argument.is_empty() => {
+                let name = self.qualify(argument);
+                match self.program.commands(&name) {
+                    Some(commands) => Ok(disassembler::disassemble_function(&name, commands)),
+                    None => Ok(format!("no function {}", name)),
+                }
+            }
+            ":trace" => {
+                self.trace = !self.trace;
+                Ok(format!("tracing {}", if self.trace { "on" } else { "off" }))
+            }
+            ":limit" => Ok(self.limit(argument)),
+            ":history" => {
+                let lines: Vec<_> = self
+                    .history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| format!("{:4}  {}", i + 1, line))
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            ":help" => Ok(HELP.to_string()),
+            _ if command.starts_with(':') => Ok(format!("unknown command {}, try :help", line)),
+            _ => self.evaluate(line, host),
+        }
+    }
+
+    /// The lines executed so far, commands included.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    fn evaluate(&self, input: &str, host: &mut dyn Host) -> Result<String> {
+        let expression = compiler::compile_expression(&self.module, input)?;
+        let mut lines = Vec::new();
+        let mut trace = |step: u64, call: &[vm::Object]| {
+            lines.push(format!("{:>6}: {}", step, vm::render(call)));
+        };
+        let tracer: Option<Trace> = if self.trace { Some(&mut trace) } else { None };
+        let result = vm::eval_expression(
+            &self.program,
+            &expression,
+            host,
+            self.limits,
+            tracer,
+            None,
+            None,
+        )?;
+        lines.push(vm::render(&result));
+        Ok(lines.join("\n"))
+    }
+
+    /// Sets the limit `argument` names to the value it gives, then shows the limits.
+    fn limit(&mut self, argument: &str) -> String {
+        let words: Vec<_> = argument.split_whitespace().collect();
+        if let [name, value] = words[..] {
+            let value = match value {
+                "none" => None,
+                _ => match value.parse() {
+                    Ok(value) => Some(value),
+                    Err(_) => return format!("invalid limit {}, try :help", value),
+                },
+            };
+            match name {
+                "steps" => self.limits.steps = value,
+                "nodes" => self.limits.nodes = value.map(|n| n as usize),
+                "calls" => self.limits.calls = value.map(|n| n as usize),
+                _ => return format!("unknown limit {}, try :help", name),
+            }
+        } else if !words.is_empty() {
+            return String::from("usage: :limit [steps|nodes|calls N|none]");
+        }
+        let show = |limit: Option<u64>| limit.map_or(String::from("none"), |n| n.to_string());
+        format!(
+            "steps {}, nodes {}, calls {}",
+            show(self.limits.steps),
+            show(self.limits.nodes.map(|n| n as u64)),
+            show(self.limits.calls.map(|n| n as u64))
+        )
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        if name.contains('.') || self.module.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.module, name)
+        }
+    }
+}
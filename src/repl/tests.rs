@@ -0,0 +1,156 @@
+use crate::data::Error;
+use crate::host::MemoryHost;
+use crate::loader::Loader;
+use crate::repl::Repl;
+use std::fs;
+
+#[test]
+fn test_repl() {
+    let dir = std::env::temp_dir().join(format!("refal-repl-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("Main.refal");
+    fs::write(
+        dir.join("Lib.refal"),
+        "$MODULE Lib;\n$ENTRY Twice { $e.1 = $e.1 $e.1; }",
+    )
+    .unwrap();
+    fs::write(
+        &main,
+        "$MODULE Main;\nRev { $t.1 $e.2 = <Rev $e.2> $t.1; = ; }",
+    )
+    .unwrap();
+
+    let mut repl = Repl::new(Loader::new(vec![]), vec![]);
+    let mut host = MemoryHost::default();
+    let mut execute = |repl: &mut Repl, line: &str| repl.execute(line, &mut host);
+    let load = format!(
+        ":load {} {}",
+        dir.join("Lib.refal").display(),
+        main.display()
+    );
+    assert_eq!(execute(&mut repl, &load).unwrap(), "loaded 2 functions");
+    assert_eq!(
+        execute(&mut repl, "<Rev 'ab'> <'Lib.Twice' 1>").unwrap(),
+        "'ba' 1 1"
+    );
+    assert_eq!(execute(&mut repl, "<Prout 'hi'>").unwrap(), "");
+    assert_eq!(execute(&mut repl, "A (B)").unwrap(), "A (B)");
+    assert!(matches!(execute(&mut repl, "<Rev"), Err(Error::Parsing(_))));
+
+    assert_eq!(execute(&mut repl, ":trace").unwrap(), "tracing on");
+    assert_eq!(
+        execute(&mut repl, "<Rev 'ab'>").unwrap(),
+        "     1: <'Main.Rev' 'ab'>\n     2: <'Main.Rev' 'b'>\n     3: <'Main.Rev'>\n'ba'"
+    );
+    assert_eq!(execute(&mut repl, ":trace").unwrap(), "tracing off");
+
+    assert!(execute(&mut repl, ":dump Rev")
+        .unwrap()
+        .starts_with("Main.Rev {\n  sentence 1:\n"));
+    assert_eq!(
+        execute(&mut repl, ":dump Nope").unwrap(),
+        "no function Main.Nope"
+    );
+
+    fs::write(&main, "$MODULE Main;\nRev { = ; }\nId { $e.1 = $e.1; }").unwrap();
+    assert_eq!(execute(&mut repl, ":reload").unwrap(), "loaded 3 functions");
+    assert_eq!(execute(&mut repl, "<Id <Rev>>").unwrap(), "");
+    fs::write(&main, "$MODULE Main;\nRev {").unwrap();
+    assert!(matches!(
+        execute(&mut repl, ":reload"),
+        Err(Error::Parsing(_))
+    ));
+    assert_eq!(execute(&mut repl, "<Id X>").unwrap(), "X");
+
+    assert_eq!(
+        execute(&mut repl, ":bogus").unwrap(),
+        "unknown command :bogus, try :help"
+    );
+    assert_eq!(repl.history().len(), 15);
+    assert_eq!(repl.history()[1], "<Rev 'ab'> <'Lib.Twice' 1>");
+    assert!(repl
+        .execute(":history", &mut MemoryHost::default())
+        .unwrap()
+        .starts_with("   1  :load "));
+    assert_eq!(host.output, vec![String::from("hi")]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_extern() {
+    let dir = std::env::temp_dir().join(format!("refal-repl-extern-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (lib, main) = (dir.join("Strings.refal"), dir.join("App.refal"));
+    fs::write(&lib, "$MODULE Strings;\n$ENTRY Twice { $e.1 = $e.1 $e.1; }").unwrap();
+    fs::write(
+        &main,
+        "$MODULE App;\n$EXTERN Twice;\nGo { $e.1 = <Twice $e.1>; }",
+    )
+    .unwrap();
+
+    let mut repl = Repl::new(Loader::new(vec![]), vec![]);
+    let mut host = MemoryHost::default();
+    assert!(matches!(
+        repl.load(std::slice::from_ref(&main)),
+        Err(Error::Linking(_))
+    ));
+    repl.load(&[lib, main]).unwrap();
+    assert_eq!(repl.execute("<Go 'ab'>", &mut host).unwrap(), "'abab'");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_limit() {
+    let dir = std::env::temp_dir().join(format!("refal-repl-limit-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("Loop.refal");
+    fs::write(&main, "$MODULE Loop;\nLoop { = <Loop>; }").unwrap();
+
+    let mut repl = Repl::new(Loader::new(vec![]), vec![]);
+    let mut host = MemoryHost::default();
+    repl.load(&[main]).unwrap();
+    assert_eq!(
+        repl.execute(":limit", &mut host).unwrap(),
+        "steps 10000000, nodes 10000000, calls none"
+    );
+    assert_eq!(
+        repl.execute(":limit steps 100", &mut host).unwrap(),
+        "steps 100, nodes 10000000, calls none"
+    );
+    assert!(matches!(
+        repl.execute("<Loop>", &mut host),
+        Err(Error::LimitExceeded { steps: 100, .. })
+    ));
+    assert_eq!(repl.execute("A", &mut host).unwrap(), "A");
+    assert_eq!(
+        repl.execute(":limit nodes none", &mut host).unwrap(),
+        "steps 100, nodes none, calls none"
+    );
+    assert_eq!(
+        repl.execute(":limit steps x", &mut host).unwrap(),
+        "invalid limit x, try :help"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_history_file() {
+    let dir = std::env::temp_dir().join(format!("refal-repl-history-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("history");
+    let lines: Vec<_> = (0..1005).map(|i| format!("{}", i)).collect();
+    fs::write(&file, lines.join("\n")).unwrap();
+
+    let mut repl = Repl::new(Loader::new(vec![]), vec![]);
+    repl.open_history(file.clone()).unwrap();
+    assert_eq!(repl.history().len(), 1000);
+    assert_eq!(repl.history()[0], "5");
+    repl.execute("A B", &mut MemoryHost::default()).unwrap();
+
+    let mut repl = Repl::new(Loader::new(vec![]), vec![]);
+    repl.open_history(file).unwrap();
+    assert_eq!(repl.history().len(), 1000);
+    assert_eq!(repl.history()[0], "6");
+    assert_eq!(repl.history()[999], "A B");
+    fs::remove_dir_all(dir).unwrap();
+}
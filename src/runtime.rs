@@ -1,130 +1,566 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+#[cfg(test)]
+mod tests;
 
-#[derive(Clone, Debug, PartialEq)]
+use crate::data::{self, Error, Result};
+use crate::printer;
+use crate::symbol::Symbol;
+#[cfg(test)]
+use std::cell::Cell;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Object {
-    Symbol(String),
+    Symbol(Symbol),
+    Char(char),
+    /// A macrodigit, i.e. a number below 2^32.
+    Number(u32),
     StrBracketL,
     StrBracketR,
     FunBracketL,
     FunBracketR,
     First,
     Last,
+    /// Stands for a subexpression shared by copies, by its index in the arena.
+    /// Never part of the result of an evaluation.
+    Shared(u32),
 }
 
 impl Object {
-    pub fn symbol(&self) -> Option<&String> {
+    pub fn symbol(&self) -> Option<Symbol> {
         match self {
-            Object::Symbol(s) => Some(s),
+            Object::Symbol(s) => Some(*s),
             _ => None,
         }
     }
+
+    /// The object as it is written in a program; `None` for the borders of the view.
+    fn to_data(self) -> Option<data::Object> {
+        Some(match self {
+            Object::Symbol(s) => data::Object::Symbol(s.to_string()),
+            Object::Char(c) => data::Object::Char(c),
+            Object::Number(n) => data::Object::Number(n),
+            Object::StrBracketL => data::Object::StrBracketL,
+            Object::StrBracketR => data::Object::StrBracketR,
+            Object::FunBracketL => data::Object::FunBracketL,
+            Object::FunBracketR => data::Object::FunBracketR,
+            Object::First | Object::Last | Object::Shared(_) => return None,
+        })
+    }
+
+    /// Checks whether the object can be matched by an s-variable.
+    pub fn is_symbol(&self) -> bool {
+        matches!(
+            self,
+            Object::Symbol(_) | Object::Char(_) | Object::Number(_)
+        )
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of nodes allocated and not yet freed in this thread, counted in tests only.
+    static LIVE_NODES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The number of nodes allocated and not yet freed in this thread, to detect leaks.
+#[cfg(test)]
+pub fn live_nodes() -> usize {
+    LIVE_NODES.with(Cell::get)
 }
 
-pub struct Node {
-    pub object: Object,
-    prev: RefCell<Option<Rc<Node>>>,
-    next: RefCell<Option<Rc<Node>>>,
-    twin: RefCell<Option<Rc<Node>>>,
+/// The index of a node in its arena.
+pub type NodeId = usize;
+
+/// Stands for a missing neighbour or twin.
+const NIL: NodeId = usize::MAX;
+
+/// An object of the view with its neighbours and, for a bracket, its pair.
+struct Node {
+    object: Object,
+    prev: NodeId,
+    next: NodeId,
+    twin: NodeId,
 }
 
-impl Node {
-    pub fn new(object: Object) -> Self {
-        Node {
+/// The first and last nodes of a list, which hold `Object::First` and `Object::Last`.
+#[derive(Clone, Copy)]
+pub struct Chain {
+    pub first: NodeId,
+    pub last: NodeId,
+}
+
+/// A subexpression kept out of the view as a list of its own, from `first` to `last`,
+/// while `refs` nodes holding `Object::Shared` stand for it. It is never modified:
+/// a node standing for it is replaced by a copy of the list when the objects are needed,
+/// or by the list itself once it is the only one left.
+#[derive(Clone, Copy)]
+struct Shared {
+    first: NodeId,
+    last: NodeId,
+    refs: usize,
+    /// The number of objects it stands for, those of the subexpressions in it included.
+    length: usize,
+}
+
+/// Storage for the nodes of the view. As in Refal-2, freed nodes are linked
+/// through `next` into a free list and reused before the arena grows.
+pub struct Arena {
+    nodes: Vec<Node>,
+    free: NodeId,
+    live: usize,
+    /// The number of nodes in use were no subexpression shared. It wraps around rather than
+    /// overflow, a limit on it being checked long before.
+    expanded: usize,
+    shared: Vec<Shared>,
+    /// The indices of `shared` no longer in use.
+    free_shared: Vec<usize>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena {
+            nodes: Vec::new(),
+            free: NIL,
+            live: 0,
+            expanded: 0,
+            shared: Vec::new(),
+            free_shared: Vec::new(),
+        }
+    }
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of nodes in use.
+    #[cfg(test)]
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /// The number of nodes in use were no subexpression shared, i.e. with the subexpressions
+    /// kept out of the view counted once for each node standing for them.
+    pub fn expanded(&self) -> usize {
+        self.expanded
+    }
+
+    /// The number of objects `object` stands for.
+    fn weight(&self, object: Object) -> usize {
+        match object {
+            Object::Shared(index) => self.shared[index as usize].length,
+            _ => 1,
+        }
+    }
+
+    /// Replaces the object of `node`, keeping the count of expanded nodes.
+    fn set_object(&mut self, node: NodeId, object: Object) {
+        let weight = self.weight(self.nodes[node].object);
+        self.expanded = self
+            .expanded
+            .wrapping_sub(weight)
+            .wrapping_add(self.weight(object));
+        self.nodes[node].object = object;
+    }
+
+    pub fn alloc(&mut self, object: Object) -> NodeId {
+        let node = Node {
             object,
-            prev: RefCell::new(None),
-            next: RefCell::new(None),
-            twin: RefCell::new(None),
+            prev: NIL,
+            next: NIL,
+            twin: NIL,
+        };
+        self.live += 1;
+        self.expanded = self.expanded.wrapping_add(self.weight(object));
+        #[cfg(test)]
+        LIVE_NODES.with(|n| n.set(n.get() + 1));
+        if let Object::Shared(index) = object {
+            self.shared[index as usize].refs += 1;
+        }
+        if self.free == NIL {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        } else {
+            let id = self.free;
+            self.free = self.nodes[id].next;
+            self.nodes[id] = node;
+            id
+        }
+    }
+
+    /// Frees `start` and the nodes following it.
+    pub fn free(&mut self, start: NodeId) {
+        let mut cursor = start;
+        loop {
+            self.live -= 1;
+            #[cfg(test)]
+            LIVE_NODES.with(|n| n.set(n.get() - 1));
+            let node = &mut self.nodes[cursor];
+            let object = node.object;
+            node.prev = NIL;
+            node.twin = NIL;
+            let next = node.next;
+            self.expanded = self.expanded.wrapping_sub(self.weight(object));
+            if let Object::Shared(index) = object {
+                self.release(index as usize);
+            }
+            if next == NIL {
+                self.nodes[cursor].next = self.free;
+                break;
+            }
+            cursor = next;
+        }
+        self.free = start;
+    }
+
+    /// Drops a reference to a shared subexpression, freeing it with the last one.
+    fn release(&mut self, index: usize) {
+        let shared = &mut self.shared[index];
+        shared.refs -= 1;
+        if shared.refs == 0 {
+            let (first, length) = (shared.first, shared.length);
+            self.free_shared.push(index);
+            // The nodes of the list were not counted as expanded, being out of the view.
+            self.expanded = self.expanded.wrapping_add(length);
+            self.free(first);
+        }
+    }
+
+    /// Replaces the nodes from `start` to `end` by a node standing for them, returned,
+    /// so that copying them takes a single node.
+    pub fn share(&mut self, start: NodeId, end: NodeId) -> NodeId {
+        let mut length = 0usize;
+        let mut cursor = start;
+        loop {
+            length = length.wrapping_add(self.weight(*self.object(cursor)));
+            if cursor == end {
+                break;
+            }
+            cursor = self.next(cursor);
+        }
+        // The nodes leave the view, the node standing for them counting for them.
+        self.expanded = self.expanded.wrapping_sub(length);
+        let shared = Shared {
+            first: start,
+            last: end,
+            refs: 0,
+            length,
+        };
+        let index = match self.free_shared.pop() {
+            Some(index) => {
+                self.shared[index] = shared;
+                index
+            }
+            None => {
+                self.shared.push(shared);
+                self.shared.len() - 1
+            }
+        };
+        let (prev, next) = (self.prev(start), self.next(end));
+        let node = self.alloc(Object::Shared(index as u32));
+        self.link(prev, node);
+        self.link(node, next);
+        self.unlink_prev(start);
+        self.unlink_next(end);
+        node
+    }
+
+    /// Replaces `node`, if it stands for a shared subexpression, by the objects of the
+    /// subexpression. `node` is kept as the first of them, or as the last one if `last`.
+    /// Returns the node at the other end.
+    pub fn unshare(&mut self, node: NodeId, last: bool) -> NodeId {
+        let mut other = node;
+        while let Object::Shared(index) = *self.object(node) {
+            let end = self.unshare_once(node, index as usize, last);
+            // When the subexpression is a single node standing for another one,
+            // the other end is found by expanding that one.
+            if other == node {
+                other = end;
+            }
+        }
+        other
+    }
+
+    fn unshare_once(&mut self, node: NodeId, index: usize, last: bool) -> NodeId {
+        let Shared {
+            first,
+            last: end,
+            refs,
+            length,
+        } = self.shared[index];
+        let (first, end) = if refs == 1 {
+            self.free_shared.push(index);
+            // The nodes of the list come back into the view.
+            self.expanded = self.expanded.wrapping_add(length);
+            (first, end)
+        } else {
+            self.shared[index].refs -= 1;
+            self.copy(first, end)
+        };
+        let (taken, mut other) = if last { (end, first) } else { (first, end) };
+        let object = *self.object(taken);
+        self.set_object(node, object);
+        if matches!(object, Object::StrBracketL | Object::StrBracketR) {
+            let twin = self.twin(taken);
+            self.pair(node, twin);
+        }
+        if first == end {
+            other = node;
+        } else if last {
+            let (prev, before) = (self.prev(node), self.prev(end));
+            self.link(prev, first);
+            self.link(before, node);
+        } else {
+            let (next, after) = (self.next(node), self.next(first));
+            self.link(node, after);
+            self.link(end, next);
+        }
+        // The object of `taken` now belongs to `node`.
+        self.set_object(taken, Object::First);
+        self.unlink_prev(taken);
+        self.unlink_next(taken);
+        self.free(taken);
+        other
+    }
+
+    /// Copies the nodes from `first` to `last` into a new list, pairing the brackets.
+    fn copy(&mut self, first: NodeId, last: NodeId) -> (NodeId, NodeId) {
+        let mut l_brackets = Vec::new();
+        let (mut head, mut tail) = (NIL, NIL);
+        let mut cursor = first;
+        loop {
+            let object = *self.object(cursor);
+            let node = self.alloc(object);
+            if tail == NIL {
+                head = node;
+            } else {
+                self.link(tail, node);
+            }
+            tail = node;
+            match object {
+                Object::StrBracketL => l_brackets.push(node),
+                Object::StrBracketR => {
+                    let bracket_l = l_brackets.pop().unwrap();
+                    self.pair(bracket_l, node);
+                }
+                _ => (),
+            }
+            if cursor == last {
+                return (head, tail);
+            }
+            cursor = self.next(cursor);
         }
     }
+
     #[inline(always)]
-    pub fn next_opt(&self) -> Option<Rc<Node>> {
-        self.next.borrow().as_ref().cloned()
+    pub fn object(&self, id: NodeId) -> &Object {
+        &self.nodes[id].object
     }
+
     #[inline(always)]
-    pub fn next(&self) -> Rc<Node> {
-        self.next.borrow().as_ref().unwrap().clone()
+    pub fn next(&self, id: NodeId) -> NodeId {
+        self.nodes[id].next
     }
+
     #[inline(always)]
-    pub fn prev(&self) -> Rc<Node> {
-        self.prev.borrow().as_ref().unwrap().clone()
+    pub fn prev(&self, id: NodeId) -> NodeId {
+        self.nodes[id].prev
     }
+
     #[inline(always)]
-    pub fn twin(&self) -> Rc<Node> {
-        self.twin.borrow().as_ref().unwrap().clone()
+    pub fn twin(&self, id: NodeId) -> NodeId {
+        self.nodes[id].twin
     }
-}
 
-pub struct Chain {
-    pub first: Rc<Node>,
-    pub last: Rc<Node>,
-}
+    pub fn link(&mut self, n1: NodeId, n2: NodeId) {
+        self.nodes[n1].next = n2;
+        self.nodes[n2].prev = n1;
+    }
+
+    pub fn unlink_prev(&mut self, n: NodeId) {
+        self.nodes[n].prev = NIL;
+    }
+
+    pub fn unlink_next(&mut self, n: NodeId) {
+        self.nodes[n].next = NIL;
+    }
 
-pub fn flatten(chain: &Chain) -> Vec<Object> {
-    let mut objects = Vec::<Object>::new();
-    let mut cursor = chain.first.clone();
-    loop {
-        match &cursor.object {
-            Object::First | Object::Last => (),
-            obj => objects.push(obj.clone()),
+    pub fn pair(&mut self, n1: NodeId, n2: NodeId) {
+        self.nodes[n1].twin = n2;
+        self.nodes[n2].twin = n1;
+    }
+
+    /// Allocates an empty list.
+    pub fn chain(&mut self) -> Chain {
+        let first = self.alloc(Object::First);
+        let last = self.alloc(Object::Last);
+        self.link(first, last);
+        Chain { first, last }
+    }
+
+    /// Collects the objects between the borders of `chain`.
+    pub fn flatten(&self, chain: &Chain) -> Vec<Object> {
+        self.objects_between(chain.first, chain.last)
+    }
+
+    /// Collects the objects strictly between `left` and `right`, those of shared subexpressions
+    /// included.
+    pub fn objects_between(&self, left: NodeId, right: NodeId) -> Vec<Object> {
+        let mut objects = Vec::<Object>::new();
+        let mut cursor = self.next(left);
+        while cursor != right {
+            self.collect(cursor, &mut objects);
+            cursor = self.next(cursor);
         }
-        if let Some(next) = cursor.next_opt() {
-            cursor = next;
-        } else {
-            break;
+        objects
+    }
+
+    fn collect(&self, node: NodeId, objects: &mut Vec<Object>) {
+        match *self.object(node) {
+            Object::Shared(index) => {
+                let shared = self.shared[index as usize];
+                let mut cursor = shared.first;
+                loop {
+                    self.collect(cursor, objects);
+                    if cursor == shared.last {
+                        break;
+                    }
+                    cursor = self.next(cursor);
+                }
+            }
+            object => objects.push(object),
         }
     }
-    objects
-}
 
-pub fn link_nodes(n1: &Rc<Node>, n2: &Rc<Node>) {
-    *n1.next.borrow_mut() = Some(n2.clone());
-    *n2.prev.borrow_mut() = Some(n1.clone());
-}
+    /// Builds a view holding `<main arg>`, with the right brackets of its calls.
+    pub fn init_view(&mut self, main: &str, arg: &[Object]) -> Result<(Vec<NodeId>, Chain)> {
+        let mut call = vec![Object::FunBracketL, Object::Symbol(Symbol::intern(main))];
+        call.extend_from_slice(arg);
+        call.push(Object::FunBracketR);
+        self.init_expression(&call)
+            .map_err(|_| Error::MalformedView(render(arg)))
+    }
 
-pub fn unlink_prev(n: &Rc<Node>) {
-    *n.prev.borrow_mut() = None;
-}
+    /// Builds a view holding `expression`, with the right brackets of its calls.
+    pub fn init_expression(&mut self, expression: &[Object]) -> Result<(Vec<NodeId>, Chain)> {
+        let chain = self.chain();
+        match self.insert_objects(chain.first, expression) {
+            Some((border, dots)) => {
+                self.link(border, chain.last);
+                Ok((dots, chain))
+            }
+            None => {
+                self.unlink_prev(chain.last);
+                self.free(chain.last);
+                self.free(chain.first);
+                Err(Error::MalformedView(render(expression)))
+            }
+        }
+    }
 
-pub fn unlink_next(n: &Rc<Node>) {
-    *n.next.borrow_mut() = None;
-}
+    /// Replaces the call between `fun_br_l` and `fun_br_r` with `objects`.
+    /// Returns the right brackets of the calls in `objects`, the one to evaluate first being last.
+    pub fn replace_call(
+        &mut self,
+        fun_br_l: NodeId,
+        fun_br_r: NodeId,
+        objects: &[Object],
+    ) -> Vec<NodeId> {
+        let prev = self.prev(fun_br_l);
+        let next = self.next(fun_br_r);
+        self.unlink_prev(fun_br_l);
+        self.unlink_next(fun_br_r);
+        self.free(fun_br_l);
+        let (border, dots) = self
+            .insert_objects(prev, objects)
+            .expect("unbalanced brackets");
+        self.link(border, next);
+        dots
+    }
 
-fn unpair(n: &Rc<Node>) {
-    *n.twin.borrow_mut() = None;
+    /// Links new nodes holding `objects` after `border` and pairs their brackets.
+    /// Returns the last new node and the right brackets of the calls, the one to evaluate first
+    /// being last, or `None` if the brackets are unbalanced or an object is shared.
+    fn insert_objects(
+        &mut self,
+        border: NodeId,
+        objects: &[Object],
+    ) -> Option<(NodeId, Vec<NodeId>)> {
+        let mut dots = Vec::<NodeId>::new();
+        let mut l_brackets = Vec::<NodeId>::new();
+        let mut border = border;
+        for object in objects {
+            if let Object::Shared(_) = object {
+                return None;
+            }
+            let node = self.alloc(*object);
+            self.link(border, node);
+            border = node;
+            match object {
+                Object::StrBracketL | Object::FunBracketL => l_brackets.push(node),
+                Object::StrBracketR | Object::FunBracketR => match l_brackets.pop() {
+                    Some(bracket_l) if is_pair(self.object(bracket_l), object) => {
+                        self.pair(bracket_l, node);
+                        if *object == Object::FunBracketR {
+                            dots.push(node);
+                        }
+                    }
+                    _ => return None,
+                },
+                _ => (),
+            }
+        }
+        if !l_brackets.is_empty() {
+            return None;
+        }
+        dots.reverse();
+        Some((border, dots))
+    }
 }
 
-pub fn pair_nodes(n1: &Rc<Node>, n2: &Rc<Node>) {
-    *n1.twin.borrow_mut() = Some(n2.clone());
-    *n2.twin.borrow_mut() = Some(n1.clone());
+/// Renders objects in Refal syntax, runs of characters as single quoted literals.
+pub fn render(objects: &[Object]) -> String {
+    let objects: Vec<_> = objects
+        .iter()
+        .copied()
+        .filter_map(Object::to_data)
+        .collect();
+    printer::render(&objects)
 }
 
-pub fn free(start: Rc<Node>) {
-    let mut cursor = start;
-    loop {
-        let next = cursor.next_opt();
-        unlink_prev(&cursor);
-        unlink_next(&cursor);
-        unpair(&cursor);
-        match next {
-            None => break,
-            Some(n) => cursor = n,
+/// Renders objects the way the output built-ins print them: characters as they are,
+/// brackets, and words and numbers separated by spaces.
+pub fn display(objects: &[Object]) -> String {
+    let is_word = |object: &Object| matches!(object, Object::Symbol(_) | Object::Number(_));
+    let mut text = String::new();
+    let mut prev: Option<&Object> = None;
+    for object in objects {
+        let image = match object {
+            Object::Symbol(s) => s.to_string(),
+            Object::Char(c) => c.to_string(),
+            Object::Number(n) => n.to_string(),
+            Object::StrBracketL => String::from("("),
+            Object::StrBracketR => String::from(")"),
+            Object::FunBracketL => String::from("<"),
+            Object::FunBracketR => String::from(">"),
+            Object::First | Object::Last | Object::Shared(_) => continue,
+        };
+        if let Some(prev) = prev.filter(|_| !text.ends_with(' ')) {
+            if is_word(prev) && !matches!(object, Object::StrBracketR | Object::FunBracketR)
+                || is_word(object) && !matches!(prev, Object::StrBracketL | Object::FunBracketL)
+            {
+                text.push(' ');
+            }
         }
+        text.push_str(&image);
+        prev = Some(object);
     }
+    text
 }
 
-pub fn init_view(main: &str) -> (Vec<Rc<Node>>, Chain) {
-    let first = Rc::new(Node::new(Object::First));
-    let fun_br_l = Rc::new(Node::new(Object::FunBracketL));
-    let fun = Rc::new(Node::new(Object::Symbol(String::from(main))));
-    let fun_br_r = Rc::new(Node::new(Object::FunBracketR));
-    let last = Rc::new(Node::new(Object::Last));
-
-    link_nodes(&first, &fun_br_l);
-    link_nodes(&fun_br_l, &fun);
-    link_nodes(&fun, &fun_br_r);
-    link_nodes(&fun_br_r, &last);
-    pair_nodes(&fun_br_l, &fun_br_r);
-
-    (vec![fun_br_r], Chain { first, last })
+fn is_pair(left: &Object, right: &Object) -> bool {
+    matches!(
+        (left, right),
+        (Object::StrBracketL, Object::StrBracketR) | (Object::FunBracketL, Object::FunBracketR)
+    )
 }
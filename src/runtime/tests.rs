@@ -0,0 +1,100 @@
+use crate::runtime::Object::*;
+use crate::runtime::{live_nodes, Arena, Object};
+use crate::symbol;
+
+#[cfg(test)]
+fn symbol(s: &str) -> Object {
+    Symbol(symbol::Symbol::intern(s))
+}
+
+#[test]
+fn test_init_expression() {
+    let mut arena = Arena::new();
+    let expression = vec![
+        FunBracketL,
+        symbol("F"),
+        StrBracketL,
+        FunBracketL,
+        symbol("G"),
+        FunBracketR,
+        StrBracketR,
+        FunBracketR,
+    ];
+    let (dots, chain) = arena.init_expression(&expression).unwrap();
+    assert_eq!(arena.flatten(&chain), expression);
+    assert_eq!(dots.len(), 2);
+    assert_eq!(
+        arena.objects_between(arena.twin(dots[1]), dots[1]),
+        vec![symbol("G")]
+    );
+    assert_eq!(arena.live(), 10);
+    arena.free(chain.first);
+    assert_eq!(arena.live(), 0);
+}
+
+#[test]
+fn test_init_expression_unbalanced() {
+    let before = live_nodes();
+    let mut arena = Arena::new();
+    assert!(arena.init_expression(&[StrBracketL, FunBracketR]).is_err());
+    assert!(arena.init_expression(&[FunBracketL]).is_err());
+    assert_eq!(arena.live(), 0);
+    assert_eq!(live_nodes(), before);
+}
+
+#[test]
+fn test_replace_call_reuses_nodes() {
+    let mut arena = Arena::new();
+    let (mut dots, chain) = arena.init_view("F", &[Number(1)]).unwrap();
+    let fun_br_r = dots.pop().unwrap();
+    let fun_br_l = arena.twin(fun_br_r);
+    let dots = arena.replace_call(fun_br_l, fun_br_r, &[Char('a'), Char('b')]);
+    assert!(dots.is_empty());
+    assert_eq!(arena.flatten(&chain), vec![Char('a'), Char('b')]);
+    assert_eq!(arena.live(), 4);
+    // The four nodes of the call were freed and two of them taken back.
+    let node = arena.alloc(Char('c'));
+    assert!(node < 6);
+    arena.free(node);
+    arena.free(chain.first);
+    assert_eq!(arena.live(), 0);
+}
+
+#[test]
+fn test_share() {
+    let mut arena = Arena::new();
+    let expression = vec![
+        symbol("A"),
+        StrBracketL,
+        symbol("B"),
+        StrBracketR,
+        symbol("C"),
+    ];
+    let (_, chain) = arena.init_expression(&expression).unwrap();
+    let start = arena.next(arena.next(chain.first));
+    let end = arena.prev(chain.last);
+    let shared = arena.share(start, end);
+    assert_eq!(arena.live(), 8);
+    assert_eq!(arena.expanded(), 7);
+    let copy = arena.alloc(*arena.object(shared));
+    arena.link(shared, copy);
+    arena.link(copy, chain.last);
+    assert_eq!(arena.expanded(), 11);
+    let twice = [&expression[..], &expression[1..]].concat();
+    assert_eq!(arena.flatten(&chain), twice);
+
+    // The first expansion copies the subexpression, the last one takes it back.
+    assert_eq!(arena.unshare(shared, false), arena.prev(copy));
+    assert_eq!(*arena.object(shared), StrBracketL);
+    assert_eq!(arena.twin(shared), arena.next(arena.next(shared)));
+    assert_eq!(arena.live(), 12);
+    assert_eq!(arena.expanded(), 11);
+    assert_eq!(arena.unshare(copy, true), start);
+    assert_eq!(*arena.object(copy), symbol("C"));
+    assert_eq!(arena.flatten(&chain), twice);
+    assert_eq!(arena.live(), 11);
+    assert_eq!(arena.expanded(), 11);
+    arena.free(chain.first);
+    assert_eq!(arena.live(), 0);
+    assert_eq!(arena.expanded(), 0);
+}
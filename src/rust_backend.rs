@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests;
+
+use crate::compiler::{arm_starts, Symbols};
+use crate::data::Command;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Generates the source of a Rust library implementing the functions of a definition table,
+/// each as the calls to `vm::native::Machine` its commands make. The library exports
+/// `program()`, the functions linked with the built-ins, to evaluate with `vm::eval_call`
+/// and the like; it depends on this crate for the view and the evaluation loop.
+pub fn generate(defs: &HashMap<String, Vec<Command>>) -> String {
+    let mut names: Vec<_> = defs.keys().collect();
+    names.sort();
+    let mut symbols = Symbols::default();
+    let functions: Vec<_> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| generate_function(index, name, &defs[*name], &mut symbols))
+        .collect();
+
+    let mut text = String::from(
+        "//! Generated by `refal --emit-rust`.\n\n\
+         use refal::program::Program;\n\
+         use refal::symbol::Symbol;\n\
+         use refal::vm::native::Machine;\n\
+         use std::sync::OnceLock;\n\n\
+         /// The functions of the program linked with the built-ins.\n\
+         pub fn program() -> Program {\n    Program::native(&[\n",
+    );
+    for (index, name) in names.iter().enumerate() {
+        writeln!(text, "        ({:?}, f{}),", name, index).unwrap();
+    }
+    text.push_str("    ])\n}\n\n");
+    text.push_str(
+        "/// The symbols the functions match and insert, interned once.\n\
+         fn symbols() -> &'static [Symbol] {\n    \
+         static SYMBOLS: OnceLock<Vec<Symbol>> = OnceLock::new();\n    \
+         SYMBOLS.get_or_init(|| {\n        [\n",
+    );
+    for name in &symbols.names {
+        writeln!(text, "            {:?},", name).unwrap();
+    }
+    text.push_str(
+        "        ]\n        .iter()\n        .map(|name| Symbol::intern(name))\n        \
+         .collect()\n    })\n}\n",
+    );
+    for function in functions {
+        text.push('\n');
+        text.push_str(&function);
+    }
+    text
+}
+
+/// Where the crate `generate` makes finds this one.
+pub enum Dependency {
+    /// The directory of its sources.
+    Path(String),
+    /// A version published to crates.io.
+    Version(String),
+}
+
+/// The manifest of the crate of the library `generate` makes, named `name`,
+/// depending on this crate as `refal` says.
+pub fn manifest(name: &str, refal: &Dependency) -> String {
+    let refal = match refal {
+        Dependency::Path(path) => format!("{{ path = {:?} }}", path),
+        Dependency::Version(version) => format!("{:?}", version),
+    };
+    format!(
+        "[package]\nname = {:?}\nversion = \"0.1.0\"\nedition = \"2018\"\n\n\
+         [dependencies]\nrefal = {}\n",
+        name, refal
+    )
+}
+
+/// Generates function `f{index}` executing `commands`. The commands go in the arms of
+/// a `match` on the address to go on at, one arm starting at each address that matching
+/// may come back to: the alternatives, e-variables to lengthen, and conditions evaluated.
+/// Within an arm, a failed match goes on at the address the machine gives.
+fn generate_function(
+    index: usize,
+    name: &str,
+    commands: &[Command],
+    symbols: &mut Symbols,
+) -> String {
+    let starts = arm_starts(commands);
+    let mut uses_symbols = false;
+    let mut arms = String::new();
+    for &start in &starts {
+        writeln!(arms, "            {} => {{", start).unwrap();
+        for (address, command) in commands.iter().enumerate().skip(start) {
+            if address > start && starts.contains(&address) {
+                if !matches!(commands[address - 1], Command::MatchEVarPrepare) {
+                    writeln!(arms, "                m.goto({});", address).unwrap();
+                }
+                break;
+            }
+            let mut symbol = |s: &Symbol| {
+                uses_symbols = true;
+                format!("s[{}]", symbols.index(s.as_str()))
+            };
+            let (call, fallible) = match command {
+                Command::MatchStart => break,
+                Command::MatchEmpty => (String::from("match_empty()"), true),
+                Command::MatchStrBracketL => (String::from("match_str_bracket_l()"), true),
+                Command::MatchStrBracketR => (String::from("match_str_bracket_r()"), true),
+                Command::MatchSymbolL(s) => (format!("match_symbol_l({})", symbol(s)), true),
+                Command::MatchSymbolR(s) => (format!("match_symbol_r({})", symbol(s)), true),
+                Command::MatchNumberL(n) => (format!("match_number_l({})", n), true),
+                Command::MatchNumberR(n) => (format!("match_number_r({})", n), true),
+                Command::MatchCharL(c) => (format!("match_char_l({:?})", c), true),
+                Command::MatchCharR(c) => (format!("match_char_r({:?})", c), true),
+                Command::MatchSVarL => (String::from("match_s_var_l()"), true),
+                Command::MatchSVarR => (String::from("match_s_var_r()"), true),
+                Command::MatchSVarLProj(n) => (format!("match_s_var_l_proj({})", n), true),
+                Command::MatchSVarRProj(n) => (format!("match_s_var_r_proj({})", n), true),
+                Command::MatchTVarL => (String::from("match_t_var_l()"), true),
+                Command::MatchTVarR => (String::from("match_t_var_r()"), true),
+                Command::MatchEVarPrepare => (format!("prepare_lengthen({})", address), false),
+                Command::MatchEVar => (String::from("match_e_var()"), false),
+                Command::MatchEVarLengthen => (format!("lengthen({})", address), true),
+                Command::MatchEVarLProj(n) => (format!("match_e_var_l_proj({})", n), true),
+                Command::MatchEVarRProj(n) => (format!("match_e_var_r_proj({})", n), true),
+                Command::MatchMoveBorderL(n) => (format!("match_move_border_l({})", n), false),
+                Command::MatchMoveBorderR(n) => (format!("match_move_border_r({})", n), false),
+                Command::SetupTransition(n) => (format!("setup_transition({})", n), false),
+                Command::SwitchSymbolL(table) => {
+                    let table: Vec<_> = table
+                        .iter()
+                        .map(|(s, target)| format!("({}, {})", symbol(s), target))
+                        .collect();
+                    let call = format!("switch_symbol_l({}, &[{}])", address, table.join(", "));
+                    writeln!(arms, "                if m.{} {{", call).unwrap();
+                    arms.push_str("                    continue;\n                }\n");
+                    continue;
+                }
+                Command::ConstrainLengthen(n) => (format!("constrain_lengthen({})", n), false),
+                Command::ConditionStart => (String::from("condition_start()"), false),
+                Command::ConditionEvaluate => {
+                    (format!("condition_evaluate({})", address + 1), false)
+                }
+                Command::RewriteStart => (format!("rewrite_start({})", address), false),
+                Command::RewriteFinalize => (String::from("rewrite_finalize()"), false),
+                Command::InsertSymbol(s) => (format!("insert_symbol({})", symbol(s)), false),
+                Command::InsertNumber(n) => (format!("insert_number({})", n), false),
+                Command::InsertChar(c) => (format!("insert_char({:?})", c), false),
+                Command::InsertStrBracketL => (String::from("insert_str_bracket_l()"), false),
+                Command::InsertStrBracketR => (String::from("insert_str_bracket_r()"), false),
+                Command::InsertFunBracketL => (String::from("insert_fun_bracket_l()"), false),
+                Command::InsertFunBracketR => (String::from("insert_fun_bracket_r()"), false),
+                Command::CopySymbol(n) => (format!("copy_symbol({})", n), false),
+                Command::CopyExpr(n) => (format!("copy_expr({})", n), false),
+                Command::TransplantObject(n) => (format!("transplant_object({})", n), false),
+                Command::TransplantExpr(n) => (format!("transplant_expr({})", n), false),
+            };
+            if fallible {
+                writeln!(arms, "                if !m.{} {{", call).unwrap();
+                arms.push_str("                    continue;\n                }\n");
+            } else {
+                writeln!(arms, "                m.{};", call).unwrap();
+            }
+            // The call is rewritten or suspended, the machine goes on with another.
+            if matches!(
+                command,
+                Command::RewriteFinalize | Command::ConditionEvaluate
+            ) {
+                arms.push_str("                return;\n");
+                break;
+            }
+        }
+        arms.push_str("            }\n");
+    }
+
+    let mut text = format!("/// `{}`\nfn f{}(m: &mut Machine) {{\n", name, index);
+    if uses_symbols {
+        text.push_str("    let s = symbols();\n");
+    }
+    text.push_str("    while let Some(address) = m.address() {\n        match address {\n");
+    text.push_str(&arms);
+    text.push_str("            _ => unreachable!(),\n        }\n    }\n}\n");
+    text
+}
@@ -0,0 +1,204 @@
+use crate::compiler::{arm_starts, compile};
+use crate::data::Command;
+use crate::program::Program;
+use crate::rust_backend::{generate, manifest, Dependency};
+use crate::vm::tests::TEST_PROGRAM;
+use crate::vm::{eval_main, render};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command as Process;
+use std::{env, fs};
+
+#[test]
+fn test_generate() {
+    let defs = compile("$MODULE C; F { A = B; $e.1 = $e.1 $e.1; } G { = '\\n'; }").unwrap();
+    assert_eq!(
+        generate(&defs),
+        r#"//! Generated by `refal --emit-rust`.
+
+use refal::program::Program;
+use refal::symbol::Symbol;
+use refal::vm::native::Machine;
+use std::sync::OnceLock;
+
+/// The functions of the program linked with the built-ins.
+pub fn program() -> Program {
+    Program::native(&[
+        ("C.F", f0),
+        ("C.G", f1),
+    ])
+}
+
+/// The symbols the functions match and insert, interned once.
+fn symbols() -> &'static [Symbol] {
+    static SYMBOLS: OnceLock<Vec<Symbol>> = OnceLock::new();
+    SYMBOLS.get_or_init(|| {
+        [
+            "A",
+            "B",
+        ]
+        .iter()
+        .map(|name| Symbol::intern(name))
+        .collect()
+    })
+}
+
+/// `C.F`
+fn f0(m: &mut Machine) {
+    let s = symbols();
+    while let Some(address) = m.address() {
+        match address {
+            0 => {
+                m.setup_transition(7);
+                if !m.match_symbol_l(s[0]) {
+                    continue;
+                }
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(3);
+                m.insert_symbol(s[1]);
+                m.rewrite_finalize();
+                return;
+            }
+            7 => {
+                m.match_e_var();
+                m.rewrite_start(8);
+                m.transplant_expr(4);
+                m.copy_expr(4);
+                m.rewrite_finalize();
+                return;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// `C.G`
+fn f1(m: &mut Machine) {
+    while let Some(address) = m.address() {
+        match address {
+            0 => {
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(1);
+                m.insert_char('\n');
+                m.rewrite_finalize();
+                return;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+"#
+    );
+}
+
+#[test]
+fn test_arm_starts() {
+    let defs = compile(
+        "$MODULE C;
+Find {
+  $e.1 'b' $s.2 $e.3, $s.2 : 'd' = Found $e.1;
+  $e.1 = None;
+}",
+    )
+    .unwrap();
+    let commands = &defs["C.Find"];
+    assert_eq!(commands[1], Command::MatchEVarPrepare);
+    assert_eq!(commands[8], Command::ConditionEvaluate);
+    assert_eq!(commands[16], Command::MatchEVar);
+    assert_eq!(
+        arm_starts(commands),
+        BTreeSet::from([0, 2, 3, 9, 16]),
+        "the start, lengthening, after lengthening, after the condition and the second sentence"
+    );
+    // Lengthening sets the address to go on at, the arm before it ends without a jump.
+    assert!(generate(&defs).contains(
+        "                m.prepare_lengthen(1);
+            }
+            2 => {
+                if !m.lengthen(2) {
+                    continue;
+                }
+                m.goto(3);
+            }"
+    ));
+}
+
+/// The binary of the crate built by the tests, printing the results of the goals it is given.
+const MAIN: &str = r#"fn main() {
+    let program = generated::program();
+    for goal in std::env::args().skip(1) {
+        match refal::vm::eval_main(&program, &goal) {
+            Ok(result) => println!("{}", refal::vm::render(&result)),
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+"#;
+
+/// Builds the crate generated from `source` in `dir` with Cargo, returning its binary,
+/// or returns `None` if Cargo cannot be run or cannot find the dependencies offline.
+fn build(dir: &Path, source: &str) -> Option<PathBuf> {
+    let refal = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dependency = Dependency::Path(refal.display().to_string());
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest("generated", &dependency)).unwrap();
+    fs::write(dir.join("src/lib.rs"), generate(&compile(source).unwrap())).unwrap();
+    fs::write(dir.join("src/main.rs"), MAIN).unwrap();
+    // The dependencies keep the versions this crate was built with.
+    if refal.join("Cargo.lock").is_file() {
+        fs::copy(refal.join("Cargo.lock"), dir.join("Cargo.lock")).unwrap();
+    }
+    let cargo = |command: &str| {
+        Process::new(env::var("CARGO").unwrap_or_else(|_| String::from("cargo")))
+            .args([command, "--offline", "--quiet", "--manifest-path"])
+            .arg(dir.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", dir.join("target"))
+            .status()
+    };
+    match cargo("fetch") {
+        Ok(status) if status.success() => (),
+        Ok(_) => {
+            eprintln!("skipping, the dependencies are not available offline");
+            return None;
+        }
+        Err(error) => {
+            eprintln!("skipping, no Cargo: {}", error);
+            return None;
+        }
+    }
+    let status = cargo("build").unwrap();
+    assert!(status.success(), "building the generated crate failed");
+    Some(dir.join("target").join("debug").join("generated"))
+}
+
+#[test]
+#[ignore = "builds the generated crate with Cargo, run with --ignored"]
+fn test_test_program() {
+    let dir = env::temp_dir().join(format!("refal-rust-test-{}", std::process::id()));
+    let executable = match build(&dir, TEST_PROGRAM) {
+        Some(executable) => executable,
+        None => return,
+    };
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let goals: Vec<_> = program
+        .functions()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| name.starts_with("Test.Test"))
+        .collect();
+    assert!(goals.len() > 100);
+    let expected: String = goals
+        .iter()
+        .map(|goal| match eval_main(&program, goal) {
+            Ok(result) => format!("{}\n", render(&result)),
+            Err(error) => format!("{}\n", error),
+        })
+        .collect();
+    let output = Process::new(executable).args(&goals).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// An identifier interned in the symbol table of the process: symbols with the same name
+/// have the same id, so matching compares ids and copying allocates nothing.
+/// Characters need no table, a `char` is its own id.
+///
+/// Names are never freed, which lets them be borrowed for the rest of the program: a process
+/// compiling ever new programs, such as a service evaluating the programs of its users,
+/// grows the table with each name it has not seen.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The number of names in the first chunk of the table, each chunk having twice as many
+/// as the one before.
+const FIRST_CHUNK: usize = 64;
+/// Enough chunks for every `u32` id.
+const CHUNKS: usize = 27;
+
+/// The names of the symbols, indexed by id. A chunk never moves once allocated and a name is
+/// set once, before its symbol is handed out, so names are read without locking.
+static NAMES: [OnceLock<Box<[OnceLock<&'static str>]>>; CHUNKS] =
+    [const { OnceLock::new() }; CHUNKS];
+
+/// The ids of the names, locked to intern a symbol.
+fn ids() -> &'static Mutex<HashMap<&'static str, Symbol>> {
+    static IDS: OnceLock<Mutex<HashMap<&'static str, Symbol>>> = OnceLock::new();
+    IDS.get_or_init(Mutex::default)
+}
+
+/// The chunk the name of symbol `id` is in and its offset there.
+fn slot(id: usize) -> (usize, usize) {
+    let chunk = (id / FIRST_CHUNK + 1).ilog2() as usize;
+    (chunk, id - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+impl Symbol {
+    /// The symbol named `name`, added to the table if it is new.
+    pub fn intern(name: &str) -> Symbol {
+        let mut ids = ids().lock().unwrap();
+        if let Some(&symbol) = ids.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(ids.len() as u32);
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let (chunk, offset) = slot(symbol.index());
+        let names = NAMES[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| OnceLock::new()).collect());
+        names[offset].set(name).unwrap();
+        ids.insert(name, symbol);
+        symbol
+    }
+
+    /// The id of the symbol, small enough to index tables by.
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let (chunk, offset) = slot(self.index());
+        NAMES[chunk]
+            .get()
+            .and_then(|names| names[offset].get())
+            .unwrap()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::symbol::{slot, Symbol, FIRST_CHUNK};
+use std::thread;
+
+#[test]
+fn test_intern() {
+    let a = Symbol::intern("Intern.A");
+    assert_eq!(Symbol::intern("Intern.A"), a);
+    assert_ne!(Symbol::intern("Intern.B"), a);
+    assert_eq!(a.as_str(), "Intern.A");
+    assert_eq!(Symbol::intern("Überall").to_string(), "Überall");
+    assert_eq!(format!("{:?}", a), "\"Intern.A\"");
+}
+
+#[test]
+fn test_slot() {
+    assert_eq!(slot(0), (0, 0));
+    assert_eq!(slot(FIRST_CHUNK - 1), (0, FIRST_CHUNK - 1));
+    assert_eq!(slot(FIRST_CHUNK), (1, 0));
+    assert_eq!(slot(3 * FIRST_CHUNK - 1), (1, 2 * FIRST_CHUNK - 1));
+    assert_eq!(slot(3 * FIRST_CHUNK), (2, 0));
+    let (chunk, offset) = slot(u32::MAX as usize);
+    assert_eq!(chunk, 26);
+    assert!(offset < FIRST_CHUNK << chunk);
+}
+
+#[test]
+fn test_intern_concurrently() {
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            thread::spawn(move || {
+                for i in 0..500 {
+                    let name = format!("Concurrent.{}", (i * 7 + t) % 600);
+                    assert_eq!(Symbol::intern(&name).as_str(), name);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(
+        Symbol::intern("Concurrent.599"),
+        Symbol::intern("Concurrent.599")
+    );
+}
@@ -1,65 +1,271 @@
+pub mod native;
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
 
-use crate::data::Command;
+use crate::coverage::Coverage;
+use crate::data::{Command, Error, Limit, Result};
+use crate::host::{Host, StdHost};
+use crate::profile::Profile;
+use crate::program::{Callee, Program};
 use crate::runtime::*;
-use std::collections::HashMap;
-use std::ptr;
-use std::rc::Rc;
+pub use crate::runtime::{render, Object};
+use crate::symbol::Symbol;
+use native::{Machine, NativeFn};
+use std::mem;
 
 struct Jump {
-    border_l: Rc<Node>,
-    border_r: Rc<Node>,
+    border_l: NodeId,
+    border_r: NodeId,
     projection_index: usize,
     command_index: usize,
 }
 
+/// A sentence suspended while the result of one of its conditions is evaluated.
+struct Frame<'a> {
+    commands: &'a [Command],
+    native: Option<NativeFn>,
+    command_index: usize,
+    projections: Vec<NodeId>,
+    jumps: Vec<Jump>,
+    conditions: Vec<Condition>,
+    /// The number of pending calls outside of the result.
+    dots_base: usize,
+    result: Chain,
+}
+
+/// The result of a condition, matched against its pattern.
+struct Condition {
+    result: Chain,
+    /// The number of jumps when the matching started: backtracking past them discards the result.
+    jumps: usize,
+}
+
+/// Inserts new nodes after `border`, pairing their brackets.
+struct Builder {
+    border: NodeId,
+    l_brackets: Vec<NodeId>,
+    /// The right brackets of the inserted calls.
+    dots: Vec<NodeId>,
+}
+
+impl Builder {
+    fn new(border: NodeId) -> Self {
+        Builder {
+            border,
+            l_brackets: Vec::new(),
+            dots: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, arena: &mut Arena, object: Object) {
+        let node = arena.alloc(object);
+        let next = arena.next(self.border);
+        arena.link(self.border, node);
+        arena.link(node, next);
+        match arena.object(node) {
+            Object::StrBracketL | Object::FunBracketL => self.l_brackets.push(node),
+            Object::StrBracketR | Object::FunBracketR => {
+                let bracket_l = self.l_brackets.pop().unwrap();
+                arena.pair(bracket_l, node);
+                if *arena.object(node) == Object::FunBracketR {
+                    self.dots.push(node);
+                }
+            }
+            _ => (),
+        }
+        self.border = node;
+    }
+}
+
+/// A rewrite being built: the objects inserted in front of the call, and the variables to move
+/// among them once the call is replaced.
+struct Rewrite {
+    builder: Builder,
+    transplants: Vec<(NodeId, NodeId, NodeId)>,
+    /// The function of the call, read beforehand when recording coverage.
+    function: Option<Symbol>,
+    address: usize,
+}
+
+/// Bounds on the resources an evaluation may take; `None` means unbounded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// The number of steps, i.e. of calls evaluated.
+    pub steps: Option<u64>,
+    /// The number of nodes of the view, including the results of conditions being matched,
+    /// a shared subexpression counting as many nodes as it has objects.
+    pub nodes: Option<usize>,
+    /// The number of calls waiting to be evaluated.
+    pub calls: Option<usize>,
+}
+
+/// Receives each call with the number of its step before the call is evaluated.
+pub type Trace<'a> = &'a mut dyn FnMut(u64, &[Object]);
+
+/// Evaluation starts by looking for the call to evaluate first.
+static START: [Command; 1] = [Command::MatchStart];
+
 struct VM<'a> {
-    defs: &'a HashMap<String, Vec<Command>>,
-    commands: &'a Vec<Command>,
+    program: &'a Program,
+    commands: &'a [Command],
+    /// The function being evaluated when it is native code, which then executes the commands.
+    native: Option<NativeFn>,
     command_index: usize,
-    projections: Vec<Rc<Node>>,
+    arena: Arena,
+    projections: Vec<NodeId>,
     jumps: Vec<Jump>,
-    border_l: Rc<Node>,
-    border_r: Rc<Node>,
-    dots: Vec<Rc<Node>>,
+    border_l: NodeId,
+    border_r: NodeId,
+    dots: Vec<NodeId>,
+    frames: Vec<Frame<'a>>,
+    conditions: Vec<Condition>,
+    host: &'a mut dyn Host,
+    limits: Limits,
+    trace: Option<Trace<'a>>,
+    profile: Option<&'a mut Profile>,
+    coverage: Option<&'a mut Coverage>,
+    steps: u64,
     done: bool,
+    /// Whether the last command matching failed.
+    failed: bool,
+    error: Option<Error>,
 }
 
-pub fn eval_main(defs: &HashMap<String, Vec<Command>>, main: &str) -> Vec<Object> {
-    let (dots, chain) = init_view(main);
-    eval(defs, dots);
-    flatten(&chain)
+pub fn eval_main(program: &Program, main: &str) -> Result<Vec<Object>> {
+    eval_call(program, main, &[])
 }
 
-fn eval(defs: &HashMap<String, Vec<Command>>, dots: Vec<Rc<Node>>) {
-    let mut vm = init_vm(defs, dots);
-    while !vm.done {
-        let cmd = &vm.commands[vm.command_index];
-        vm.command_index += 1;
-        execute_cmd(&mut vm, cmd);
-    }
+/// Evaluates `<fun arg>`, where `arg` may itself contain function calls.
+pub fn eval_call(program: &Program, fun: &str, arg: &[Object]) -> Result<Vec<Object>> {
+    eval_call_with(program, fun, arg, &mut StdHost::new())
 }
 
-fn init_vm(defs: &HashMap<String, Vec<Command>>, mut dots: Vec<Rc<Node>>) -> VM {
-    let fun_br_r = dots.pop().unwrap();
-    let fun_br_l = fun_br_r.twin();
-    let fun = fun_br_l.next();
-    let fun_br_l_prev = fun_br_l.prev();
+/// Evaluates `<fun arg>` with the I/O built-ins talking to `host`.
+pub fn eval_call_with(
+    program: &Program,
+    fun: &str,
+    arg: &[Object],
+    host: &mut dyn Host,
+) -> Result<Vec<Object>> {
+    eval_call_limited(program, fun, arg, host, Limits::default())
+}
+
+/// Evaluates `<fun arg>` with the I/O built-ins talking to `host`,
+/// failing with `Error::LimitExceeded` before a step exceeding the limit on steps or calls,
+/// or after a step leaving more nodes in the view than allowed.
+pub fn eval_call_limited(
+    program: &Program,
+    fun: &str,
+    arg: &[Object],
+    host: &mut dyn Host,
+    limits: Limits,
+) -> Result<Vec<Object>> {
+    let mut arena = Arena::new();
+    let (dots, chain) = arena.init_view(fun, arg)?;
+    let mut vm = init_vm(program, arena, dots, chain, host, limits);
+    let result = eval(&mut vm).map(|_| vm.arena.flatten(&chain));
+    vm.arena.free(chain.first);
+    result
+}
 
-    let fun_sym = fun.object.symbol().unwrap();
-    let commands = defs.get(fun_sym).expect(fun_sym);
+/// Evaluates `expression`, which may contain any number of calls, within `limits`,
+/// passing the calls to `trace`, recording where the evaluation goes in `profile`
+/// and the sentences rewriting calls in `coverage`.
+pub fn eval_expression<'a>(
+    program: &'a Program,
+    expression: &[Object],
+    host: &'a mut dyn Host,
+    limits: Limits,
+    trace: Option<Trace<'a>>,
+    profile: Option<&'a mut Profile>,
+    coverage: Option<&'a mut Coverage>,
+) -> Result<Vec<Object>> {
+    let mut arena = Arena::new();
+    let (dots, chain) = arena.init_expression(expression)?;
+    let mut vm = init_vm(program, arena, dots, chain, host, limits);
+    vm.trace = trace;
+    vm.profile = profile;
+    vm.coverage = coverage;
+    let result = eval(&mut vm).map(|_| vm.arena.flatten(&chain));
+    vm.arena.free(chain.first);
+    result
+}
+
+fn eval(vm: &mut VM) -> Result<()> {
+    while !vm.done {
+        match vm.native {
+            Some(function) => function(&mut Machine::new(vm)),
+            None => {
+                let cmd = &vm.commands[vm.command_index];
+                vm.command_index += 1;
+                execute_cmd(vm, cmd);
+            }
+        }
+    }
+    vm.free_conditions();
+    if let Some(profile) = &mut vm.profile {
+        profile.finish();
+    }
+    match vm.error.take() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
 
+fn init_vm<'a>(
+    program: &'a Program,
+    arena: Arena,
+    dots: Vec<NodeId>,
+    view: Chain,
+    host: &'a mut dyn Host,
+    limits: Limits,
+) -> VM<'a> {
     VM {
         command_index: 0,
-        projections: vec![fun_br_l_prev, fun.clone(), fun_br_r.clone()],
+        arena,
+        projections: Vec::new(),
         jumps: Vec::new(),
-        border_l: fun,
-        border_r: fun_br_r,
+        border_l: view.first,
+        border_r: view.first,
         dots,
-        commands,
+        frames: Vec::new(),
+        conditions: Vec::new(),
+        host,
+        limits,
+        trace: None,
+        profile: None,
+        coverage: None,
+        steps: 0,
+        commands: &START,
+        native: None,
         done: false,
-        defs,
+        failed: false,
+        error: None,
+        program,
+    }
+}
+
+/// Finds the definition of the function called by the brackets `fun_br_l` and `fun_br_r`.
+fn lookup<'a>(
+    program: &'a Program,
+    arena: &Arena,
+    fun_br_l: NodeId,
+    fun_br_r: NodeId,
+) -> Result<&'a Callee> {
+    let fun = arena.next(fun_br_l);
+    match arena.object(fun).symbol() {
+        Some(fun_name) if fun != fun_br_r => {
+            program
+                .lookup(fun_name)
+                .ok_or_else(|| Error::UndefinedFunction {
+                    function: fun_name.to_string(),
+                    argument: render(&arena.objects_between(fun, fun_br_r)),
+                })
+        }
+        _ => {
+            let call = arena.objects_between(arena.prev(fun_br_l), arena.next(fun_br_r));
+            Err(Error::MalformedView(render(&call)))
+        }
     }
 }
 
@@ -69,8 +275,12 @@ fn execute_cmd(vm: &mut VM, cmd: &Command) {
         Command::MatchEmpty => vm.match_empty(),
         Command::MatchStrBracketL => vm.match_str_bracket_l(),
         Command::MatchStrBracketR => vm.match_str_bracket_r(),
-        Command::MatchSymbolL(symbol) => vm.match_symbol_l(symbol),
-        Command::MatchSymbolR(symbol) => vm.match_symbol_r(symbol),
+        Command::MatchSymbolL(symbol) => vm.match_symbol_l(*symbol),
+        Command::MatchSymbolR(symbol) => vm.match_symbol_r(*symbol),
+        Command::MatchNumberL(n) => vm.match_number_l(*n),
+        Command::MatchNumberR(n) => vm.match_number_r(*n),
+        Command::MatchCharL(c) => vm.match_char_l(*c),
+        Command::MatchCharR(c) => vm.match_char_r(*c),
         Command::MatchSVarL => vm.match_s_var_l(),
         Command::MatchSVarR => vm.match_s_var_r(),
         Command::MatchSVarLProj(n) => vm.match_s_var_l_proj(*n),
@@ -85,54 +295,197 @@ fn execute_cmd(vm: &mut VM, cmd: &Command) {
         Command::MatchMoveBorderL(n) => vm.match_move_border_l(*n),
         Command::MatchMoveBorderR(n) => vm.match_move_border_r(*n),
         Command::SetupTransition(n) => vm.setup_transition(*n),
+        Command::SwitchSymbolL(table) => {
+            vm.switch_symbol_l(vm.command_index - 1, table);
+        }
         Command::ConstrainLengthen(n) => vm.constrain_lengthen(*n),
+        Command::ConditionStart => vm.condition_start(),
         Command::RewriteStart => vm.rewrite_start(),
         _ => panic!("illegal cmd: {:?}", cmd),
     }
 }
 
-impl VM<'_> {
+impl<'a> VM<'a> {
     fn match_start(&mut self) {
-        if self.dots.is_empty() {
-            self.done = true;
-            return;
+        loop {
+            // The view is checked after every step, the last one and built-ins included.
+            if let Some(limit) = self.exceeded_nodes() {
+                self.exceed(limit);
+                return;
+            }
+            if self
+                .frames
+                .last()
+                .is_some_and(|frame| frame.dots_base == self.dots.len())
+            {
+                self.resume();
+                return;
+            }
+            if self.dots.is_empty() {
+                self.done = true;
+                return;
+            }
+            if let Some(limit) = self.exceeded_limit() {
+                self.exceed(limit);
+                return;
+            }
+            self.steps += 1;
+            let fun_br_r = self.dots.pop().unwrap();
+            let fun_br_l = self.arena.twin(fun_br_r);
+            let fun = self.arena.next(fun_br_l);
+            self.arena.unshare(fun, false);
+            if let Some(trace) = &mut self.trace {
+                let arena = &self.arena;
+                trace(
+                    self.steps,
+                    &arena.objects_between(arena.prev(fun_br_l), arena.next(fun_br_r)),
+                );
+            }
+
+            match lookup(self.program, &self.arena, fun_br_l, fun_br_r) {
+                Ok(Callee::Compiled(commands)) => {
+                    self.enter(commands, None, fun_br_l, fun_br_r);
+                    self.profile(|profile, function| profile.enter(function, Some(commands)));
+                    return;
+                }
+                Ok(Callee::Native(native)) => {
+                    self.enter(&START, Some(*native), fun_br_l, fun_br_r);
+                    self.profile(|profile, function| profile.enter(function, None));
+                    return;
+                }
+                Ok(Callee::Builtin(builtin)) => {
+                    if let Some(profile) = &mut self.profile {
+                        profile.enter(self.arena.object(fun).symbol().unwrap(), None);
+                    }
+                    let arg = self.arena.objects_between(fun, fun_br_r);
+                    match builtin(self.host, &arg) {
+                        Some(result) => {
+                            let dots = self.arena.replace_call(fun_br_l, fun_br_r, &result);
+                            self.dots.extend(dots);
+                        }
+                        None => {
+                            self.error = Some(Error::RecognitionImpossible {
+                                function: self
+                                    .arena
+                                    .object(fun)
+                                    .symbol()
+                                    .map(|s| s.to_string())
+                                    .unwrap_or_default(),
+                                argument: render(&arg),
+                            });
+                            self.done = true;
+                            return;
+                        }
+                    }
+                }
+                Err(error) => {
+                    self.error = Some(error);
+                    self.done = true;
+                    return;
+                }
+            }
         }
+    }
 
-        self.border_r = self.dots.pop().unwrap();
-        self.border_l = self.border_r.twin();
+    /// Starts matching the call between `fun_br_l` and `fun_br_r` against a function.
+    fn enter(
+        &mut self,
+        commands: &'a [Command],
+        native: Option<NativeFn>,
+        fun_br_l: NodeId,
+        fun_br_r: NodeId,
+    ) {
+        let fun = self.arena.next(fun_br_l);
+        self.commands = commands;
+        self.native = native;
+        self.projections.push(self.arena.prev(fun_br_l));
+        self.projections.push(fun);
+        self.projections.push(fun_br_r);
+        self.border_l = fun;
+        self.border_r = fun_br_r;
+        self.command_index = 0;
+    }
 
-        let fun = self.border_l.next();
-        let fun_name = fun.object.symbol().unwrap();
-        self.commands = self.defs.get(fun_name).unwrap();
+    /// Finds a limit on steps or calls that evaluating one more step would exceed.
+    fn exceeded_limit(&self) -> Option<Limit> {
+        match (self.limits.steps, self.limits.calls) {
+            (Some(steps), _) if self.steps >= steps => Some(Limit::Steps(steps)),
+            (_, Some(calls)) if self.dots.len() > calls => Some(Limit::Calls(calls)),
+            _ => None,
+        }
+    }
 
-        self.projections.push(self.border_l.prev());
-        self.projections.push(fun.clone());
-        self.projections.push(self.border_r.clone());
+    /// Finds whether the view exceeds the limit on nodes.
+    fn exceeded_nodes(&self) -> Option<Limit> {
+        self.limits
+            .nodes
+            .filter(|&nodes| self.arena.expanded() > nodes)
+            .map(Limit::Nodes)
+    }
 
-        self.border_l = fun;
-        self.command_index = 0;
+    fn exceed(&mut self, limit: Limit) {
+        self.error = Some(Error::LimitExceeded {
+            limit,
+            steps: self.steps,
+        });
+        self.done = true;
     }
 
     fn match_empty(&mut self) {
-        let next = self.border_l.next();
-        if !ptr::eq(next.as_ref(), self.border_r.as_ref()) {
+        if self.arena.next(self.border_l) != self.border_r {
             self.fail();
         }
     }
 
-    fn match_symbol_l(&mut self, symbol: &str) {
+    fn match_symbol_l(&mut self, symbol: Symbol) {
+        if self.shift_border_l() {
+            match self.arena.object(self.border_l) {
+                Object::Symbol(s) if *s == symbol => self.projections.push(self.border_l),
+                _ => self.fail(),
+            }
+        }
+    }
+
+    fn match_symbol_r(&mut self, symbol: Symbol) {
+        if self.shift_border_r() {
+            match self.arena.object(self.border_r) {
+                Object::Symbol(s) if *s == symbol => self.projections.push(self.border_r),
+                _ => self.fail(),
+            }
+        }
+    }
+
+    fn match_number_l(&mut self, number: u32) {
         if self.shift_border_l() {
-            match &self.border_l.object {
-                Object::Symbol(s) if s == symbol => self.projections.push(self.border_l.clone()),
+            match self.arena.object(self.border_l) {
+                Object::Number(n) if *n == number => self.projections.push(self.border_l),
                 _ => self.fail(),
             }
         }
     }
 
-    fn match_symbol_r(&mut self, symbol: &str) {
+    fn match_number_r(&mut self, number: u32) {
         if self.shift_border_r() {
-            match &self.border_r.object {
-                Object::Symbol(s) if s == symbol => self.projections.push(self.border_r.clone()),
+            match self.arena.object(self.border_r) {
+                Object::Number(n) if *n == number => self.projections.push(self.border_r),
+                _ => self.fail(),
+            }
+        }
+    }
+
+    fn match_char_l(&mut self, char: char) {
+        if self.shift_border_l() {
+            match self.arena.object(self.border_l) {
+                Object::Char(c) if *c == char => self.projections.push(self.border_l),
+                _ => self.fail(),
+            }
+        }
+    }
+
+    fn match_char_r(&mut self, char: char) {
+        if self.shift_border_r() {
+            match self.arena.object(self.border_r) {
+                Object::Char(c) if *c == char => self.projections.push(self.border_r),
                 _ => self.fail(),
             }
         }
@@ -140,11 +493,11 @@ impl VM<'_> {
 
     fn match_str_bracket_l(&mut self) {
         if self.shift_border_l() {
-            match self.border_l.object {
+            match self.arena.object(self.border_l) {
                 Object::StrBracketL => {
-                    self.border_r = self.border_l.twin();
-                    self.projections.push(self.border_l.clone());
-                    self.projections.push(self.border_l.twin());
+                    self.border_r = self.arena.twin(self.border_l);
+                    self.projections.push(self.border_l);
+                    self.projections.push(self.border_r);
                 }
                 _ => self.fail(),
             }
@@ -153,11 +506,12 @@ impl VM<'_> {
 
     fn match_str_bracket_r(&mut self) {
         if self.shift_border_r() {
-            match self.border_r.object {
+            match self.arena.object(self.border_r) {
                 Object::StrBracketR => {
-                    self.projections.push(self.border_r.twin());
-                    self.projections.push(self.border_r.clone());
-                    self.border_r = self.border_r.twin();
+                    let twin = self.arena.twin(self.border_r);
+                    self.projections.push(twin);
+                    self.projections.push(self.border_r);
+                    self.border_r = twin;
                 }
                 _ => self.fail(),
             }
@@ -166,128 +520,138 @@ impl VM<'_> {
 
     fn match_s_var_l(&mut self) {
         if self.shift_border_l() {
-            if self.border_l.object.symbol().is_none() {
+            if !self.arena.object(self.border_l).is_symbol() {
                 self.fail()
             } else {
-                self.projections.push(self.border_l.clone())
+                self.projections.push(self.border_l)
             }
         }
     }
 
     fn match_s_var_r(&mut self) {
         if self.shift_border_r() {
-            if self.border_r.object.symbol().is_none() {
+            if !self.arena.object(self.border_r).is_symbol() {
                 self.fail()
             } else {
-                self.projections.push(self.border_r.clone())
+                self.projections.push(self.border_r)
             }
         }
     }
 
     fn match_s_var_l_proj(&mut self, n: usize) {
         if self.shift_border_l() {
-            let object = &self.projections[n].object;
-            if &self.border_l.object != object {
+            let object = self.arena.object(self.projections[n]);
+            if self.arena.object(self.border_l) != object {
                 self.fail()
             } else {
-                self.projections.push(self.border_l.clone())
+                self.projections.push(self.border_l)
             }
         }
     }
 
     fn match_s_var_r_proj(&mut self, n: usize) {
         if self.shift_border_r() {
-            let object = &self.projections[n].object;
-            if &self.border_r.object != object {
+            let object = self.arena.object(self.projections[n]);
+            if self.arena.object(self.border_r) != object {
                 self.fail()
             } else {
-                self.projections.push(self.border_r.clone())
+                self.projections.push(self.border_r)
             }
         }
     }
 
     fn match_t_var_l(&mut self) {
         if self.shift_border_l() {
-            self.projections.push(self.border_l.clone());
-            if self.border_l.object == Object::StrBracketL {
-                self.border_l = self.border_l.twin();
+            self.projections.push(self.border_l);
+            if *self.arena.object(self.border_l) == Object::StrBracketL {
+                self.border_l = self.arena.twin(self.border_l);
             }
-            self.projections.push(self.border_l.clone());
+            self.projections.push(self.border_l);
         }
     }
 
     fn match_t_var_r(&mut self) {
         if self.shift_border_r() {
-            let to_insert = self.border_r.clone();
-            if self.border_r.object == Object::StrBracketR {
-                self.border_r = self.border_r.twin()
+            let to_insert = self.border_r;
+            if *self.arena.object(self.border_r) == Object::StrBracketR {
+                self.border_r = self.arena.twin(self.border_r)
             }
-            self.projections.push(self.border_r.clone());
+            self.projections.push(self.border_r);
             self.projections.push(to_insert);
         }
     }
 
     fn match_e_var(&mut self) {
-        let start = &self.border_l.next();
-        let end = &self.border_r.prev();
-        self.projections.push(start.clone());
-        self.projections.push(end.clone());
+        let start = self.arena.next(self.border_l);
+        let end = self.arena.prev(self.border_r);
+        self.projections.push(start);
+        self.projections.push(end);
     }
 
     fn match_e_var_l_proj(&mut self, n: usize) {
-        let border1 = self.projections[n - 1].clone();
-        let border2 = self.projections[n].clone();
-        let start = self.border_l.next();
-        let mut cursor = border1.prev();
-        while !ptr::eq(cursor.as_ref(), border2.as_ref()) {
-            cursor = cursor.next();
+        let border1 = self.projections[n - 1];
+        let mut border2 = self.projections[n];
+        let start = self.arena.next(self.border_l);
+        let mut cursor = self.arena.prev(border1);
+        while cursor != border2 {
+            cursor = self.arena.next(cursor);
+            let end = self.arena.unshare(cursor, false);
+            if cursor == border2 {
+                border2 = end;
+                self.projections[n] = end;
+            }
             if !self.shift_border_l() {
                 return;
             }
-            if cursor.object == self.border_l.object {
+            if self.arena.object(cursor) == self.arena.object(self.border_l) {
                 continue;
             }
             self.fail();
             return;
         }
         self.projections.push(start);
-        self.projections.push(self.border_l.clone());
+        self.projections.push(self.border_l);
     }
 
     fn match_e_var_r_proj(&mut self, n: usize) {
-        let border1 = self.projections[n - 1].clone();
-        let border2 = self.projections[n].clone();
-        let end = self.border_r.prev();
-        let mut cursor = border2.next();
-        while !ptr::eq(cursor.as_ref(), border1.as_ref()) {
-            cursor = cursor.prev();
+        let mut border1 = self.projections[n - 1];
+        let border2 = self.projections[n];
+        let end = self.arena.prev(self.border_r);
+        let mut cursor = self.arena.next(border2);
+        while cursor != border1 {
+            cursor = self.arena.prev(cursor);
+            let start = self.arena.unshare(cursor, true);
+            if cursor == border1 {
+                border1 = start;
+                self.projections[n - 1] = start;
+            }
             if !self.shift_border_r() {
                 return;
             }
-            if cursor.object == self.border_r.object {
+            if self.arena.object(cursor) == self.arena.object(self.border_r) {
                 continue;
             }
             self.fail();
             return;
         }
-        self.projections.push(self.border_r.clone());
+        self.projections.push(self.border_r);
         self.projections.push(end);
     }
 
     fn match_move_border_l(&mut self, n: usize) {
-        self.border_l = self.projections[n].clone();
+        self.border_l = self.projections[n];
     }
 
     fn match_move_border_r(&mut self, n: usize) {
-        self.border_r = self.projections[n].clone();
+        self.border_r = self.projections[n];
     }
 
     fn prepare_lengthen(&mut self) {
-        self.projections.push(self.border_l.next());
-        self.projections.push(self.border_l.clone());
+        self.projections.push(self.arena.next(self.border_l));
+        self.projections.push(self.border_l);
         self.jumps.push(Jump {
-            border_l: self.border_l.clone(),
-            border_r: self.border_r.clone(),
+            border_l: self.border_l,
+            border_r: self.border_r,
             projection_index: self.projections.len(),
             command_index: self.command_index,
         });
@@ -295,15 +659,16 @@ impl VM<'_> {
     }
 
     fn lengthen(&mut self) {
+        self.profile(|profile, function| profile.function(function).lengthenings += 1);
         self.border_l = self.projections.pop().unwrap();
         if self.shift_border_l() {
-            if self.border_l.object == Object::StrBracketL {
-                self.border_l = self.border_l.twin();
+            if *self.arena.object(self.border_l) == Object::StrBracketL {
+                self.border_l = self.arena.twin(self.border_l);
             }
-            self.projections.push(self.border_l.clone());
+            self.projections.push(self.border_l);
             self.jumps.push(Jump {
-                border_l: self.border_l.clone(),
-                border_r: self.border_r.clone(),
+                border_l: self.border_l,
+                border_r: self.border_r,
                 projection_index: self.projections.len(),
                 command_index: self.command_index - 1,
             });
@@ -312,186 +677,374 @@ impl VM<'_> {
 
     fn setup_transition(&mut self, command_index: usize) {
         self.jumps.push(Jump {
-            border_l: self.border_l.clone(),
-            border_r: self.border_r.clone(),
+            border_l: self.border_l,
+            border_r: self.border_r,
             projection_index: self.projections.len(),
             command_index,
         });
     }
 
+    /// Goes on at the address `table` pairs with the symbol right of the left border, if any,
+    /// and tells whether it does; `address` is that of the `SwitchSymbolL` command.
+    fn switch_symbol_l(&mut self, address: usize, table: &[(Symbol, usize)]) -> bool {
+        let next = self.arena.next(self.border_l);
+        if next == self.border_r {
+            return false;
+        }
+        self.arena.unshare(next, false);
+        let target = match self.arena.object(next) {
+            Object::Symbol(symbol) => table
+                .iter()
+                .find(|(key, _)| key == symbol)
+                .map(|&(_, target)| target),
+            _ => None,
+        };
+        match target {
+            Some(target) => {
+                self.command_index = target;
+                self.profile(|profile, function| profile.switch(function, address, target));
+                true
+            }
+            None => false,
+        }
+    }
+
     fn constrain_lengthen(&mut self, n: usize) {
         for _ in 0..n {
             self.jumps.pop();
         }
     }
 
+    /// Builds the result of a condition and suspends the sentence until it is evaluated.
+    fn condition_start(&mut self) {
+        let (result, mut builder) = self.begin_condition();
+        loop {
+            let cmd = &self.commands[self.command_index];
+            self.command_index += 1;
+            match cmd {
+                Command::ConditionEvaluate => break,
+                _ => self.build(&mut builder, cmd),
+            }
+        }
+        self.evaluate_condition(result, builder);
+    }
+
+    /// Starts building the result of a condition in a list of its own.
+    fn begin_condition(&mut self) -> (Chain, Builder) {
+        let result = self.arena.chain();
+        (result, Builder::new(result.first))
+    }
+
+    /// Suspends the sentence, to go on at `command_index` once `result` is evaluated.
+    fn evaluate_condition(&mut self, result: Chain, mut builder: Builder) {
+        self.frames.push(Frame {
+            commands: self.commands,
+            native: self.native,
+            command_index: self.command_index,
+            projections: mem::take(&mut self.projections),
+            jumps: mem::take(&mut self.jumps),
+            conditions: mem::take(&mut self.conditions),
+            dots_base: self.dots.len(),
+            result,
+        });
+        while let Some(dot) = builder.dots.pop() {
+            self.dots.push(dot)
+        }
+        self.match_start();
+    }
+
+    /// Continues a suspended sentence by matching the evaluated result of its condition.
+    fn resume(&mut self) {
+        let frame = self.frames.pop().unwrap();
+        self.commands = frame.commands;
+        self.native = frame.native;
+        self.command_index = frame.command_index;
+        self.projections = frame.projections;
+        self.jumps = frame.jumps;
+        self.conditions = frame.conditions;
+        self.border_l = frame.result.first;
+        self.border_r = frame.result.last;
+        self.projections.push(self.border_l);
+        self.projections.push(self.border_r);
+        self.conditions.push(Condition {
+            result: frame.result,
+            jumps: self.jumps.len(),
+        });
+    }
+
+    fn free_conditions(&mut self) {
+        for condition in self.conditions.drain(..) {
+            self.arena.free(condition.result.first);
+        }
+        for frame in self.frames.drain(..) {
+            self.arena.free(frame.result.first);
+            for condition in frame.conditions {
+                self.arena.free(condition.result.first);
+            }
+        }
+    }
+
     fn rewrite_start(&mut self) {
-        self.jumps.clear();
-        let mut border = self.projections[0].clone();
-        let mut local_dots: Vec<Rc<Node>> = vec![];
-        let mut l_brackets: Vec<Rc<Node>> = vec![];
-        let mut l_fun_brackets: Vec<Rc<Node>> = vec![];
-        let mut transplants: Vec<(Rc<Node>, Rc<Node>, Rc<Node>)> = vec![];
+        let mut rewrite = self.begin_rewrite(self.command_index - 1);
         loop {
             let cmd = &self.commands[self.command_index];
             self.command_index += 1;
             match cmd {
-                Command::InsertSymbol(s) => {
-                    let symbol = Rc::new(Node::new(Object::Symbol(s.clone())));
-                    let next = border.next();
-                    link_nodes(&border, &symbol);
-                    border = symbol;
-                    link_nodes(&border, &next);
-                }
-                Command::InsertStrBracketL => {
-                    let bracket_l = Rc::new(Node::new(Object::StrBracketL));
-                    l_brackets.push(bracket_l.clone());
-                    let next = border.next();
-                    link_nodes(&border, &bracket_l);
-                    border = bracket_l;
-                    link_nodes(&border, &next);
-                }
-                Command::InsertStrBracketR => {
-                    let bracket_r = Rc::new(Node::new(Object::StrBracketR));
-                    let bracket_l = l_brackets.pop().unwrap();
-                    pair_nodes(&bracket_l, &bracket_r);
-                    let next = border.next();
-                    link_nodes(&border, &bracket_r);
-                    border = bracket_r;
-                    link_nodes(&border, &next);
-                }
-                Command::InsertFunBracketL => {
-                    let bracket_l = Rc::new(Node::new(Object::FunBracketL));
-                    l_fun_brackets.push(bracket_l.clone());
-                    let next = border.next();
-                    link_nodes(&border, &bracket_l);
-                    border = bracket_l;
-                    link_nodes(&border, &next);
-                }
-                Command::InsertFunBracketR => {
-                    let bracket_r = Rc::new(Node::new(Object::FunBracketR));
-                    let bracket_l = l_fun_brackets.pop().unwrap();
-                    pair_nodes(&bracket_l, &bracket_r);
-                    let next = border.next();
-                    link_nodes(&border, &bracket_r);
-                    border = bracket_r;
-                    link_nodes(&border, &next);
-                    local_dots.push(border.clone());
-                }
-                Command::TransplantObject(n) => {
-                    transplants.push((
-                        border.clone(),
-                        self.projections[*n].clone(),
-                        self.projections[*n].clone(),
-                    ));
-                }
-                Command::TransplantExpr(n) => {
-                    let start = self.projections[*n - 1].clone();
-                    let end = self.projections[*n].clone();
-                    if !ptr::eq(end.next().as_ref(), start.as_ref()) {
-                        transplants.push((border.clone(), start, end));
-                    }
+                Command::RewriteFinalize => {
+                    self.finish_rewrite(rewrite);
+                    return;
                 }
-                Command::CopySymbol(n) => {
-                    let node = &self.projections[*n];
-                    let s = node.object.symbol().unwrap().to_string();
-                    let symbol = Rc::new(Node::new(Object::Symbol(s)));
-                    let current_next = border.next();
-                    link_nodes(&border, &symbol);
-                    border = symbol;
-                    link_nodes(&border, &current_next);
+                _ => self.rewrite(&mut rewrite, cmd),
+            }
+        }
+    }
+
+    /// Starts rewriting the call with the sentence whose `RewriteStart` is at `address`.
+    fn begin_rewrite(&mut self, address: usize) -> Rewrite {
+        self.profile(|profile, function| profile.rewrite(function, address));
+        // The call is gone once rewritten, its function is read beforehand.
+        let function = match self.coverage {
+            Some(_) => self.arena.object(self.projections[1]).symbol(),
+            None => None,
+        };
+        self.jumps.clear();
+        Rewrite {
+            builder: Builder::new(self.projections[0]),
+            transplants: Vec::new(),
+            function,
+            address,
+        }
+    }
+
+    /// Executes a command of a rewrite other than `RewriteFinalize`.
+    fn rewrite(&mut self, rewrite: &mut Rewrite, cmd: &Command) {
+        self.profile_nodes(cmd);
+        let Rewrite {
+            builder,
+            transplants,
+            ..
+        } = rewrite;
+        match cmd {
+            Command::TransplantObject(n) => {
+                transplants.push((builder.border, self.projections[*n], self.projections[*n]));
+            }
+            Command::TransplantExpr(n) => {
+                let start = self.projections[*n - 1];
+                let end = self.projections[*n];
+                if self.arena.next(end) != start {
+                    transplants.push((builder.border, start, end));
                 }
-                Command::CopyExpr(n) => {
-                    let node1 = &self.projections[*n - 1];
-                    let node2 = &self.projections[*n];
-                    let mut current_node: Rc<Node> = node1.prev();
-                    let next = &border.next();
-                    while !ptr::eq(current_node.as_ref(), node2.as_ref()) {
-                        current_node = current_node.next();
-                        match &current_node.object {
-                            Object::StrBracketL => {
-                                let current_to_insert = Rc::new(Node::new(Object::StrBracketL));
-                                l_brackets.push(current_to_insert.clone());
-                                link_nodes(&border, &current_to_insert);
-                                border = current_to_insert;
-                            }
-                            Object::StrBracketR => {
-                                let current_to_insert = Rc::new(Node::new(Object::StrBracketR));
-                                let prev_l_bracket = l_brackets.pop().unwrap();
-                                pair_nodes(&prev_l_bracket, &current_to_insert);
-                                link_nodes(&border, &current_to_insert);
-                                border = current_to_insert;
-                            }
-                            object => {
-                                let current_to_insert = Rc::new(Node::new(object.clone()));
-                                link_nodes(&border, &current_to_insert);
-                                border = current_to_insert;
-                            }
-                        }
-                    }
-                    link_nodes(&border, next);
+            }
+            Command::CopyExpr(n) => {
+                if let Some(node) = self.share_var(*n, transplants) {
+                    let object = *self.arena.object(node);
+                    builder.insert(&mut self.arena, object);
                 }
-                Command::RewriteFinalize => {
-                    let node = &self.projections[2];
-                    let garbage = if !ptr::eq(border.as_ref(), node.as_ref()) {
-                        let next = &node.next();
-                        let first_to_delete = border.next();
-                        let last_to_delete = next.prev();
-                        link_nodes(&border, next);
-                        unlink_next(&last_to_delete);
-                        unlink_prev(&first_to_delete);
-                        Some(first_to_delete)
-                    } else {
-                        None
-                    };
-                    while let Some(transplant) = transplants.pop() {
-                        let (border, start, end) = transplant;
-                        link_nodes(&start.prev(), &end.next());
-                        link_nodes(&end, &border.next());
-                        link_nodes(&border, &start);
-                    }
-                    if let Some(start) = garbage {
-                        free(start);
-                    }
-                    while let Some(dot) = local_dots.pop() {
-                        self.dots.push(dot)
-                    }
-                    self.projections.clear();
-                    return;
+            }
+            _ => self.build(builder, cmd),
+        }
+    }
+
+    /// Replaces the call by the objects built and the variables moved.
+    fn finish_rewrite(&mut self, rewrite: Rewrite) {
+        let Rewrite {
+            mut builder,
+            mut transplants,
+            function,
+            address,
+        } = rewrite;
+        let border = builder.border;
+        let node = self.projections[2];
+        let garbage = if border != node {
+            let next = self.arena.next(node);
+            let first_to_delete = self.arena.next(border);
+            let last_to_delete = self.arena.prev(next);
+            self.arena.link(border, next);
+            self.arena.unlink_next(last_to_delete);
+            self.arena.unlink_prev(first_to_delete);
+            Some(first_to_delete)
+        } else {
+            None
+        };
+        while let Some(transplant) = transplants.pop() {
+            let (border, start, end) = transplant;
+            let (prev, next) = (self.arena.prev(start), self.arena.next(end));
+            self.arena.link(prev, next);
+            let next = self.arena.next(border);
+            self.arena.link(end, next);
+            self.arena.link(border, start);
+        }
+        if let Some(start) = garbage {
+            self.arena.free(start);
+        }
+        while let Some(dot) = builder.dots.pop() {
+            self.dots.push(dot)
+        }
+        for condition in self.conditions.drain(..) {
+            self.arena.free(condition.result.first);
+        }
+        self.projections.clear();
+        // Native code keeps no commands to find the sentences in.
+        if let (Some(coverage), Some(function), None) = (&mut self.coverage, function, self.native)
+        {
+            coverage.record(function, self.commands, address);
+        }
+    }
+
+    /// The node standing for the value of the variable ending at projection `n`, `None` if it
+    /// is empty. A value of several nodes is replaced by a node sharing it, so that it can be
+    /// copied as a whole; the projections and `transplants` referring to it are updated.
+    fn share_var(
+        &mut self,
+        n: usize,
+        transplants: &mut [(NodeId, NodeId, NodeId)],
+    ) -> Option<NodeId> {
+        let (start, end) = (self.projections[n - 1], self.projections[n]);
+        if self.arena.next(end) == start {
+            return None;
+        }
+        if start == end {
+            return Some(start);
+        }
+        let node = self.arena.share(start, end);
+        for projection in &mut self.projections {
+            if *projection == start || *projection == end {
+                *projection = node;
+            }
+        }
+        for transplant in transplants {
+            if (transplant.1, transplant.2) == (start, end) {
+                (transplant.1, transplant.2) = (node, node);
+            }
+        }
+        Some(node)
+    }
+
+    /// Executes a command inserting objects, possibly copies of matched variables.
+    /// Copies in the results of conditions are made node by node, as the matched view
+    /// must stay as it is in case the condition fails.
+    fn build(&mut self, builder: &mut Builder, cmd: &Command) {
+        let arena = &mut self.arena;
+        match cmd {
+            Command::InsertSymbol(s) => builder.insert(arena, Object::Symbol(*s)),
+            Command::InsertNumber(n) => builder.insert(arena, Object::Number(*n)),
+            Command::InsertChar(c) => builder.insert(arena, Object::Char(*c)),
+            Command::InsertStrBracketL => builder.insert(arena, Object::StrBracketL),
+            Command::InsertStrBracketR => builder.insert(arena, Object::StrBracketR),
+            Command::InsertFunBracketL => builder.insert(arena, Object::FunBracketL),
+            Command::InsertFunBracketR => builder.insert(arena, Object::FunBracketR),
+            Command::CopySymbol(n) => {
+                let object = *arena.object(self.projections[*n]);
+                builder.insert(arena, object)
+            }
+            Command::CopyExpr(n) => {
+                let start = self.projections[*n - 1];
+                let end = self.projections[*n];
+                let mut cursor = arena.prev(start);
+                while cursor != end {
+                    cursor = arena.next(cursor);
+                    let object = *arena.object(cursor);
+                    builder.insert(arena, object);
                 }
-                _ => panic!("internal error"),
             }
+            _ => panic!("internal error"),
         }
     }
 
     fn fail(&mut self) {
+        self.failed = true;
         match self.jumps.pop() {
-            None => panic!("Recognition impossible"),
+            None => {
+                let fun = self.projections[1];
+                self.error = Some(Error::RecognitionImpossible {
+                    function: self
+                        .arena
+                        .object(fun)
+                        .symbol()
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                    argument: render(&self.arena.objects_between(fun, self.projections[2])),
+                });
+                self.done = true;
+            }
             Some(jump) => {
+                while let Some(condition) = self.conditions.last() {
+                    if condition.jumps <= self.jumps.len() {
+                        break;
+                    }
+                    let condition = self.conditions.pop().unwrap();
+                    self.arena.free(condition.result.first);
+                }
                 self.border_l = jump.border_l;
                 self.border_r = jump.border_r;
                 self.projections.truncate(jump.projection_index);
                 self.command_index = jump.command_index;
+                self.profile(|profile, function| profile.jump(function, jump.command_index));
             }
         }
     }
 
+    /// Passes the function of the call being evaluated to `record` when profiling.
+    fn profile(&mut self, record: impl FnOnce(&mut Profile, Symbol)) {
+        if let Some(profile) = &mut self.profile {
+            let function = self.arena.object(self.projections[1]).symbol().unwrap();
+            record(profile, function);
+        }
+    }
+
+    /// Counts the nodes a command of the rewrite copies or transplants when profiling.
+    fn profile_nodes(&mut self, cmd: &Command) {
+        if self.profile.is_none() {
+            return;
+        }
+        let nodes = |n: usize| self.count_nodes(self.projections[n - 1], self.projections[n]);
+        let (copied, transplanted) = match cmd {
+            Command::CopySymbol(_) => (1, 0),
+            Command::CopyExpr(n) => (nodes(*n), 0),
+            Command::TransplantObject(_) => (0, 1),
+            Command::TransplantExpr(n) => (0, nodes(*n)),
+            _ => return,
+        };
+        self.profile(|profile, function| {
+            let profile = profile.function(function);
+            profile.copied += copied;
+            profile.transplanted += transplanted;
+        });
+    }
+
+    /// The number of nodes from `start` to `end`, none if `end` is the left neighbour of `start`.
+    fn count_nodes(&self, start: NodeId, end: NodeId) -> u64 {
+        let mut count = 0;
+        let mut cursor = self.arena.prev(start);
+        while cursor != end {
+            cursor = self.arena.next(cursor);
+            count += 1;
+        }
+        count
+    }
+
+    /// Moves the left border to the next object, expanding a shared subexpression
+    /// there so that the border is on its first object.
     fn shift_border_l(&mut self) -> bool {
-        self.border_l = self.border_l.next();
-        if ptr::eq(self.border_l.as_ref(), self.border_r.as_ref()) {
+        self.border_l = self.arena.next(self.border_l);
+        if self.border_l == self.border_r {
             self.fail();
             false
         } else {
+            self.arena.unshare(self.border_l, false);
             true
         }
     }
 
+    /// Moves the right border to the previous object, expanding a shared subexpression
+    /// there so that the border is on its last object.
     fn shift_border_r(&mut self) -> bool {
-        self.border_r = self.border_r.prev();
-        if ptr::eq(self.border_l.as_ref(), self.border_r.as_ref()) {
+        self.border_r = self.arena.prev(self.border_r);
+        if self.border_l == self.border_r {
             self.fail();
             false
         } else {
+            self.arena.unshare(self.border_r, true);
             true
         }
     }
@@ -0,0 +1,235 @@
+#[cfg(test)]
+mod tests;
+
+use super::{Builder, Rewrite, VM};
+use crate::data::Command;
+use crate::runtime::Chain;
+use crate::symbol::Symbol;
+
+/// A function compiled to native code, as `rust_backend` generates it. It executes the commands
+/// of the function through the `Machine`, from the address the machine gives, and returns once
+/// the call is rewritten, a condition is to be evaluated or the evaluation has stopped.
+pub type NativeFn = fn(&mut Machine<'_, '_>);
+
+/// The evaluation of a call as native code sees it, with a method for each command taking
+/// the operands of the command. The matching methods return `false` when matching fails,
+/// after which `address` tells where to go on.
+pub struct Machine<'m, 'a> {
+    vm: &'m mut VM<'a>,
+    rewrite: Option<Rewrite>,
+    condition: Option<(Chain, Builder)>,
+}
+
+impl<'m, 'a> Machine<'m, 'a> {
+    pub(super) fn new(vm: &'m mut VM<'a>) -> Self {
+        Machine {
+            vm,
+            rewrite: None,
+            condition: None,
+        }
+    }
+
+    /// The address of the command to execute next, `None` once the evaluation has stopped.
+    pub fn address(&self) -> Option<usize> {
+        if self.vm.done {
+            None
+        } else {
+            Some(self.vm.command_index)
+        }
+    }
+
+    /// Goes on at `address`.
+    pub fn goto(&mut self, address: usize) {
+        self.vm.command_index = address;
+    }
+
+    fn check(&mut self, matching: impl FnOnce(&mut VM<'a>)) -> bool {
+        self.vm.failed = false;
+        matching(self.vm);
+        !self.vm.failed
+    }
+
+    pub fn match_empty(&mut self) -> bool {
+        self.check(VM::match_empty)
+    }
+
+    pub fn match_str_bracket_l(&mut self) -> bool {
+        self.check(VM::match_str_bracket_l)
+    }
+
+    pub fn match_str_bracket_r(&mut self) -> bool {
+        self.check(VM::match_str_bracket_r)
+    }
+
+    pub fn match_symbol_l(&mut self, symbol: Symbol) -> bool {
+        self.check(|vm| vm.match_symbol_l(symbol))
+    }
+
+    pub fn match_symbol_r(&mut self, symbol: Symbol) -> bool {
+        self.check(|vm| vm.match_symbol_r(symbol))
+    }
+
+    pub fn match_number_l(&mut self, number: u32) -> bool {
+        self.check(|vm| vm.match_number_l(number))
+    }
+
+    pub fn match_number_r(&mut self, number: u32) -> bool {
+        self.check(|vm| vm.match_number_r(number))
+    }
+
+    pub fn match_char_l(&mut self, char: char) -> bool {
+        self.check(|vm| vm.match_char_l(char))
+    }
+
+    pub fn match_char_r(&mut self, char: char) -> bool {
+        self.check(|vm| vm.match_char_r(char))
+    }
+
+    pub fn match_s_var_l(&mut self) -> bool {
+        self.check(VM::match_s_var_l)
+    }
+
+    pub fn match_s_var_r(&mut self) -> bool {
+        self.check(VM::match_s_var_r)
+    }
+
+    pub fn match_s_var_l_proj(&mut self, n: usize) -> bool {
+        self.check(|vm| vm.match_s_var_l_proj(n))
+    }
+
+    pub fn match_s_var_r_proj(&mut self, n: usize) -> bool {
+        self.check(|vm| vm.match_s_var_r_proj(n))
+    }
+
+    pub fn match_t_var_l(&mut self) -> bool {
+        self.check(VM::match_t_var_l)
+    }
+
+    pub fn match_t_var_r(&mut self) -> bool {
+        self.check(VM::match_t_var_r)
+    }
+
+    /// Starts lengthening an e-variable; `address` is that of the `MatchEVarPrepare` command,
+    /// and matching goes on two commands later.
+    pub fn prepare_lengthen(&mut self, address: usize) {
+        self.vm.command_index = address + 1;
+        self.vm.prepare_lengthen();
+    }
+
+    pub fn match_e_var(&mut self) {
+        self.vm.match_e_var();
+    }
+
+    /// Lengthens an e-variable; `address` is that of the `MatchEVarLengthen` command.
+    pub fn lengthen(&mut self, address: usize) -> bool {
+        self.vm.command_index = address + 1;
+        self.check(VM::lengthen)
+    }
+
+    pub fn match_e_var_l_proj(&mut self, n: usize) -> bool {
+        self.check(|vm| vm.match_e_var_l_proj(n))
+    }
+
+    pub fn match_e_var_r_proj(&mut self, n: usize) -> bool {
+        self.check(|vm| vm.match_e_var_r_proj(n))
+    }
+
+    pub fn match_move_border_l(&mut self, n: usize) {
+        self.vm.match_move_border_l(n);
+    }
+
+    pub fn match_move_border_r(&mut self, n: usize) {
+        self.vm.match_move_border_r(n);
+    }
+
+    pub fn setup_transition(&mut self, address: usize) {
+        self.vm.setup_transition(address);
+    }
+
+    /// Goes on at the address `table` pairs with the symbol right of the left border, if any,
+    /// and tells whether it does; `address` is that of the `SwitchSymbolL` command.
+    pub fn switch_symbol_l(&mut self, address: usize, table: &[(Symbol, usize)]) -> bool {
+        self.vm.switch_symbol_l(address, table)
+    }
+
+    pub fn constrain_lengthen(&mut self, n: usize) {
+        self.vm.constrain_lengthen(n);
+    }
+
+    pub fn condition_start(&mut self) {
+        self.condition = Some(self.vm.begin_condition());
+    }
+
+    /// Suspends the sentence until the result of its condition is evaluated,
+    /// to go on at `address`.
+    pub fn condition_evaluate(&mut self, address: usize) {
+        let (result, builder) = self.condition.take().expect("no condition started");
+        self.vm.command_index = address;
+        self.vm.evaluate_condition(result, builder);
+    }
+
+    /// Starts rewriting the call; `address` is that of the `RewriteStart` command.
+    pub fn rewrite_start(&mut self, address: usize) {
+        self.rewrite = Some(self.vm.begin_rewrite(address));
+    }
+
+    /// Replaces the call and finds the next one to evaluate.
+    pub fn rewrite_finalize(&mut self) {
+        let rewrite = self.rewrite.take().expect("no rewrite started");
+        self.vm.finish_rewrite(rewrite);
+        self.vm.match_start();
+    }
+
+    pub fn insert_symbol(&mut self, symbol: Symbol) {
+        self.construct(&Command::InsertSymbol(symbol));
+    }
+
+    pub fn insert_number(&mut self, number: u32) {
+        self.construct(&Command::InsertNumber(number));
+    }
+
+    pub fn insert_char(&mut self, char: char) {
+        self.construct(&Command::InsertChar(char));
+    }
+
+    pub fn insert_str_bracket_l(&mut self) {
+        self.construct(&Command::InsertStrBracketL);
+    }
+
+    pub fn insert_str_bracket_r(&mut self) {
+        self.construct(&Command::InsertStrBracketR);
+    }
+
+    pub fn insert_fun_bracket_l(&mut self) {
+        self.construct(&Command::InsertFunBracketL);
+    }
+
+    pub fn insert_fun_bracket_r(&mut self) {
+        self.construct(&Command::InsertFunBracketR);
+    }
+
+    pub fn copy_symbol(&mut self, n: usize) {
+        self.construct(&Command::CopySymbol(n));
+    }
+
+    pub fn copy_expr(&mut self, n: usize) {
+        self.construct(&Command::CopyExpr(n));
+    }
+
+    pub fn transplant_object(&mut self, n: usize) {
+        self.construct(&Command::TransplantObject(n));
+    }
+
+    pub fn transplant_expr(&mut self, n: usize) {
+        self.construct(&Command::TransplantExpr(n));
+    }
+
+    /// Executes a command building the rewrite or the result of a condition.
+    fn construct(&mut self, cmd: &Command) {
+        match (&mut self.rewrite, &mut self.condition) {
+            (Some(rewrite), _) => self.vm.rewrite(rewrite, cmd),
+            (None, Some((_, builder))) => self.vm.build(builder, cmd),
+            (None, None) => panic!("no rewrite or condition started"),
+        }
+    }
+}
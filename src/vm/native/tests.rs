@@ -0,0 +1,101 @@
+use crate::compiler::{compile, decision_trees};
+use crate::data::Error;
+use crate::host::MemoryHost;
+use crate::profile::Profile;
+use crate::program::Program;
+use crate::rust_backend::generate;
+use crate::symbol::Symbol;
+use crate::vm::{eval_call, eval_expression, render, Limits, Object};
+
+/// What `rust_backend` generates from `PROGRAM`, with the paths of this crate.
+#[rustfmt::skip]
+mod generated;
+
+static PROGRAM: &str = "$MODULE C;
+Find {
+  $e.1 'b' $s.2 $e.3, $s.2 : 'd' = Found <Count $e.1>;
+  $e.1 = None;
+}
+Count { $s.1 $e.2 = <Count $e.2> 1; = ; }
+Digit { Zero = 0; One = 1; Two = 2; $e.1 = None; }";
+
+#[test]
+fn test_generated() {
+    let mut defs = compile(PROGRAM).unwrap();
+    decision_trees(&mut defs);
+    let expected = generate(&defs).replace("use refal::", "use crate::");
+    assert_eq!(include_str!("tests/generated.rs"), expected);
+}
+
+fn chars(text: &str) -> Vec<Object> {
+    text.chars().map(Object::Char).collect()
+}
+
+#[test]
+fn test_native() {
+    let native = generated::program();
+    let interpreted = Program::new(compile(PROGRAM).unwrap());
+    for arg in ["xabcabdy", "abc", "bd", ""] {
+        let expected = eval_call(&interpreted, "C.Find", &chars(arg));
+        let result = eval_call(&native, "C.Find", &chars(arg));
+        assert_eq!(result, expected, "{:?}", arg);
+    }
+    assert_eq!(
+        eval_call(&native, "C.Find", &chars("xabcabdy")).map(|r| render(&r)),
+        Ok(String::from("Found 1 1 1 1 1"))
+    );
+}
+
+#[test]
+fn test_native_errors() {
+    let native = generated::program();
+    assert_eq!(
+        eval_call(
+            &native,
+            "C.Count",
+            &[Object::StrBracketL, Object::StrBracketR]
+        ),
+        Err(Error::RecognitionImpossible {
+            function: String::from("C.Count"),
+            argument: String::from("()"),
+        })
+    );
+
+    let mut profile = Profile::new();
+    let call = [
+        Object::FunBracketL,
+        Object::Symbol(Symbol::intern("C.Count")),
+        Object::Char('a'),
+        Object::Char('b'),
+        Object::FunBracketR,
+    ];
+    let result = eval_expression(
+        &native,
+        &call,
+        &mut MemoryHost::default(),
+        Limits::default(),
+        None,
+        Some(&mut profile),
+        None,
+    );
+    assert_eq!(result.map(|r| render(&r)), Ok(String::from("1 1")));
+    assert_eq!(profile.functions()[&Symbol::intern("C.Count")].calls, 3);
+}
+
+#[test]
+fn test_native_switch() {
+    let native = generated::program();
+    let interpreted = Program::new(compile(PROGRAM).unwrap());
+    let symbol = |name: &str| Object::Symbol(Symbol::intern(name));
+    for arg in [
+        vec![symbol("Zero")],
+        vec![symbol("Two")],
+        vec![symbol("Three")],
+        vec![symbol("One"), symbol("One")],
+        vec![Object::Char('a')],
+        vec![],
+    ] {
+        let expected = eval_call(&interpreted, "C.Digit", &arg);
+        assert_eq!(eval_call(&native, "C.Digit", &arg), expected, "{:?}", arg);
+    }
+}
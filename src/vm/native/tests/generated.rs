@@ -0,0 +1,179 @@
+//! Generated by `refal --emit-rust`.
+
+use crate::program::Program;
+use crate::symbol::Symbol;
+use crate::vm::native::Machine;
+use std::sync::OnceLock;
+
+/// The functions of the program linked with the built-ins.
+pub fn program() -> Program {
+    Program::native(&[
+        ("C.Count", f0),
+        ("C.Digit", f1),
+        ("C.Find", f2),
+    ])
+}
+
+/// The symbols the functions match and insert, interned once.
+fn symbols() -> &'static [Symbol] {
+    static SYMBOLS: OnceLock<Vec<Symbol>> = OnceLock::new();
+    SYMBOLS.get_or_init(|| {
+        [
+            "C.Count",
+            "One",
+            "Two",
+            "Zero",
+            "None",
+            "Found",
+        ]
+        .iter()
+        .map(|name| Symbol::intern(name))
+        .collect()
+    })
+}
+
+/// `C.Count`
+fn f0(m: &mut Machine) {
+    let s = symbols();
+    while let Some(address) = m.address() {
+        match address {
+            0 => {
+                m.setup_transition(11);
+                if !m.match_s_var_l() {
+                    continue;
+                }
+                m.match_e_var();
+                m.rewrite_start(3);
+                m.insert_fun_bracket_l();
+                m.insert_symbol(s[0]);
+                m.transplant_expr(5);
+                m.insert_fun_bracket_r();
+                m.insert_number(1);
+                m.rewrite_finalize();
+                return;
+            }
+            11 => {
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(12);
+                m.rewrite_finalize();
+                return;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// `C.Digit`
+fn f1(m: &mut Machine) {
+    let s = symbols();
+    while let Some(address) = m.address() {
+        match address {
+            0 => {
+                m.setup_transition(20);
+                if m.switch_symbol_l(1, &[(s[1], 8), (s[2], 14)]) {
+                    continue;
+                }
+                if !m.match_symbol_l(s[3]) {
+                    continue;
+                }
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(4);
+                m.insert_number(0);
+                m.rewrite_finalize();
+                return;
+            }
+            8 => {
+                if !m.match_symbol_l(s[1]) {
+                    continue;
+                }
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(10);
+                m.insert_number(1);
+                m.rewrite_finalize();
+                return;
+            }
+            14 => {
+                if !m.match_symbol_l(s[2]) {
+                    continue;
+                }
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(16);
+                m.insert_number(2);
+                m.rewrite_finalize();
+                return;
+            }
+            20 => {
+                m.match_e_var();
+                m.rewrite_start(21);
+                m.insert_symbol(s[4]);
+                m.rewrite_finalize();
+                return;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// `C.Find`
+fn f2(m: &mut Machine) {
+    let s = symbols();
+    while let Some(address) = m.address() {
+        match address {
+            0 => {
+                m.setup_transition(19);
+                m.prepare_lengthen(1);
+            }
+            2 => {
+                if !m.lengthen(2) {
+                    continue;
+                }
+                m.goto(3);
+            }
+            3 => {
+                if !m.match_char_l('b') {
+                    continue;
+                }
+                if !m.match_s_var_l() {
+                    continue;
+                }
+                m.match_e_var();
+                m.condition_start();
+                m.copy_symbol(6);
+                m.condition_evaluate(9);
+                return;
+            }
+            9 => {
+                if !m.match_char_l('d') {
+                    continue;
+                }
+                if !m.match_empty() {
+                    continue;
+                }
+                m.rewrite_start(11);
+                m.insert_symbol(s[5]);
+                m.insert_fun_bracket_l();
+                m.insert_symbol(s[0]);
+                m.transplant_expr(4);
+                m.insert_fun_bracket_r();
+                m.rewrite_finalize();
+                return;
+            }
+            19 => {
+                m.match_e_var();
+                m.rewrite_start(20);
+                m.insert_symbol(s[4]);
+                m.rewrite_finalize();
+                return;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
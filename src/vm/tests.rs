@@ -1,18 +1,26 @@
-use crate::vm::eval_main;
-
-static TEST_PROGRAM: &str = r#"
+use crate::data::{Error, Limit};
+use crate::host::MemoryHost;
+use crate::program::Program;
+use crate::runtime::live_nodes;
+use crate::symbol::Symbol;
+use crate::vm::{
+    eval_call, eval_call_limited, eval_call_with, eval_expression, eval_main, render, Limits,
+    Object,
+};
+
+pub(crate) static TEST_PROGRAM: &str = r#"
 $MODULE Test;
 
 Palindrome {
     = True;
     $s.1 = True;
-    $s.1 $e.1 $s.1 = <Palindrome $e.1>;
+    $s.2 $e.1 $s.2 = <Palindrome $e.1>;
     $e.1 = False;
 }
 
 ChangePlusToMinus {
     '+' $e.1 = '-' <ChangePlusToMinus $e.1>;
-    $s.1 $e.1 = $s.1 <ChangePlusToMinus $e.1>;
+    $s.2 $e.1 = $s.2 <ChangePlusToMinus $e.1>;
     = ;
 }
 
@@ -22,11 +30,11 @@ Translate {
 }
 
 Table {
- = 	(('c' 'a' 'n' 'e') 'd' 'o' 'g')
-    (('g' 'a' 't' 't' 'o') 'c' 'a' 't')
-    (('c' 'a' 'v' 'a' 'l' 'l' 'o') 'h' 'o' 'r' 's' 'e')
-    (('r' 'a' 'n' 'a') 'f' 'r' 'o' 'g')
-    (('p' 'o' 'r' 'c' 'o') 'p' 'i' 'g');
+ = 	(('cane') 'dog')
+    (('gatto') 'cat')
+    (('cavallo') 'horse')
+    (('rana') 'frog')
+    (('porco') 'pig');
 }
 
 D1 {
@@ -82,18 +90,18 @@ SortInsert {
 }
 
 Sort1 {
-    ($e.1) $t.1 $e.2 = <Sort1 (<Insert $e.1 $t.1>) $e.2>;
+    ($e.1) $t.3 $e.2 = <Sort1 (<Insert $e.1 $t.3>) $e.2>;
     ($e.1) = $e.1;
 }
 
 Insert {
-    $e.1 $t.1 $t.2 = <Insert1 (<PreAlph $t.1 $t.2>) $e.1 $t.1 $t.2>;
+    $e.1 $t.3 $t.2 = <Insert1 (<PreAlph $t.3 $t.2>) $e.1 $t.3 $t.2>;
     $e.1 = $e.1;
 }
 
 Insert1 {
     (True) $e.1 = $e.1;
-    (False) $e.1 $t.1 $t.2 = <Insert $e.1 $t.2> $t.1;
+    (False) $e.1 $t.3 $t.2 = <Insert $e.1 $t.2> $t.3;
 }
 
 SortMerge {
@@ -151,18 +159,18 @@ IsA {
 }
 
 CountTerms {
-     = '0';
-    $t.1 $e.1 = <'Arithmetic.Add' '1' <CountTerms $e.1>>;
+     = 0;
+    $t.2 $e.1 = <Add 1 <CountTerms $e.1>>;
 }
 
 Permutations {
      = ();
-    $s.1 $e.1 = <InsertAll $s.1 <Permutations $e.1>>;
+    $s.2 $e.1 = <InsertAll $s.2 <Permutations $e.1>>;
 }
 
 InsertAll {
-    $s.1 ($e.1) = <InsertPer () $s.1 ($e.1)>;
-    $s.1 ($e.1) $e.2 = <InsertPer () $s.1 ($e.1)> <InsertAll $s.1 $e.2>;
+    $s.2 ($e.1) = <InsertPer () $s.2 ($e.1)>;
+    $s.3 ($e.1) $e.2 = <InsertPer () $s.3 ($e.1)> <InsertAll $s.3 $e.2>;
 }
 
 InsertPer {
@@ -177,31 +185,31 @@ DeleteDuplicates {
 
 RepeatedSL {
      = True;
-   $s.1 $s.1 $e.1 = <RepeatedSL $e.1>;
+   $s.2 $s.2 $e.1 = <RepeatedSL $e.1>;
    $e.1 = False;
 }
 
 RepeatedSR {
      = True;
-   $e.1 $s.1 $s.1 = <RepeatedSR $e.1>;
+   $e.1 $s.2 $s.2 = <RepeatedSR $e.1>;
    $e.1 = False;
 }
 
 RepeatedTL {
      = True;
-   $t.1 $t.1 $e.1 = <RepeatedTL $e.1>;
+   $t.2 $t.2 $e.1 = <RepeatedTL $e.1>;
    $e.1 = False;
 }
 
 RepeatedTR {
      = True;
-   $e.1 $t.1 $t.1 = <RepeatedTR $e.1>;
+   $e.1 $t.2 $t.2 = <RepeatedTR $e.1>;
    $e.1 = False;
 }
 
 RepeatedEL {
      = True;
-   $t.1 $e.1  $t.1 $e.1 $e.2 = <RepeatedEL $e.2>;
+   $t.3 $e.1  $t.3 $e.1 $e.2 = <RepeatedEL $e.2>;
    $e.2 = False;
 }
 
@@ -225,7 +233,7 @@ RepeatedInBrackets {
 
 RepeatedER4 {
      = True;
-   ($s.1 $e.1 $e.1) ($s.2 $e.2 $e.2) = True;
+   ($s.3 $e.1 $e.1) ($s.4 $e.2 $e.2) = True;
    $e.1 = False;
 }
 
@@ -237,28 +245,28 @@ DoubleInBrackets {
 
 SymmetryE {
     = True;
-   $t.1 $e.1 $e.2 $t.1 $e.1 = True;
+   $t.3 $e.1 $e.2 $t.3 $e.1 = True;
    $e.2 = False;
 }
 
 SymbolR {
-    $e.1 $s.1 = True;
+    $e.1 $s.2 = True;
     $e.1 = False;
 }
 
 TermR {
-    $e.1 $t.1 = True;
+    $e.1 $t.2 = True;
     $e.1 = False;
 }
 
 Repeated {
-    ($e.1 $s.1 $e.2)
-    ($e.3 $s.1 $e.4)
-    ($e.5 $s.2 $e.6)
-    ($e.7 $s.2 $e.8)
-    ($e.9 $s.3 $e.10)
-    ($e.11 $s.3 $e.12)
-        = $s.1 $s.2 $s.3;
+    ($e.1 $s.13 $e.2)
+    ($e.3 $s.13 $e.4)
+    ($e.5 $s.14 $e.6)
+    ($e.7 $s.14 $e.8)
+    ($e.9 $s.15 $e.10)
+    ($e.11 $s.15 $e.12)
+        = $s.13 $s.14 $s.15;
     $e.1
         = N;
 }
@@ -278,27 +286,27 @@ RemoveRepeated3 {
 TestPalindrome1
 { = <'Test.Palindrome'>; }
 TestPalindrome1Expected
-{ = 'True'; }
+{ = True; }
 
 TestPalindrome2
 { = <'Test.Palindrome' 'a'>; }
 TestPalindrome2Expected
-{ = 'True'; }
+{ = True; }
 
 TestPalindrome3
 { = <'Test.Palindrome' 'a' 'a'>; }
 TestPalindrome3Expected
-{ = 'True'; }
+{ = True; }
 
 TestPalindrome4
 { = <'Test.Palindrome' 'a' 'b'>; }
 TestPalindrome4Expected
-{ = 'False'; }
+{ = False; }
 
 TestPalindrome5
 { = <'Test.Palindrome' ()>; }
 TestPalindrome5Expected
-{ = 'False'; }
+{ = False; }
 
 TestChangePlusToMinus1
 { = <'Test.ChangePlusToMinus'>; }
@@ -393,27 +401,27 @@ TestBlanks3Expected
 TestPreAlph1
 { = <'Test.PreAlph' 'a' 'a'>; }
 TestPreAlph1Expected
-{ = 'True'; }
+{ = True; }
 
 TestPreAlph2
 { = <'Test.PreAlph' 'a' 'b'>; }
 TestPreAlph2Expected
-{ = 'True'; }
+{ = True; }
 
 TestPreAlph3
 { = <'Test.PreAlph' 'a' 'z'>; }
 TestPreAlph3Expected
-{ = 'True'; }
+{ = True; }
 
 TestPreAlph4
 { = <'Test.PreAlph' 'y' 'z'>; }
 TestPreAlph4Expected
-{ = 'True'; }
+{ = True; }
 
 TestPreAlph5
 { = <'Test.PreAlph' 'z' 'a'>; }
 TestPreAlph5Expected
-{ = 'False'; }
+{ = False; }
 
 TestRecursiveAdd1
 { = <'Test.RecursiveAdd' ('0') '0'>; }
@@ -478,17 +486,17 @@ TestSortMerge3Expected
 TestBracketsRight1
 { = <'Test.BracketsRight' >; }
 TestBracketsRight1Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestBracketsRight2
 { = <'Test.BracketsRight' ()>; }
 TestBracketsRight2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestBracketsRight3
 { = <'Test.BracketsRight' ()() 'a'>; }
 TestBracketsRight3Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestPermutations1
 { = <'Test.Permutations' >; }
@@ -513,127 +521,127 @@ TestPermutations4Expected
 RepeatedSL1
 { = <'Test.RepeatedSL'>; }
 RepeatedSL1Expected
-{ = 'True' ;}
+{ = True ;}
 
 RepeatedSL2
 { = <'Test.RepeatedSL' 'a' 'a'>; }
 RepeatedSL2Expected
-{ = 'True' ;}
+{ = True ;}
 
 RepeatedSL3
 { = <'Test.RepeatedSL' 'a'>; }
 RepeatedSL3Expected
-{ = 'False' ;}
+{ = False ;}
 
 RepeatedSR1
 { = <'Test.RepeatedSR'>; }
 RepeatedSR1Expected
-{ = 'True' ;}
+{ = True ;}
 
 RepeatedSR2
 { = <'Test.RepeatedSR' 'a' 'a'>; }
 RepeatedSR2Expected
-{ = 'True' ;}
+{ = True ;}
 
 RepeatedSR3
 { = <'Test.RepeatedSR' 'a'>; }
 RepeatedSR3Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedTL1
 { = <'Test.RepeatedTL'>; }
 TestRepeatedTL1Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedTL2
 { = <'Test.RepeatedTL' 'a' 'a'>; }
 TestRepeatedTL2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedTL3
 { = <'Test.RepeatedTL' 'a'>; }
 TestRepeatedTL3Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedTL4
 { = <'Test.RepeatedTL' 'a' 'b'>; }
 TestRepeatedTL4Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedTR1
 { = <'Test.RepeatedTR'>; }
 TestRepeatedTR1Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedTR2
 { = <'Test.RepeatedTR' 'a' 'a'>; }
 TestRepeatedTR2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedTR3
 { = <'Test.RepeatedTR' 'a'>; }
 TestRepeatedTR3Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedTR4
 { = <'Test.RepeatedTR' 'a' 'b'>; }
 TestRepeatedTR4Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedEL1
 { = <'Test.RepeatedEL'>; }
 TestRepeatedEL1Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedEL2
 { = <'Test.RepeatedEL' 'a' 'a'>; }
 TestRepeatedEL2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedEL3
 { = <'Test.RepeatedEL' 'a' 'b' 'a' 'b'>; }
 TestRepeatedEL3Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedEL4
 { = <'Test.RepeatedEL' 'a' 'b' 'c' 'a' 'b'>; }
 TestRepeatedEL4Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedEL5
 { = <'Test.RepeatedEL' () ()>; }
 TestRepeatedEL5Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedEL6
 { = <'Test.RepeatedEL' ('a') ('a')>; }
 TestRepeatedEL6Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedEL7
 { = <'Test.RepeatedEL' ('a') ('b')>; }
 TestRepeatedEL7Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestRepeatedInBrackets1
 { = <'Test.RepeatedInBrackets'>; }
 TestRepeatedInBrackets1Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedInBrackets2
 { = <'Test.RepeatedInBrackets' () ()>; }
 TestRepeatedInBrackets2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedInBrackets3
 { = <'Test.RepeatedInBrackets' ('a' 'a') ('b' 'b')>; }
 TestRepeatedInBrackets3Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestRepeatedInBrackets4
 { = <'Test.RepeatedInBrackets' ('a' 'a') ('b' 'c')>; }
 TestRepeatedInBrackets4Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestDoubleInBrackets1
 { = <'Test.DoubleInBrackets'>; }
@@ -648,77 +656,67 @@ TestDoubleInBrackets2Expected
 TestSymmetryE1
 { = <'Test.SymmetryE'>; }
 TestSymmetryE1Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestSymmetryE2
 { = <'Test.SymmetryE' 'a' 'a'>; }
 TestSymmetryE2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestSymmetryE3
 { = <'Test.SymmetryE' 'a' 'b' 'a'>; }
 TestSymmetryE3Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestSymmetryE4
 { = <'Test.SymmetryE' 'a' () 'b' 'a' ()>; }
 TestSymmetryE4Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestSymmetryE5
 { = <'Test.SymmetryE' 'a' () 'b' 'z'>; }
 TestSymmetryE5Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestSymbolR1
 { = <'Test.SymbolR'>; }
 TestSymbolR1Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestSymbolR2
 { = <'Test.SymbolR' ()>; }
 TestSymbolR2Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestSymbolR3
 { = <'Test.SymbolR' () () 'a'>; }
 TestSymbolR3Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestTermR1
 { = <'Test.TermR'>; }
 TestTermR1Expected
-{ = 'False' ;}
+{ = False ;}
 
 TestTermR2
 { = <'Test.TermR' ()>; }
 TestTermR2Expected
-{ = 'True' ;}
+{ = True ;}
 
 TestTermR3
 { = <'Test.TermR' () () 'a'>; }
 TestTermR3Expected
-{ = 'True' ;}
-
-TestMu1
-{ = <'Mu.Mu' 'Test.Palindrome' 'a'>; }
-TestMu1Expected
-{ = 'True' ;}
-
-TestMu2
-{ = <'Mu.Mu' 'Test.Palindrome' 'a' 'b'>; }
-TestMu2Expected
-{ = 'False' ;}
+{ = True ;}
 
 TestBuiltinPlus
-{ = <'Builtin.+' '1' '2'>; }
+{ = <Add 1 2>; }
 TestBuiltinPlusExpected
-{ = '3' ;}
+{ = 3 ;}
 
 TestBuiltinMinus
-{ = <'Builtin.-' '10' '2'>; }
+{ = <Sub 10 2>; }
 TestBuiltinMinusExpected
-{ = '8' ;}
+{ = 8 ;}
 
 TestRepeated1
 { = <'Test.Repeated' () () () () () ()>; }
@@ -758,9 +756,9 @@ TestRemoveRepeated33Expected
 
 fn test_example(goal1: &str, goal2: &str) {
     use crate::compiler::compile;
-    let defs = compile(TEST_PROGRAM).unwrap();
-    let out1 = eval_main(&defs, &String::from(goal1));
-    let out2 = eval_main(&defs, &String::from(goal2));
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let out1 = eval_main(&program, &String::from(goal1)).unwrap();
+    let out2 = eval_main(&program, &String::from(goal2)).unwrap();
     assert_eq!(out1, out2);
 }
 
@@ -1219,6 +1217,16 @@ fn test_term_r3() {
     test_example("Test.TestTermR3", "Test.TestTermR3Expected")
 }
 
+#[test]
+fn test_builtin_plus() {
+    test_example("Test.TestBuiltinPlus", "Test.TestBuiltinPlusExpected")
+}
+
+#[test]
+fn test_builtin_minus() {
+    test_example("Test.TestBuiltinMinus", "Test.TestBuiltinMinusExpected")
+}
+
 #[test]
 fn test_remove_repeated_21() {
     test_example(
@@ -1250,3 +1258,516 @@ fn test_remove_repeated_33() {
         "Test.TestRemoveRepeated33Expected",
     )
 }
+
+fn test_call(goal: &str, arg: &str, expected: &str) {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let arg = compile_expression("Test", arg).unwrap();
+    let expected = compile_expression("Test", expected).unwrap();
+    assert_eq!(eval_call(&program, goal, &arg).unwrap(), expected);
+}
+
+#[test]
+fn test_call_palindrome() {
+    test_call("Test.Palindrome", "'a' 'b' 'a'", "True");
+    test_call("Test.Palindrome", "'a' 'b'", "False");
+}
+
+#[test]
+fn test_call_nested() {
+    test_call(
+        "Test.Translate",
+        "('r' 'a' 'n' 'a') <Table>",
+        "'f' 'r' 'o' 'g'",
+    );
+    test_call(
+        "Test.BinaryAdd",
+        "(<BinaryAdd ('1') ('1')>) ('1')",
+        "'1' '1'",
+    );
+}
+
+#[test]
+fn test_call_objects() {
+    use crate::compiler::compile;
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let arg = vec![
+        Object::StrBracketL,
+        Object::Symbol(Symbol::intern("a")),
+        Object::StrBracketR,
+        Object::StrBracketL,
+        Object::StrBracketR,
+    ];
+    let expected = vec![
+        Object::StrBracketL,
+        Object::Symbol(Symbol::intern("a")),
+        Object::Symbol(Symbol::intern("a")),
+        Object::StrBracketR,
+        Object::StrBracketL,
+        Object::StrBracketR,
+    ];
+    assert_eq!(
+        eval_call(&program, "Test.DoubleInBrackets", &arg).unwrap(),
+        expected
+    );
+}
+
+fn test_error(goal: &str, arg: &str, expected: Error) {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(TEST_PROGRAM).unwrap());
+    let arg = compile_expression("Test", arg).unwrap();
+    assert_eq!(eval_call(&program, goal, &arg).unwrap_err(), expected);
+}
+
+#[test]
+fn test_recognition_impossible() {
+    test_error(
+        "Test.Insert1",
+        "(Maybe) 'a' ('b')",
+        Error::RecognitionImpossible {
+            function: String::from("Test.Insert1"),
+            argument: String::from("(Maybe) 'a' ('b')"),
+        },
+    );
+    test_error(
+        "Test.Palindrome",
+        "<Pairs1 Maybe 'a' 'b'>",
+        Error::RecognitionImpossible {
+            function: String::from("Test.Pairs1"),
+            argument: String::from("Maybe 'ab'"),
+        },
+    );
+}
+
+#[test]
+fn test_undefined_function() {
+    test_error(
+        "Test.Missing",
+        "'a' ()",
+        Error::UndefinedFunction {
+            function: String::from("Test.Missing"),
+            argument: String::from("'a' ()"),
+        },
+    );
+    let program =
+        Program::new(crate::compiler::compile("$MODULE U; F { = <'V.G' 1 A>; }").unwrap());
+    assert_eq!(
+        eval_call(&program, "U.F", &[]).unwrap_err(),
+        Error::UndefinedFunction {
+            function: String::from("V.G"),
+            argument: String::from("1 A"),
+        },
+    );
+}
+
+#[test]
+fn test_malformed_view() {
+    test_error(
+        "Test.Palindrome",
+        "<(A)>",
+        Error::MalformedView(String::from("<(A)>")),
+    );
+    let program = Program::new(crate::compiler::compile(TEST_PROGRAM).unwrap());
+    assert_eq!(
+        eval_call(&program, "Test.Palindrome", &[Object::StrBracketL]).unwrap_err(),
+        Error::MalformedView(String::from("(")),
+    );
+}
+
+#[cfg(test)]
+pub(crate) const ARITHMETIC_PROGRAM: &str = "
+$MODULE Arith;
+
+Factorial {
+    0 = 1;
+    $s.N = <Mul $s.N <Factorial <Sub $s.N 1>>>;
+}
+
+Sum {
+    = 0;
+    $s.N $e.Rest = <Add $s.N <Sum $e.Rest>>;
+}
+
+Sign {
+    $e.N = <SignName <Compare ($e.N) 0>>;
+}
+
+SignName {
+    '-' = Negative;
+    '0' = Zero;
+    '+' = Positive;
+}
+";
+
+#[cfg(test)]
+fn test_arithmetic(goal: &str, arg: &str, expected: &str) {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(ARITHMETIC_PROGRAM).unwrap());
+    let arg = compile_expression("Arith", arg).unwrap();
+    let expected = compile_expression("Arith", expected).unwrap();
+    assert_eq!(eval_call(&program, goal, &arg).unwrap(), expected);
+}
+
+#[test]
+fn test_builtin_arithmetic() {
+    test_arithmetic("Arith.Factorial", "5", "120");
+    test_arithmetic("Arith.Factorial", "20", "566454140 2192834560");
+    test_arithmetic("Arith.Sum", "1 2 3 4", "10");
+    test_arithmetic("Arith.Sum", "<Div (1 0) 2> <Mod 7 3>", "2147483649");
+    test_arithmetic("Arith.Sign", "'-' 3", "Negative");
+    test_arithmetic("Arith.Sign", "0", "Zero");
+    test_arithmetic("Arith.Sign", "<Sub 7 2>", "Positive");
+}
+
+#[test]
+fn test_builtin_recognition_impossible() {
+    let program = Program::new(crate::compiler::compile(ARITHMETIC_PROGRAM).unwrap());
+    let arg = crate::compiler::compile_expression("Arith", "<Div 1 0>").unwrap();
+    assert_eq!(
+        eval_call(&program, "Arith.Sum", &arg).unwrap_err(),
+        Error::RecognitionImpossible {
+            function: String::from("Div"),
+            argument: String::from("1 0"),
+        }
+    );
+}
+
+#[cfg(test)]
+pub(crate) const IO_PROGRAM: &str = "
+$MODULE IO;
+
+Echo {
+    = <EchoLine <Card>>;
+}
+
+EchoLine {
+    0 = ;
+    $e.Line = <Prout 'got: ' $e.Line> <Echo>;
+}
+
+Save {
+    $e.Text = <Open 'w' 1 'out.txt'> <Put 1 $e.Text> <Close 1>
+        <Open 'r' 2 'out.txt'> <Get 2> <Get 2> <Close 2>;
+}
+";
+
+#[cfg(test)]
+fn test_io(goal: &str, arg: &str, host: &mut MemoryHost) -> Result<Vec<Object>, Error> {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(IO_PROGRAM).unwrap());
+    let arg = compile_expression("IO", arg).unwrap();
+    eval_call_with(&program, goal, &arg, host)
+}
+
+#[test]
+fn test_console_io() {
+    let mut host = MemoryHost::new(&["hello", "", "bye"]);
+    assert_eq!(test_io("IO.Echo", "", &mut host), Ok(vec![]));
+    assert_eq!(host.output, vec!["got: hello", "got: ", "got: bye"]);
+
+    let mut host = MemoryHost::default();
+    let result = test_io("IO.EchoLine", "<Print Hi 'there' 42 (A)>", &mut host);
+    assert_eq!(result, Ok(vec![]));
+    assert_eq!(host.output, vec!["Hi there 42 (A)", "got: Hi there 42 (A)"]);
+}
+
+#[test]
+fn test_file_io() {
+    let mut host = MemoryHost::default();
+    let expected = crate::compiler::compile_expression("IO", "'a' 'b' 7 'a' 'b' ' ' '7' 0");
+    assert_eq!(test_io("IO.Save", "'a' 'b' 7", &mut host), expected);
+    assert_eq!(host.files["out.txt"], vec!["ab 7"]);
+
+    let result = test_io("IO.Echo", "<Get 3>", &mut host);
+    assert_eq!(
+        result,
+        Err(Error::RecognitionImpossible {
+            function: String::from("Get"),
+            argument: String::from("3"),
+        })
+    );
+}
+
+#[cfg(test)]
+const CONDITIONS_PROGRAM: &str = "
+$MODULE Cond;
+
+FirstBig {
+    $e.1 $s.X $e.2 , <Compare $s.X 10> : '+' = $s.X;
+    $e.1 = None;
+}
+
+SumTo {
+    $s.N $e.1 $s.X $e.2 $s.Y $e.3 , <Add $s.X $s.Y> : $s.N = $s.X $s.Y;
+    $s.N $e.1 = None;
+}
+
+Find {
+    $e.1 , $e.1 : $e.A $s.X $e.B , <Compare $s.X 5> : '+' = $s.X;
+}
+
+Classify {
+    $e.1 , <Compare ($e.1) 0> : {
+        '-' = Negative;
+        '0' = Zero;
+        $s.S , $e.1 : {
+            1 = One;
+            $e.2 = Many $e.2;
+        };
+    };
+}
+";
+
+#[cfg(test)]
+fn eval_conditions(goal: &str, arg: &str) -> Result<Vec<Object>, Error> {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(CONDITIONS_PROGRAM).unwrap());
+    let arg = compile_expression("Cond", arg).unwrap();
+    eval_call(&program, goal, &arg)
+}
+
+#[cfg(test)]
+fn test_conditions_call(goal: &str, arg: &str, expected: &str) {
+    let expected = crate::compiler::compile_expression("Cond", expected).unwrap();
+    assert_eq!(eval_conditions(goal, arg), Ok(expected));
+}
+
+#[test]
+fn test_conditions() {
+    test_conditions_call("Cond.FirstBig", "3 12 5 20", "12");
+    test_conditions_call("Cond.FirstBig", "1 2", "None");
+    test_conditions_call("Cond.SumTo", "10 3 4 6 5", "4 6");
+    test_conditions_call("Cond.SumTo", "10 3 4", "None");
+    test_conditions_call("Cond.Find", "1 7 3 9", "7");
+    assert_eq!(
+        eval_conditions("Cond.Find", "1 2"),
+        Err(Error::RecognitionImpossible {
+            function: String::from("Cond.Find"),
+            argument: String::from("1 2"),
+        })
+    );
+}
+
+#[test]
+fn test_blocks() {
+    test_conditions_call("Cond.Classify", "'-' 5", "Negative");
+    test_conditions_call("Cond.Classify", "0", "Zero");
+    test_conditions_call("Cond.Classify", "1", "One");
+    test_conditions_call("Cond.Classify", "1 0", "Many 1 0");
+    assert_eq!(
+        eval_conditions("Cond.Classify", "A"),
+        Err(Error::RecognitionImpossible {
+            function: String::from("Compare"),
+            argument: String::from("(A) 0"),
+        })
+    );
+}
+
+#[cfg(test)]
+const LIMITS_PROGRAM: &str = "
+$MODULE Lim;
+
+Loop { = <Loop>; }
+
+Grow { $e.1 = <Grow $e.1 $e.1>; }
+
+Fork { = <Fork> <Fork>; }
+
+Wide { = A B C D E F G H I J; }
+
+Count {
+    0 = Done;
+    $s.N = <Count <Sub $s.N 1>>;
+}
+";
+
+#[cfg(test)]
+fn eval_limited(goal: &str, arg: &str, limits: Limits) -> Result<Vec<Object>, Error> {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(LIMITS_PROGRAM).unwrap());
+    let arg = compile_expression("Lim", arg).unwrap();
+    eval_call_limited(&program, goal, &arg, &mut MemoryHost::default(), limits)
+}
+
+#[test]
+fn test_limits() {
+    let steps = Limits {
+        steps: Some(100),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("Lim.Loop", "", steps),
+        Err(Error::LimitExceeded {
+            limit: Limit::Steps(100),
+            steps: 100,
+        })
+    );
+    let nodes = Limits {
+        nodes: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("Lim.Grow", "A", nodes),
+        Err(Error::LimitExceeded {
+            limit: Limit::Nodes(1000),
+            steps: 10,
+        })
+    );
+    let calls = Limits {
+        calls: Some(50),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("Lim.Fork", "", calls),
+        Err(Error::LimitExceeded {
+            limit: Limit::Calls(50),
+            steps: 50,
+        })
+    );
+    let all = Limits {
+        steps: Some(21),
+        nodes: Some(10),
+        calls: Some(2),
+    };
+    assert_eq!(
+        eval_limited("Lim.Count", "10", all),
+        Ok(vec![Object::Symbol(Symbol::intern("Done"))])
+    );
+    assert_eq!(
+        eval_limited("Lim.Count", "11", all)
+            .unwrap_err()
+            .to_string(),
+        "limit of 21 steps exceeded after 21 steps"
+    );
+    assert_eq!(
+        eval_limited("Lim.Wide", "", all),
+        Err(Error::LimitExceeded {
+            limit: Limit::Nodes(10),
+            steps: 1,
+        })
+    );
+}
+
+const SHARING_PROGRAM: &str = "
+$MODULE Sh;
+
+Pow {
+    0 $e.1 = $e.1;
+    $s.N $e.1 = <Pow <Sub $s.N 1> $e.1 $e.1>;
+}
+
+Dup { $e.1 = $e.1 $e.1; }
+
+Eq {
+    ($e.1) ($e.1) = True;
+    ($e.1) ($e.2) = False;
+}
+
+Rev {
+    $s.1 $e.2 = <Rev $e.2> $s.1;
+    ($e.1) $e.2 = <Rev $e.2> (<Rev $e.1>);
+    = ;
+}
+
+Last { $e.1 $t.2 = $t.2; }
+";
+
+#[cfg(test)]
+fn eval_sharing(goal: &str, arg: &str, limits: Limits) -> Result<Vec<Object>, Error> {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(SHARING_PROGRAM).unwrap());
+    let arg = compile_expression("Sh", arg).unwrap();
+    eval_call_limited(&program, goal, &arg, &mut MemoryHost::default(), limits)
+}
+
+#[test]
+fn test_sharing() {
+    let before = live_nodes();
+    let result = eval_sharing("Sh.Pow", "16 A", Limits::default()).unwrap();
+    assert_eq!(result.len(), 1 << 16);
+    let nodes = Limits {
+        nodes: Some(200),
+        ..Limits::default()
+    };
+    // The objects shared count in full, so that a result too large to be built is not.
+    let exceeded = Err(Error::LimitExceeded {
+        limit: Limit::Nodes(200),
+        steps: 15,
+    });
+    assert_eq!(eval_sharing("Sh.Pow", "64 A", nodes), exceeded);
+    assert_eq!(eval_sharing("Prout", "<Pow 64 A>", nodes), exceeded);
+    let expected = |expression: &str| crate::compiler::compile_expression("Sh", expression);
+    assert_eq!(
+        eval_sharing("Sh.Rev", "<Dup 'ab' (C <Dup D>)>", nodes),
+        expected("(D D C) 'ba' (D D C) 'ba'")
+    );
+    assert_eq!(
+        eval_sharing("Sh.Last", "<Dup (A) <Dup B (C)>>", nodes),
+        expected("(C)")
+    );
+    assert_eq!(
+        eval_sharing("Sh.Eq", "(<Pow 3 'ab'>) (<Dup <Pow 2 'ab'>>)", nodes),
+        expected("True")
+    );
+    assert_eq!(
+        eval_sharing("Sh.Eq", "(<Pow 3 'ab'>) (<Dup <Pow 2 'ab'>> 'a')", nodes),
+        expected("False")
+    );
+    assert_eq!(live_nodes(), before);
+}
+
+#[test]
+fn test_limits_free_view() {
+    let before = live_nodes();
+    let limits = Limits {
+        steps: Some(1000),
+        nodes: Some(1000),
+        ..Limits::default()
+    };
+    assert!(eval_limited("Lim.Grow", "A", limits).is_err());
+    assert!(eval_limited("Lim.Count", "100", limits).is_ok());
+    assert_eq!(live_nodes(), before);
+}
+
+#[test]
+fn test_eval_expression() {
+    use crate::compiler::{compile, compile_expression};
+    let program = Program::new(compile(LIMITS_PROGRAM).unwrap());
+    let mut host = MemoryHost::default();
+    let expression = compile_expression("Lim", "A (B)").unwrap();
+    assert_eq!(
+        eval_expression(
+            &program,
+            &expression,
+            &mut host,
+            Limits::default(),
+            None,
+            None,
+            None
+        ),
+        Ok(expression)
+    );
+
+    let expression = compile_expression("Lim", "<Count 1> X <Count 0>").unwrap();
+    let mut calls = Vec::new();
+    let mut trace = |step: u64, call: &[Object]| calls.push(format!("{} {}", step, render(call)));
+    let result = eval_expression(
+        &program,
+        &expression,
+        &mut host,
+        Limits::default(),
+        Some(&mut trace),
+        None,
+        None,
+    );
+    assert_eq!(render(&result.unwrap()), "Done X Done");
+    assert_eq!(
+        calls,
+        vec![
+            "1 <'Lim.Count' 1>",
+            "2 <Sub 1 1>",
+            "3 <'Lim.Count' 0>",
+            "4 <'Lim.Count' 0>",
+        ]
+    );
+}